@@ -7,6 +7,7 @@ use oom_guard::config::{Args, Config};
 use oom_guard::killer::ProcessSelector;
 use oom_guard::monitor::{MemInfo, ProcessInfo};
 
+#[allow(clippy::too_many_lines)]
 fn main() -> Result<()> {
     // Initialize logging
     env_logger::Builder::from_default_env()
@@ -76,10 +77,10 @@ fn main() -> Result<()> {
     let swap_below_kill = mem_info.is_swap_below_threshold(config.swap_threshold_kill);
 
     println!("Threshold Status:");
-    println!("  Memory below warn threshold: {}", mem_below_warn);
-    println!("  Memory below kill threshold: {}", mem_below_kill);
-    println!("  Swap below warn threshold: {}", swap_below_warn);
-    println!("  Swap below kill threshold: {}", swap_below_kill);
+    println!("  Memory below warn threshold: {mem_below_warn}");
+    println!("  Memory below kill threshold: {mem_below_kill}");
+    println!("  Swap below warn threshold: {swap_below_warn}");
+    println!("  Swap below kill threshold: {swap_below_kill}");
     println!();
 
     // Read process information
@@ -93,7 +94,7 @@ fn main() -> Result<()> {
     println!("{}", "-".repeat(50));
 
     // Create process selector
-    let selector = ProcessSelector::new(config.clone());
+    let selector = ProcessSelector::new(config);
 
     // Get statistics
     let stats = selector.get_statistics(&processes);
@@ -114,17 +115,16 @@ fn main() -> Result<()> {
         println!("  RSS: {}", MemInfo::format_size(victim.rss_kb));
         println!("  OOM Score: {}", victim.oom_score);
         println!("  UID: {}", victim.uid);
-        println!();
     } else {
         println!("No suitable victim found (all processes are protected)");
-        println!();
     }
+    println!();
 
     // Show top 10 processes by memory usage
     println!("Top 10 Processes by RSS:");
     println!("{}", "-".repeat(50));
     let mut sorted = processes.clone();
-    sorted.sort_by(|a, b| b.rss_kb.cmp(&a.rss_kb));
+    sorted.sort_by_key(|p| std::cmp::Reverse(p.rss_kb));
 
     for (i, proc) in sorted.iter().take(10).enumerate() {
         println!(
@@ -142,7 +142,7 @@ fn main() -> Result<()> {
     println!("Top 10 Processes by OOM Score:");
     println!("{}", "-".repeat(50));
     let mut by_oom = processes;
-    by_oom.sort_by(|a, b| b.oom_score.cmp(&a.oom_score));
+    by_oom.sort_by_key(|p| std::cmp::Reverse(p.oom_score));
 
     for (i, proc) in by_oom.iter().take(10).enumerate() {
         println!(
@@ -1,6 +1,7 @@
 // Command-line argument parsing
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
 
 /// OOM Guard - Memory monitor and process killer
 ///
@@ -31,6 +32,16 @@ pub struct Args {
     #[arg(short = 'S', long = "swap-size", value_name = "SIZE[,KILL_SIZE]")]
     pub swap_size_kb: Option<String>,
 
+    /// Leave zram devices out of swap thresholds, as what they hold is in
+    /// RAM; with zram as the only swap, memory thresholds alone decide
+    #[arg(long = "zram-as-memory")]
+    pub zram_as_memory: bool,
+
+    /// When to act: all (memory and swap low, the default), any, mem-only,
+    /// or an expression like "mem < 5% && (swap < 10% || psi_full > 30)"
+    #[arg(long = "trigger", value_name = "all|any|mem-only|EXPR")]
+    pub trigger: Option<String>,
+
     /// Memory check interval in seconds (default: 1)
     #[arg(short = 'i', long = "interval", value_name = "SECONDS")]
     pub interval: Option<u64>,
@@ -55,6 +66,103 @@ pub struct Args {
     #[arg(short = 'g', long = "kill-group")]
     pub kill_group: bool,
 
+    /// Rank whole applications (a process and its children, up to session
+    /// and cgroup boundaries) by their summed memory and kill them entirely
+    #[arg(long = "kill-tree")]
+    pub kill_tree: bool,
+
+    /// Cooldown after a kill SECONDS[,KILL_SECONDS] (default: 10,5)
+    /// First value applies at the warn threshold, second at the kill threshold
+    #[arg(long = "cooldown", value_name = "SECONDS[,KILL_SECONDS]")]
+    pub cooldown: Option<String>,
+
+    /// Kill budget COUNT[,MINUTES]: stop killing and alert after COUNT kills
+    /// within MINUTES (default window: 60)
+    #[arg(long = "kill-budget", value_name = "COUNT[,MINUTES]")]
+    pub kill_budget: Option<String>,
+
+    /// At the warn threshold, first try to reclaim memory from the top candidates
+    /// (cgroup memory.reclaim or MADV_PAGEOUT) before sending any signal
+    #[arg(long = "proactive-reclaim")]
+    pub proactive_reclaim: bool,
+
+    /// Number of top candidates to reclaim memory from (default: 3)
+    #[arg(long = "proactive-reclaim-candidates", value_name = "COUNT")]
+    pub proactive_reclaim_candidates: Option<usize>,
+
+    /// Throttle the top candidate at the warn threshold by lowering memory.high
+    /// of its cgroup, before freezing or killing
+    #[arg(long = "throttle")]
+    pub throttle: bool,
+
+    /// memory.high to apply, as a percentage of the cgroup's current usage,
+    /// from 50 to below 100 (default: 90)
+    #[arg(long = "throttle-percent", value_name = "PERCENT")]
+    pub throttle_percent: Option<f64>,
+
+    /// Restore memory.high once available memory reaches PERCENT (default: 20)
+    #[arg(long = "throttle-release", value_name = "PERCENT")]
+    pub throttle_release: Option<f64>,
+
+    /// Script to run when a cgroup is throttled or its memory.high restored
+    #[arg(long = "throttle-script", value_name = "PATH")]
+    pub throttle_script: Option<String>,
+
+    /// Escalate to the regular warn action if memory has not recovered
+    /// SECONDS after throttling (default: 30)
+    #[arg(long = "throttle-timeout", value_name = "SECONDS")]
+    pub throttle_timeout: Option<u64>,
+
+    /// Freeze victims (cgroup.freeze or SIGSTOP) at the warn threshold instead of
+    /// sending SIGTERM; they are thawed when memory recovers
+    #[arg(long = "freeze")]
+    pub freeze: bool,
+
+    /// Available memory percentage at which frozen victims are thawed (default: 20)
+    #[arg(long = "freeze-thaw", value_name = "PERCENT")]
+    pub freeze_thaw: Option<f64>,
+
+    /// Kill frozen victims if memory has not recovered after SECONDS (default: 60)
+    #[arg(long = "freeze-timeout", value_name = "SECONDS")]
+    pub freeze_timeout: Option<u64>,
+
+    /// Respawn limit COUNT[,MINUTES]: after COUNT kills of the same program
+    /// within MINUTES (default: 10), choose other victims and alert (default: off)
+    #[arg(long = "respawn-limit", value_name = "COUNT[,MINUTES]")]
+    pub respawn_limit: Option<String>,
+
+    /// Script to run when a respawn loop is detected (e.g. to stop the unit)
+    #[arg(long = "respawn-script", value_name = "PATH")]
+    pub respawn_script: Option<String>,
+
+    /// Also watch the memory.events OOM kill counter of a cgroup, e.g.
+    /// /system.slice/postgresql.service (can be used multiple times)
+    #[arg(long = "watch-cgroup", value_name = "PATH")]
+    pub watch_cgroups: Vec<String>,
+
+    /// Script to run when the kernel OOM killer killed a process
+    #[arg(long = "kernel-oom-script", value_name = "PATH")]
+    pub kernel_oom_script: Option<String>,
+
+    /// Memory quota for each matching user, as uid=N[-M]:KIB, user=NAME:KIB
+    /// or group=NAME:KIB (can be used multiple times; the first match applies)
+    #[arg(long = "user-quota", value_name = "SELECTOR:KIB")]
+    pub user_quotas: Vec<String>,
+
+    /// Seconds a user may stay over quota before one of their processes
+    /// is killed (default: 30)
+    #[arg(long = "quota-grace", value_name = "SECONDS")]
+    pub quota_grace: Option<u64>,
+
+    /// At the kill threshold, kill as many processes as needed to recover to
+    /// the warn threshold plus this margin (percent of total memory)
+    #[arg(long = "reclaim-margin", value_name = "PERCENT")]
+    pub reclaim_margin: Option<f64>,
+
+    /// Maximum number of processes killed in one reclaim episode (default: 10)
+    #[arg(long = "reclaim-max-kills", value_name = "COUNT")]
+    pub reclaim_max_kills: Option<usize>,
+
     /// Set daemon priority (-20 to 19, lower = higher priority)
     #[arg(short = 'p', long = "set-priority", value_name = "PRIORITY")]
     pub priority: Option<i32>,
@@ -79,6 +187,115 @@ pub struct Args {
     #[arg(long = "ignore", value_name = "REGEX")]
     pub ignore: Vec<String>,
 
+    /// Prefer to kill processes whose resolved executable path matches this regex
+    #[arg(long = "prefer-exe", value_name = "REGEX")]
+    pub prefer_exe: Vec<String>,
+
+    /// Avoid killing processes whose resolved executable path matches this regex
+    #[arg(long = "avoid-exe", value_name = "REGEX")]
+    pub avoid_exe: Vec<String>,
+
+    /// Ignore processes whose resolved executable path matches this regex,
+    /// e.g. "^/usr/sbin/sshd$"
+    #[arg(long = "ignore-exe", value_name = "REGEX")]
+    pub ignore_exe: Vec<String>,
+
+    /// Prefer to kill processes whose user name matches this regex
+    #[arg(long = "prefer-user", value_name = "REGEX")]
+    pub prefer_user: Vec<String>,
+
+    /// Avoid killing processes whose user name matches this regex
+    #[arg(long = "avoid-user", value_name = "REGEX")]
+    pub avoid_user: Vec<String>,
+
+    /// Ignore processes whose user name matches this regex
+    #[arg(long = "ignore-user", value_name = "REGEX")]
+    pub ignore_user: Vec<String>,
+
+    /// Prefer to kill processes whose systemd unit matches this regex
+    #[arg(long = "prefer-unit", value_name = "REGEX")]
+    pub prefer_unit: Vec<String>,
+
+    /// Avoid killing processes whose systemd unit matches this regex
+    #[arg(long = "avoid-unit", value_name = "REGEX")]
+    pub avoid_unit: Vec<String>,
+
+    /// Ignore processes whose systemd unit matches this regex
+    #[arg(long = "ignore-unit", value_name = "REGEX")]
+    pub ignore_unit: Vec<String>,
+
+    /// Prefer to kill processes whose container ID matches this regex
+    #[arg(long = "prefer-container", value_name = "REGEX")]
+    pub prefer_container: Vec<String>,
+
+    /// Avoid killing processes whose container ID matches this regex
+    #[arg(long = "avoid-container", value_name = "REGEX")]
+    pub avoid_container: Vec<String>,
+
+    /// Ignore processes whose container ID matches this regex
+    #[arg(long = "ignore-container", value_name = "REGEX")]
+    pub ignore_container: Vec<String>,
+
+    /// Per-process rule "MATCHERS => ACTIONS" (can be used multiple times),
+    /// e.g. "name=^java$ user=^build$ => prefer score*2"
+    #[arg(long = "rule", value_name = "RULE")]
+    pub rules: Vec<String>,
+
+    /// Load rules from FILE, one per line; they come before --rule rules
+    #[arg(long = "rules-file", value_name = "FILE")]
+    pub rules_file: Option<String>,
+
+    /// Apply only the first matching rule, or all matching rules (default: all);
+    /// --ignore and the other pattern options always apply
+    #[arg(long = "rule-mode", value_name = "first|all")]
+    pub rule_mode: Option<String>,
+
+    /// Weight the scores of processes in a systemd slice, as SLICE=WEIGHT
+    /// (can be used multiple times), e.g. app.slice=2; 0 never kills there
+    #[arg(long = "slice-weight", value_name = "SLICE=WEIGHT")]
+    pub slice_weights: Vec<String>,
+
+    /// Take victims from the user whose processes use the most memory, then
+    /// by score within that user's processes
+    #[arg(long = "fair-users")]
+    pub fair_users: bool,
+
+    /// Memory allowance in KiB for --fair-users, as KIB (every user) or
+    /// USER=KIB (one user, by name or uid; can be used multiple times).
+    /// Users are then ranked by their share of the allowance
+    #[arg(long = "user-allowance", value_name = "[USER=]KIB")]
+    pub user_allowances: Vec<String>,
+
+    /// Score weight WEIGHT[,SESSION_WEIGHT] between 0 and 1 for processes in
+    /// the foreground of their terminal, and for other processes of the
+    /// active logind session (default: 1,1, no penalty; SESSION_WEIGHT
+    /// defaults to WEIGHT)
+    #[arg(long = "interactive-penalty", value_name = "WEIGHT[,SESSION_WEIGHT]")]
+    pub interactive_penalty: Option<String>,
+
+    /// Boost the scores of young processes: x2 when just started, x1.5 at
+    /// SECONDS old, fading towards x1 for long-running ones
+    #[arg(long = "prefer-young", value_name = "SECONDS")]
+    pub prefer_young: Option<u64>,
+
+    /// Never kill processes younger than SECONDS
+    #[arg(long = "min-age", value_name = "SECONDS")]
+    pub min_age: Option<u64>,
+
+    /// Never kill processes older than SECONDS
+    #[arg(long = "max-age", value_name = "SECONDS")]
+    pub max_age: Option<u64>,
+
+    /// Keep processes whose name or command line matches PATTERN at
+    /// oom_score_adj ADJ (-1000 to 1000; can be used multiple times), e.g.
+    /// ^sshd$=-900, re-applied to new processes on every scan
+    #[arg(long = "oom-score-adj", value_name = "PATTERN=ADJ")]
+    pub oom_score_adj: Vec<String>,
+
+    /// Restore the original oom_score_adj of adjusted processes on shutdown
+    #[arg(long = "restore-oom-score-adj")]
+    pub restore_oom_score_adj: bool,
+
     /// Dry run mode - don't actually kill processes, just report what would be killed
     #[arg(long = "dryrun")]
     pub dry_run: bool,
@@ -90,6 +307,40 @@ pub struct Args {
     /// Use syslog instead of stdout/stderr for logging
     #[arg(long = "syslog")]
     pub syslog: bool,
+
+    /// Record memory, PSI and the process table at every check to PATH,
+    /// for later use with the replay command
+    #[arg(long = "record", value_name = "PATH")]
+    pub record: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Offline commands; the daemon runs when none is given
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Replay a recording through the decision logic with the given options
+    /// and print which processes would have been acted on, and when
+    Replay {
+        /// Recording created with --record
+        #[arg(value_name = "FILE")]
+        recording: PathBuf,
+    },
+    /// Print every process with its rank in victim selection, or the reason
+    /// it is ignored, using the given options
+    #[command(alias = "candidates")]
+    Explain,
+    /// Show memory state, threshold distances and the candidate ranking,
+    /// refreshed every check interval, using the given options
+    Top,
+    /// Run a synthetic memory scenario through the decision logic with the
+    /// given options and check its expectations
+    Simulate {
+        /// Scenario file describing memory, processes and expectations
+        #[arg(value_name = "FILE")]
+        scenario: PathBuf,
+    },
 }
 
 impl Args {
@@ -1,6 +1,6 @@
 // Environment variable configuration support
 
-use super::Config;
+use super::{Config, UserQuota};
 use anyhow::Result;
 use std::env;
 use std::time::Duration;
@@ -38,6 +38,12 @@ pub fn apply_env_overrides(mut config: Config) -> Result<Config> {
     if let Ok(val) = env::var("OOM_GUARD_SWAP_SIZE_KILL") {
         config.swap_size_kill = Some(val.parse()?);
     }
+    if let Ok(val) = env::var("OOM_GUARD_ZRAM_AS_MEMORY") {
+        config.zram_as_memory = parse_bool(&val)?;
+    }
+    if let Ok(val) = env::var("OOM_GUARD_TRIGGER") {
+        config.trigger = val.parse()?;
+    }
 
     // Monitoring intervals
     if let Ok(val) = env::var("OOM_GUARD_INTERVAL") {
@@ -47,10 +53,7 @@ pub fn apply_env_overrides(mut config: Config) -> Result<Config> {
         config.report_interval = Duration::from_secs(val.parse()?);
     }
 
-    // Process selection
-    if let Ok(val) = env::var("OOM_GUARD_SORT_BY_RSS") {
-        config.sort_by_rss = parse_bool(&val)?;
-    }
+    config = apply_selection_overrides(config)?;
 
     // Behavior flags
     if let Ok(val) = env::var("OOM_GUARD_DRY_RUN") {
@@ -70,12 +73,173 @@ pub fn apply_env_overrides(mut config: Config) -> Result<Config> {
     if let Ok(val) = env::var("OOM_GUARD_KILL_GROUP") {
         config.kill_group = parse_bool(&val)?;
     }
+    if let Ok(val) = env::var("OOM_GUARD_KILL_TREE") {
+        config.kill_tree = parse_bool(&val)?;
+    }
+
+    config = apply_kill_policy_overrides(config)?;
 
     // Priority
     if let Ok(val) = env::var("OOM_GUARD_PRIORITY") {
         config.priority = Some(val.parse()?);
     }
 
+    // Incident recording
+    if let Ok(val) = env::var("OOM_GUARD_RECORD") {
+        config.record = Some(val);
+    }
+
+    Ok(config)
+}
+
+/// Apply overrides for process selection, slice weights and per-user fairness
+fn apply_selection_overrides(mut config: Config) -> Result<Config> {
+    // Process selection
+    if let Ok(val) = env::var("OOM_GUARD_SORT_BY_RSS") {
+        config.sort_by_rss = parse_bool(&val)?;
+    }
+    if let Ok(val) = env::var("OOM_GUARD_RULES_FILE") {
+        config.rules_file = Some(val);
+    }
+    if let Ok(val) = env::var("OOM_GUARD_RULE_MODE") {
+        config.rule_mode = val.parse()?;
+    }
+    if let Ok(val) = env::var("OOM_GUARD_SLICE_WEIGHTS") {
+        config.slice_weights = list_entries(&val)
+            .map(super::parse_slice_weight)
+            .collect::<Result<_>>()?;
+    }
+    if let Ok(val) = env::var("OOM_GUARD_FAIR_USERS") {
+        config.fair_users = parse_bool(&val)?;
+    }
+    if let Ok(val) = env::var("OOM_GUARD_FOREGROUND_WEIGHT") {
+        config.foreground_weight = val.parse()?;
+    }
+    if let Ok(val) = env::var("OOM_GUARD_ACTIVE_SESSION_WEIGHT") {
+        config.active_session_weight = val.parse()?;
+    }
+    if let Ok(val) = env::var("OOM_GUARD_PREFER_YOUNG") {
+        config.prefer_young = Some(Duration::from_secs(val.parse()?));
+    }
+    if let Ok(val) = env::var("OOM_GUARD_MIN_AGE") {
+        config.min_age = Some(Duration::from_secs(val.parse()?));
+    }
+    if let Ok(val) = env::var("OOM_GUARD_MAX_AGE") {
+        config.max_age = Some(Duration::from_secs(val.parse()?));
+    }
+    if let Ok(val) = env::var("OOM_GUARD_OOM_SCORE_ADJ") {
+        config.oom_score_adj = list_entries(&val)
+            .map(super::parse_oom_score_adj)
+            .collect::<Result<_>>()?;
+    }
+    if let Ok(val) = env::var("OOM_GUARD_RESTORE_OOM_SCORE_ADJ") {
+        config.restore_oom_score_adj = parse_bool(&val)?;
+    }
+    if let Ok(val) = env::var("OOM_GUARD_USER_ALLOWANCES") {
+        config.user_allowance = None;
+        config.user_allowances.clear();
+        for allowance in val.split(',').filter(|s| !s.trim().is_empty()) {
+            config.add_user_allowance(super::parse_user_allowance(allowance)?);
+        }
+    }
+
+    Ok(config)
+}
+
+/// Apply overrides for cooldown, kill budget, reclaim, throttling, freezing and respawn detection
+fn apply_kill_policy_overrides(mut config: Config) -> Result<Config> {
+    // Kill rate limiting
+    if let Ok(val) = env::var("OOM_GUARD_COOLDOWN_WARN") {
+        config.cooldown_warn = super::cooldown_from_secs(val.parse()?)?;
+    }
+    if let Ok(val) = env::var("OOM_GUARD_COOLDOWN_KILL") {
+        config.cooldown_kill = super::cooldown_from_secs(val.parse()?)?;
+    }
+    if let Ok(val) = env::var("OOM_GUARD_KILL_BUDGET") {
+        config.kill_budget = Some(val.parse()?);
+    }
+    if let Ok(val) = env::var("OOM_GUARD_KILL_BUDGET_WINDOW") {
+        config.kill_budget_window = super::window_from_minutes(val.parse()?)?;
+    }
+
+    // Proactive reclaim
+    if let Ok(val) = env::var("OOM_GUARD_PROACTIVE_RECLAIM") {
+        config.proactive_reclaim = parse_bool(&val)?;
+    }
+    if let Ok(val) = env::var("OOM_GUARD_PROACTIVE_RECLAIM_CANDIDATES") {
+        config.proactive_reclaim_candidates = val.parse()?;
+    }
+
+    // Throttle strategy
+    if let Ok(val) = env::var("OOM_GUARD_THROTTLE") {
+        config.throttle = parse_bool(&val)?;
+    }
+    if let Ok(val) = env::var("OOM_GUARD_THROTTLE_PERCENT") {
+        config.throttle_percent = val.parse()?;
+    }
+    if let Ok(val) = env::var("OOM_GUARD_THROTTLE_RELEASE") {
+        config.throttle_release_percent = val.parse()?;
+    }
+    if let Ok(val) = env::var("OOM_GUARD_THROTTLE_SCRIPT") {
+        config.throttle_script = Some(val);
+    }
+    if let Ok(val) = env::var("OOM_GUARD_THROTTLE_TIMEOUT") {
+        config.throttle_timeout = Duration::from_secs(val.parse()?);
+    }
+
+    // Freeze strategy
+    if let Ok(val) = env::var("OOM_GUARD_FREEZE") {
+        config.freeze = parse_bool(&val)?;
+    }
+    if let Ok(val) = env::var("OOM_GUARD_FREEZE_THAW") {
+        config.freeze_thaw_percent = val.parse()?;
+    }
+    if let Ok(val) = env::var("OOM_GUARD_FREEZE_TIMEOUT") {
+        config.freeze_timeout = Duration::from_secs(val.parse()?);
+    }
+
+    // Respawn loop detection
+    if let Ok(val) = env::var("OOM_GUARD_RESPAWN_LIMIT") {
+        config.respawn_limit = val.parse()?;
+    }
+    if let Ok(val) = env::var("OOM_GUARD_RESPAWN_WINDOW") {
+        config.respawn_window = super::window_from_minutes(val.parse()?)?;
+    }
+    if let Ok(val) = env::var("OOM_GUARD_RESPAWN_SCRIPT") {
+        config.respawn_script = Some(val);
+    }
+
+    // Kernel OOM kill detection
+    if let Ok(val) = env::var("OOM_GUARD_WATCH_CGROUPS") {
+        config.watch_cgroups = val
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .collect();
+    }
+    if let Ok(val) = env::var("OOM_GUARD_KERNEL_OOM_SCRIPT") {
+        config.kernel_oom_script = Some(val);
+    }
+
+    // Per-user quotas
+    if let Ok(val) = env::var("OOM_GUARD_USER_QUOTAS") {
+        config.user_quotas = list_entries(&val)
+            .map(UserQuota::parse)
+            .collect::<Result<_>>()?;
+    }
+    if let Ok(val) = env::var("OOM_GUARD_QUOTA_GRACE") {
+        config.quota_grace = Duration::from_secs(val.parse()?);
+    }
+
+    // Reclaim target mode
+    if let Ok(val) = env::var("OOM_GUARD_RECLAIM_MARGIN") {
+        config.reclaim_margin = Some(val.parse()?);
+    }
+    if let Ok(val) = env::var("OOM_GUARD_RECLAIM_MAX_KILLS") {
+        config.reclaim_max_kills = val.parse()?;
+    }
+
     Ok(config)
 }
 
@@ -89,24 +253,38 @@ fn parse_bool(s: &str) -> Result<bool> {
     }
 }
 
+/// Entries of a variable holding a list of patterns or rules, separated by
+/// ';' since a pattern may contain ',' (e.g. "^worker{1,3}$")
+fn list_entries(val: &str) -> impl Iterator<Item = &str> {
+    val.split(';').filter(|s| !s.trim().is_empty())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_parse_bool() {
-        assert_eq!(parse_bool("true").unwrap(), true);
-        assert_eq!(parse_bool("TRUE").unwrap(), true);
-        assert_eq!(parse_bool("1").unwrap(), true);
-        assert_eq!(parse_bool("yes").unwrap(), true);
-        assert_eq!(parse_bool("on").unwrap(), true);
-
-        assert_eq!(parse_bool("false").unwrap(), false);
-        assert_eq!(parse_bool("FALSE").unwrap(), false);
-        assert_eq!(parse_bool("0").unwrap(), false);
-        assert_eq!(parse_bool("no").unwrap(), false);
-        assert_eq!(parse_bool("off").unwrap(), false);
+        assert!(parse_bool("true").unwrap());
+        assert!(parse_bool("TRUE").unwrap());
+        assert!(parse_bool("1").unwrap());
+        assert!(parse_bool("yes").unwrap());
+        assert!(parse_bool("on").unwrap());
+
+        assert!(!parse_bool("false").unwrap());
+        assert!(!parse_bool("FALSE").unwrap());
+        assert!(!parse_bool("0").unwrap());
+        assert!(!parse_bool("no").unwrap());
+        assert!(!parse_bool("off").unwrap());
 
         assert!(parse_bool("invalid").is_err());
     }
+
+    #[test]
+    fn test_list_entries() {
+        let entries: Vec<&str> = list_entries("^worker{1,3}$=500;^sshd$=-900;").collect();
+        assert_eq!(entries, ["^worker{1,3}$=500", "^sshd$=-900"]);
+        let (pattern, adj) = super::super::parse_oom_score_adj(entries[0]).unwrap();
+        assert_eq!((pattern.as_str(), adj), ("^worker{1,3}$", 500));
+    }
 }
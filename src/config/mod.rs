@@ -2,11 +2,19 @@
 
 mod args;
 mod env;
+mod quota;
+mod rules;
+mod trigger;
 
+use crate::killer::throttle::{DEFAULT_THROTTLE_PERCENT, MIN_THROTTLE_PERCENT};
+use crate::monitor::cgroup::is_slice_name;
 use anyhow::{bail, Context, Result};
-pub use args::Args;
+pub use args::{Args, Command};
+pub use quota::{QuotaSelector, UserQuota};
 use regex::{Regex, RegexBuilder};
+pub use rules::{load_rules_file, ProcessField, Rule, RuleMode, RuleOutcome, RuleSet};
 use std::time::Duration;
+pub use trigger::{Trigger, TriggerExpr};
 
 /// Maximum allowed length for regex patterns to prevent ReDoS attacks
 const MAX_REGEX_PATTERN_LENGTH: usize = 256;
@@ -74,6 +82,92 @@ fn parse_size_pair(s: &str, default_kill_ratio: f64) -> Result<(u64, u64)> {
     Ok((warn, kill))
 }
 
+/// Parse count and window from string "COUNT" or "COUNT,MINUTES"
+/// Returns (count, window_minutes)
+fn parse_count_window(s: &str, default_minutes: u64) -> Result<(usize, u64)> {
+    let parts: Vec<&str> = s.split(',').collect();
+    let count: usize = parts[0].trim().parse().context("Invalid count")?;
+
+    let minutes: u64 = if parts.len() > 1 {
+        parts[1]
+            .trim()
+            .parse()
+            .context("Invalid window in minutes")?
+    } else {
+        default_minutes
+    };
+
+    Ok((count, minutes))
+}
+
+/// Convert a cooldown in seconds, which must be finite and not negative
+fn cooldown_from_secs(secs: f64) -> Result<Duration> {
+    if !secs.is_finite() || secs < 0.0 {
+        bail!("Invalid cooldown {secs}: must be 0 or more seconds");
+    }
+    Duration::try_from_secs_f64(secs)
+        .ok()
+        .with_context(|| format!("Invalid cooldown {secs}: too long"))
+}
+
+/// Convert a window in minutes, which must fit in a Duration
+fn window_from_minutes(minutes: u64) -> Result<Duration> {
+    minutes
+        .checked_mul(60)
+        .map(Duration::from_secs)
+        .with_context(|| format!("Invalid window {minutes} minutes: too long"))
+}
+
+/// Parse a slice weight from string "SLICE=WEIGHT", e.g. "app.slice=2"
+fn parse_slice_weight(s: &str) -> Result<(String, f64)> {
+    let (slice, weight) = s
+        .split_once('=')
+        .with_context(|| format!("Invalid slice weight '{s}' (expected SLICE=WEIGHT)"))?;
+    let slice = slice.trim();
+    if !is_slice_name(slice) || slice.contains('/') {
+        anyhow::bail!("Invalid slice weight '{s}': '{slice}' is not a slice name");
+    }
+    let weight: f64 = weight
+        .trim()
+        .parse()
+        .with_context(|| format!("Invalid weight in slice weight '{s}'"))?;
+    if !weight.is_finite() || weight < 0.0 {
+        anyhow::bail!("Invalid slice weight '{s}': weight must be 0 or more");
+    }
+    Ok((slice.to_string(), weight))
+}
+
+/// Parse an oom_score_adj setting from string "PATTERN=ADJ", e.g.
+/// "^sshd$=-900"; the pattern ends at the last '='
+fn parse_oom_score_adj(s: &str) -> Result<(Regex, i32)> {
+    let (pattern, adj) = s
+        .rsplit_once('=')
+        .with_context(|| format!("Invalid oom_score_adj setting '{s}' (expected PATTERN=ADJ)"))?;
+    let adj = rules::parse_oom_score_adj(adj)
+        .with_context(|| format!("Invalid oom_score_adj setting '{s}'"))?;
+    Ok((compile_safe_regex(pattern.trim())?, adj))
+}
+
+/// Parse a user allowance from string "KIB" (the default for every user)
+/// or "USER=KIB" (one user, by name or uid)
+fn parse_user_allowance(s: &str) -> Result<(Option<String>, u64)> {
+    let (user, size) = match s.split_once('=') {
+        Some((user, size)) => (Some(user.trim()), size),
+        None => (None, s),
+    };
+    if user.is_some_and(str::is_empty) {
+        anyhow::bail!("Invalid user allowance '{s}': missing user name");
+    }
+    let kb: u64 = size
+        .trim()
+        .parse()
+        .with_context(|| format!("Invalid size in user allowance '{s}' (expected [USER=]KIB)"))?;
+    if kb == 0 {
+        anyhow::bail!("Invalid user allowance '{s}': size must be more than 0");
+    }
+    Ok((user.map(str::to_string), kb))
+}
+
 /// Main configuration struct for OOM Guard
 #[derive(Debug, Clone)]
 pub struct Config {
@@ -88,6 +182,8 @@ pub struct Config {
     pub mem_size_kill: Option<u64>,  // Kill size in KiB
     pub swap_size_warn: Option<u64>, // Warning size in KiB
     pub swap_size_kill: Option<u64>, // Kill size in KiB
+    pub zram_as_memory: bool,        // Leave zram out of swap, whose RAM use counts as memory
+    pub trigger: Trigger,            // How memory and swap thresholds combine, or a condition
 
     // Monitoring intervals
     pub check_interval: Duration, // How often to check memory (fixed, or max for adaptive)
@@ -99,6 +195,23 @@ pub struct Config {
     pub prefer: Vec<Regex>, // Regex patterns for preferred victims
     pub avoid: Vec<Regex>,  // Regex patterns to avoid killing
     pub ignore: Vec<Regex>, // Regex patterns to completely ignore
+    pub prefer_fields: Vec<(ProcessField, Regex)>, // --prefer-exe, --prefer-user, ... patterns
+    pub avoid_fields: Vec<(ProcessField, Regex)>, // --avoid-exe, --avoid-user, ... patterns
+    pub ignore_fields: Vec<(ProcessField, Regex)>, // --ignore-exe, --ignore-user, ... patterns
+    pub rules: Vec<Rule>,   // Ordered per-process rules, from --rules-file then --rule
+    pub rules_file: Option<String>, // File the rules were loaded from
+    pub rule_mode: RuleMode, // Apply the first matching rule or all of them
+    pub slice_weights: Vec<(String, f64)>, // Score weight per systemd slice, 0 to never kill
+    pub fair_users: bool,   // Take victims from the user using the most memory first
+    pub user_allowance: Option<u64>, // Default per-user allowance in KiB for --fair-users
+    pub user_allowances: Vec<(String, u64)>, // Allowance in KiB per user name or uid
+    pub foreground_weight: f64, // Score weight of foreground terminal processes
+    pub active_session_weight: f64, // Score weight of other active-session processes
+    pub prefer_young: Option<Duration>, // Boost scores of processes younger than about this
+    pub min_age: Option<Duration>, // Never kill processes younger than this
+    pub max_age: Option<Duration>, // Never kill processes older than this
+    pub oom_score_adj: Vec<(Regex, i32)>, // oom_score_adj to keep matching processes at
+    pub restore_oom_score_adj: bool, // Restore the original values on shutdown
 
     // Behavior flags
     pub dry_run: bool, // Don't actually kill processes
@@ -115,12 +228,55 @@ pub struct Config {
 
     // Process group killing
     pub kill_group: bool, // Kill entire process group
+    pub kill_tree: bool,  // Rank and kill whole application subtrees
+
+    // Kill rate limiting
+    pub cooldown_warn: Duration, // Cooldown after a kill at the warn threshold
+    pub cooldown_kill: Duration, // Cooldown after a kill at the kill threshold
+    pub kill_budget: Option<usize>, // Maximum kills within kill_budget_window
+    pub kill_budget_window: Duration, // Rolling window for the kill budget
+
+    // Proactive reclaim
+    pub proactive_reclaim: bool, // Reclaim memory at warn threshold before killing
+    pub proactive_reclaim_candidates: usize, // Number of top candidates to reclaim from
+
+    // Throttle strategy
+    pub throttle: bool, // Throttle the top candidate via memory.high at the warn threshold
+    pub throttle_percent: f64, // memory.high as a percentage of the cgroup's current usage
+    pub throttle_release_percent: f64, // Restore memory.high once available memory reaches this level
+    pub throttle_script: Option<String>, // Script to run when throttling or restoring
+    pub throttle_timeout: Duration, // Escalate if memory has not recovered this long after throttling
+
+    // Freeze strategy
+    pub freeze: bool, // Freeze victims at the warn threshold instead of SIGTERM
+    pub freeze_thaw_percent: f64, // Thaw once available memory recovers to this level
+    pub freeze_timeout: Duration, // Kill frozen victims if memory has not recovered by then
+
+    // Respawn loop detection
+    pub respawn_limit: usize, // Kills of one identity before escalating (0 = off)
+    pub respawn_window: Duration, // Rolling window for respawn detection
+    pub respawn_script: Option<String>, // Script to run when a respawn loop is detected
+
+    // Kernel OOM kill detection
+    pub watch_cgroups: Vec<String>, // cgroups whose memory.events OOM kills are watched
+    pub kernel_oom_script: Option<String>, // Script to run after a kernel OOM kill
+
+    // Per-user quotas
+    pub user_quotas: Vec<UserQuota>, // Memory limit per user, the first matching applies
+    pub quota_grace: Duration,       // Time over quota before killing within the user
+
+    // Reclaim target mode
+    pub reclaim_margin: Option<f64>, // Recover to warn threshold + margin (%) per kill episode
+    pub reclaim_max_kills: usize,    // Maximum kills in one episode
 
     // Priority setting
     pub priority: Option<i32>, // Daemon priority
 
     // Syslog
     pub syslog: bool, // Use syslog instead of stdout/stderr
+
+    // Incident recording
+    pub record: Option<String>, // Record samples of memory and processes to this file
 }
 
 impl Config {
@@ -128,6 +284,9 @@ impl Config {
     pub fn from_args(args: Args) -> Result<Self> {
         let mut config = Self::default();
 
+        // Process selection patterns and rules, before fields are moved out
+        config.compile_selection(&args)?;
+
         // Parse memory thresholds (percentages)
         if let Some(mem_threshold_str) = args.mem_threshold {
             let (warn, kill) = parse_threshold_pair(&mem_threshold_str, 0.5)?;
@@ -153,6 +312,10 @@ impl Config {
             config.swap_size_warn = Some(warn);
             config.swap_size_kill = Some(kill);
         }
+        config.zram_as_memory = args.zram_as_memory;
+        if let Some(trigger) = args.trigger {
+            config.trigger = trigger.parse()?;
+        }
 
         // Monitoring intervals
         if let Some(interval) = args.interval {
@@ -166,16 +329,9 @@ impl Config {
 
         // Process selection
         config.sort_by_rss = args.sort_by_rss;
-
-        // Compile regex patterns with safety limits (ReDoS protection)
-        for pattern in args.prefer {
-            config.prefer.push(compile_safe_regex(&pattern)?);
-        }
-        for pattern in args.avoid {
-            config.avoid.push(compile_safe_regex(&pattern)?);
-        }
-        for pattern in args.ignore {
-            config.ignore.push(compile_safe_regex(&pattern)?);
+        config.rules_file = args.rules_file;
+        if let Some(mode) = args.rule_mode {
+            config.rule_mode = mode.parse()?;
         }
 
         // Behavior flags
@@ -190,6 +346,66 @@ impl Config {
 
         // Process group killing
         config.kill_group = args.kill_group;
+        config.kill_tree = args.kill_tree;
+
+        // Per-user fairness
+        config.fair_users = args.fair_users;
+
+        // Kill rate limiting
+        if let Some(cooldown_str) = args.cooldown {
+            let (warn, kill) = parse_threshold_pair(&cooldown_str, 0.5)?;
+            config.cooldown_warn = cooldown_from_secs(warn)?;
+            config.cooldown_kill = cooldown_from_secs(kill)?;
+        }
+        if let Some(budget_str) = args.kill_budget {
+            let (max_kills, minutes) = parse_count_window(&budget_str, 60)?;
+            config.kill_budget = Some(max_kills);
+            config.kill_budget_window = window_from_minutes(minutes)?;
+        }
+
+        // Proactive reclaim
+        config.proactive_reclaim = args.proactive_reclaim;
+        if let Some(count) = args.proactive_reclaim_candidates {
+            config.proactive_reclaim_candidates = count;
+        }
+
+        // Throttle strategy
+        config.throttle = args.throttle;
+        if let Some(percent) = args.throttle_percent {
+            config.throttle_percent = percent;
+        }
+        if let Some(release) = args.throttle_release {
+            config.throttle_release_percent = release;
+        }
+        config.throttle_script = args.throttle_script;
+        if let Some(timeout) = args.throttle_timeout {
+            config.throttle_timeout = Duration::from_secs(timeout);
+        }
+
+        // Freeze strategy
+        config.freeze = args.freeze;
+        if let Some(thaw) = args.freeze_thaw {
+            config.freeze_thaw_percent = thaw;
+        }
+        if let Some(timeout) = args.freeze_timeout {
+            config.freeze_timeout = Duration::from_secs(timeout);
+        }
+
+        // Respawn loop detection
+        if let Some(limit_str) = args.respawn_limit {
+            let (limit, minutes) = parse_count_window(&limit_str, 10)?;
+            config.respawn_limit = limit;
+            config.respawn_window = window_from_minutes(minutes)?;
+        }
+        config.respawn_script = args.respawn_script;
+        config.watch_cgroups = args.watch_cgroups;
+        config.kernel_oom_script = args.kernel_oom_script;
+
+        // Reclaim target mode
+        config.reclaim_margin = args.reclaim_margin;
+        if let Some(max_kills) = args.reclaim_max_kills {
+            config.reclaim_max_kills = max_kills;
+        }
 
         // Priority
         config.priority = args.priority;
@@ -197,15 +413,110 @@ impl Config {
         // Syslog
         config.syslog = args.syslog;
 
+        // Incident recording
+        config.record = args.record;
+
         // Apply environment variable overrides
         config = env::apply_env_overrides(config)?;
 
+        // Rules from the file come before rules given with --rule
+        if let Some(path) = &config.rules_file {
+            config.rules.splice(0..0, load_rules_file(path)?);
+        }
+
         // Validate configuration
         config.validate()?;
 
         Ok(config)
     }
 
+    /// Compile --prefer, --avoid and --ignore patterns, their per-field
+    /// variants and --rule rules
+    fn compile_selection(&mut self, args: &Args) -> Result<()> {
+        // Compile regex patterns with safety limits (ReDoS protection)
+        for pattern in &args.prefer {
+            self.prefer.push(compile_safe_regex(pattern)?);
+        }
+        for pattern in &args.avoid {
+            self.avoid.push(compile_safe_regex(pattern)?);
+        }
+        for pattern in &args.ignore {
+            self.ignore.push(compile_safe_regex(pattern)?);
+        }
+
+        let fields = [
+            (
+                ProcessField::Exe,
+                [&args.prefer_exe, &args.avoid_exe, &args.ignore_exe],
+            ),
+            (
+                ProcessField::User,
+                [&args.prefer_user, &args.avoid_user, &args.ignore_user],
+            ),
+            (
+                ProcessField::Unit,
+                [&args.prefer_unit, &args.avoid_unit, &args.ignore_unit],
+            ),
+            (
+                ProcessField::Container,
+                [
+                    &args.prefer_container,
+                    &args.avoid_container,
+                    &args.ignore_container,
+                ],
+            ),
+        ];
+        for (field, [prefer, avoid, ignore]) in fields {
+            for (target, patterns) in [
+                (&mut self.prefer_fields, prefer),
+                (&mut self.avoid_fields, avoid),
+                (&mut self.ignore_fields, ignore),
+            ] {
+                for pattern in patterns {
+                    target.push((field, compile_safe_regex(pattern)?));
+                }
+            }
+        }
+
+        for (index, rule) in args.rules.iter().enumerate() {
+            self.rules
+                .push(Rule::parse(rule, format!("rule {} ({rule})", index + 1))?);
+        }
+        for weight in &args.slice_weights {
+            self.slice_weights.push(parse_slice_weight(weight)?);
+        }
+        for allowance in &args.user_allowances {
+            self.add_user_allowance(parse_user_allowance(allowance)?);
+        }
+        if let Some(penalty) = &args.interactive_penalty {
+            let (foreground, session) = parse_threshold_pair(penalty, 1.0)?;
+            self.foreground_weight = foreground;
+            self.active_session_weight = session;
+        }
+        self.prefer_young = args.prefer_young.map(Duration::from_secs);
+        self.min_age = args.min_age.map(Duration::from_secs);
+        self.max_age = args.max_age.map(Duration::from_secs);
+        for setting in &args.oom_score_adj {
+            self.oom_score_adj.push(parse_oom_score_adj(setting)?);
+        }
+        self.restore_oom_score_adj |= args.restore_oom_score_adj;
+        for quota in &args.user_quotas {
+            self.user_quotas.push(UserQuota::parse(quota)?);
+        }
+        if let Some(grace) = args.quota_grace {
+            self.quota_grace = Duration::from_secs(grace);
+        }
+        Ok(())
+    }
+
+    /// Set the default allowance, or the allowance of one user
+    fn add_user_allowance(&mut self, (user, kb): (Option<String>, u64)) {
+        match user {
+            Some(user) => self.user_allowances.push((user, kb)),
+            None => self.user_allowance = Some(kb),
+        }
+    }
+
     /// Validate configuration values
     fn validate(&self) -> Result<()> {
         // Validate percentage ranges
@@ -246,6 +557,73 @@ impl Config {
             log::warn!("Both -s and -S set, using -S (absolute size)");
         }
 
+        // Validate reclaim target mode
+        if let Some(margin) = self.reclaim_margin {
+            if !(0.0..=100.0).contains(&margin) {
+                anyhow::bail!("reclaim_margin must be between 0 and 100");
+            }
+        }
+        // Validate kill budget
+        if self.kill_budget == Some(0) {
+            anyhow::bail!("kill_budget must be at least 1");
+        }
+        if self.kill_budget.is_some() && self.kill_budget_window.is_zero() {
+            anyhow::bail!("kill_budget_window must be at least 1 minute");
+        }
+
+        if self.proactive_reclaim && self.proactive_reclaim_candidates == 0 {
+            anyhow::bail!("proactive_reclaim_candidates must be at least 1");
+        }
+
+        // Validate throttle strategy
+        if self.throttle_percent < MIN_THROTTLE_PERCENT || self.throttle_percent >= 100.0 {
+            anyhow::bail!("throttle_percent must be at least {MIN_THROTTLE_PERCENT} and below 100");
+        }
+        if !(0.0..=100.0).contains(&self.throttle_release_percent) {
+            anyhow::bail!("throttle_release_percent must be between 0 and 100");
+        }
+
+        // Validate freeze strategy
+        if !(0.0..=100.0).contains(&self.freeze_thaw_percent) {
+            anyhow::bail!("freeze_thaw_percent must be between 0 and 100");
+        }
+        if self.freeze && self.freeze_thaw_percent <= self.mem_threshold_warn {
+            log::warn!(
+                "freeze_thaw_percent ({}) is not above mem_threshold_warn ({}); victims may be frozen and thawed repeatedly",
+                self.freeze_thaw_percent,
+                self.mem_threshold_warn
+            );
+        }
+
+        if self.respawn_limit > 0 && self.respawn_window.is_zero() {
+            anyhow::bail!("respawn_window must be at least 1 minute");
+        }
+
+        for weight in [self.foreground_weight, self.active_session_weight] {
+            if !(0.0..=1.0).contains(&weight) {
+                anyhow::bail!("interactive penalty weights must be between 0 and 1");
+            }
+        }
+
+        if self.prefer_young.is_some_and(|scale| scale.is_zero()) {
+            anyhow::bail!("prefer_young must be at least 1 second");
+        }
+        if let (Some(min), Some(max)) = (self.min_age, self.max_age) {
+            if min >= max {
+                anyhow::bail!("min_age must be less than max_age");
+            }
+        }
+
+        if !self.user_allowances.is_empty() && self.user_allowance.is_none() {
+            anyhow::bail!(
+                "per-user allowances need a default allowance for the other users (--user-allowance KIB)"
+            );
+        }
+
+        if self.reclaim_max_kills == 0 {
+            anyhow::bail!("reclaim_max_kills must be at least 1");
+        }
+
         // Validate priority range
         if let Some(priority) = self.priority {
             if !(-20..=19).contains(&priority) {
@@ -268,13 +646,32 @@ impl Default for Config {
             mem_size_kill: None,
             swap_size_warn: None,
             swap_size_kill: None,
+            zram_as_memory: false,
+            trigger: Trigger::All, // Memory and swap both low, as earlyoom does
             check_interval: Duration::from_secs(1), // Check every second (or max for adaptive)
             report_interval: Duration::from_secs(60), // Report every minute
-            adaptive_sleep: true,                   // Use adaptive sleep by default
-            sort_by_rss: false,                     // Use oom_score by default
+            adaptive_sleep: true,  // Use adaptive sleep by default
+            sort_by_rss: false,    // Use oom_score by default
             prefer: Vec::new(),
             avoid: Vec::new(),
             ignore: Vec::new(),
+            prefer_fields: Vec::new(),
+            avoid_fields: Vec::new(),
+            ignore_fields: Vec::new(),
+            rules: Vec::new(),
+            rules_file: None,
+            rule_mode: RuleMode::All, // Accumulate the actions of every matching rule
+            slice_weights: Vec::new(), // No slice weights
+            fair_users: false,
+            user_allowance: None,
+            user_allowances: Vec::new(),
+            foreground_weight: 1.0,     // No interactive penalty by default
+            active_session_weight: 1.0, // No interactive penalty by default
+            prefer_young: None,         // Age does not affect scores by default
+            min_age: None,
+            max_age: None,
+            oom_score_adj: Vec::new(), // Leave other processes' oom_score_adj alone
+            restore_oom_score_adj: false,
             dry_run: false,
             debug: false,
             notify: false,
@@ -283,8 +680,33 @@ impl Default for Config {
             pre_kill_script: None,
             post_kill_script: None,
             kill_group: false,
+            kill_tree: false,
+            cooldown_warn: Duration::from_secs(10), // 10s after a kill at warn level
+            cooldown_kill: Duration::from_secs(5),  // 5s after a kill at kill level
+            kill_budget: None,
+            kill_budget_window: Duration::from_secs(3600),
+            proactive_reclaim: false,
+            proactive_reclaim_candidates: 3,
+            throttle: false,
+            throttle_percent: DEFAULT_THROTTLE_PERCENT,
+            throttle_release_percent: 20.0,
+            throttle_script: None,
+            throttle_timeout: Duration::from_secs(30),
+            freeze: false,
+            freeze_thaw_percent: 20.0,
+            freeze_timeout: Duration::from_secs(60),
+            respawn_limit: 0, // Off unless --respawn-limit is given
+            respawn_window: Duration::from_secs(600),
+            user_quotas: Vec::new(),
+            quota_grace: Duration::from_secs(30), // Warn, then kill 30s later
+            respawn_script: None,
+            watch_cgroups: Vec::new(),
+            kernel_oom_script: None,
+            reclaim_margin: None,
+            reclaim_max_kills: 10,
             priority: None,
             syslog: false,
+            record: None,
         }
     }
 }
@@ -357,15 +779,103 @@ mod tests {
     #[test]
     fn test_parse_size_pair_single_value() {
         let (warn, kill) = parse_size_pair("1048576", 0.5).unwrap();
-        assert_eq!(warn, 1048576);
-        assert_eq!(kill, 524288); // 50% of 1048576
+        assert_eq!(warn, 1_048_576);
+        assert_eq!(kill, 524_288); // 50% of 1048576
     }
 
     #[test]
     fn test_parse_size_pair_both_values() {
         let (warn, kill) = parse_size_pair("1048576,262144", 0.5).unwrap();
-        assert_eq!(warn, 1048576);
-        assert_eq!(kill, 262144);
+        assert_eq!(warn, 1_048_576);
+        assert_eq!(kill, 262_144);
+    }
+
+    #[test]
+    fn test_parse_count_window_default_window() {
+        let (count, minutes) = parse_count_window("5", 60).unwrap();
+        assert_eq!(count, 5);
+        assert_eq!(minutes, 60);
+    }
+
+    #[test]
+    fn test_parse_count_window_with_window() {
+        let (count, minutes) = parse_count_window("3,10", 60).unwrap();
+        assert_eq!(count, 3);
+        assert_eq!(minutes, 10);
+        assert!(parse_count_window("three", 60).is_err());
+    }
+
+    #[test]
+    fn test_parse_oom_score_adj() {
+        let (pattern, adj) = parse_oom_score_adj("^sshd$=-900").unwrap();
+        assert_eq!((pattern.as_str(), adj), ("^sshd$", -900));
+        let (pattern, adj) = parse_oom_score_adj("chrome --type=renderer=+500").unwrap();
+        assert_eq!((pattern.as_str(), adj), ("chrome --type=renderer", 500));
+        assert!(parse_oom_score_adj("sshd").is_err());
+        assert!(parse_oom_score_adj("sshd=-2000").is_err());
+        assert!(parse_oom_score_adj("[=1").is_err());
+    }
+
+    #[test]
+    fn test_cooldown_from_secs() {
+        assert_eq!(
+            cooldown_from_secs(2.5).unwrap(),
+            Duration::from_millis(2500)
+        );
+        assert_eq!(cooldown_from_secs(0.0).unwrap(), Duration::ZERO);
+        assert!(cooldown_from_secs(-1.0).is_err());
+        assert!(cooldown_from_secs(f64::NAN).is_err());
+        assert!(cooldown_from_secs(f64::INFINITY).is_err());
+        assert!(cooldown_from_secs(1e30).is_err());
+    }
+
+    #[test]
+    fn test_window_from_minutes() {
+        assert_eq!(window_from_minutes(0).unwrap(), Duration::ZERO);
+        assert_eq!(window_from_minutes(60).unwrap(), Duration::from_secs(3600));
+        assert!(window_from_minutes(400_000_000_000_000_000).is_err());
+        assert!(window_from_minutes(u64::MAX).is_err());
+    }
+
+    #[test]
+    fn test_windows_too_long_are_rejected() {
+        use clap::Parser;
+        for flag in ["--kill-budget", "--respawn-limit"] {
+            let args = Args::try_parse_from(["oom_guard", flag, "3,400000000000000000"]).unwrap();
+            assert!(Config::from_args(args).is_err(), "{flag}");
+        }
+    }
+
+    #[test]
+    fn test_parse_slice_weight() {
+        assert_eq!(
+            parse_slice_weight("app.slice=2").unwrap(),
+            ("app.slice".to_string(), 2.0)
+        );
+        assert_eq!(
+            parse_slice_weight(" system.slice = 0 ").unwrap(),
+            ("system.slice".to_string(), 0.0)
+        );
+        assert!(parse_slice_weight("app.slice").is_err());
+        assert!(parse_slice_weight("nginx.service=2").is_err());
+        assert!(parse_slice_weight("app.slice=-1").is_err());
+        assert!(parse_slice_weight("app.slice=much").is_err());
+    }
+
+    #[test]
+    fn test_parse_user_allowance() {
+        assert_eq!(parse_user_allowance("8388608").unwrap(), (None, 8_388_608));
+        assert_eq!(
+            parse_user_allowance(" alice = 16777216 ").unwrap(),
+            (Some("alice".to_string()), 16_777_216)
+        );
+        assert_eq!(
+            parse_user_allowance("1000=4194304").unwrap(),
+            (Some("1000".to_string()), 4_194_304)
+        );
+        assert!(parse_user_allowance("=4194304").is_err());
+        assert!(parse_user_allowance("alice=0").is_err());
+        assert!(parse_user_allowance("8GiB").is_err());
     }
 
     #[test]
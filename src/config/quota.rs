@@ -0,0 +1,103 @@
+// Per-user memory quotas: "SELECTOR:KIB", each matching user on their own
+//
+//   uid=1000-60000:8388608    every regular user may use 8 GiB
+//   group=students:4194304    members of the students group 4 GiB
+//   user=alice:16777216       alice 16 GiB
+//
+// The first quota matching a user applies.
+
+use super::rules::parse_uid_range;
+use anyhow::{bail, Context, Result};
+use std::ops::RangeInclusive;
+
+/// Users a quota applies to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QuotaSelector {
+    Uids(RangeInclusive<u32>),
+    User(String),
+    /// Members of a group, primary or supplementary
+    Group(String),
+}
+
+/// Memory limit for each user matched by a selector
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserQuota {
+    pub selector: QuotaSelector,
+    pub limit_kb: u64,
+    /// The quota as given, for log messages
+    pub spec: String,
+}
+
+impl UserQuota {
+    /// Parse "uid=N[-M]:KIB", "user=NAME:KIB" or "group=NAME:KIB"
+    pub fn parse(spec: &str) -> Result<Self> {
+        let spec = spec.trim();
+        let (selector, limit) = spec
+            .rsplit_once(':')
+            .with_context(|| format!("Invalid quota '{spec}' (expected SELECTOR:KIB)"))?;
+        let (key, value) = selector
+            .split_once('=')
+            .with_context(|| format!("Invalid quota '{spec}' (expected uid=, user= or group=)"))?;
+        let value = value.trim();
+        if value.is_empty() {
+            bail!("Invalid quota '{spec}': empty {key}");
+        }
+        let selector = match key.trim() {
+            "uid" => QuotaSelector::Uids(
+                parse_uid_range(value).with_context(|| format!("Invalid quota '{spec}'"))?,
+            ),
+            "user" => QuotaSelector::User(value.to_string()),
+            "group" => QuotaSelector::Group(value.to_string()),
+            key => bail!("Invalid quota '{spec}': unknown selector '{key}'"),
+        };
+        let limit_kb: u64 = limit
+            .trim()
+            .parse()
+            .with_context(|| format!("Invalid size in quota '{spec}'"))?;
+        if limit_kb == 0 {
+            bail!("Invalid quota '{spec}': size must be more than 0");
+        }
+        Ok(Self {
+            selector,
+            limit_kb,
+            spec: spec.to_string(),
+        })
+    }
+}
+
+impl std::fmt::Display for UserQuota {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.spec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_quota() {
+        let quota = UserQuota::parse("uid=1000-60000:8388608").unwrap();
+        assert_eq!(quota.selector, QuotaSelector::Uids(1000..=60000));
+        assert_eq!(quota.limit_kb, 8_388_608);
+        assert_eq!(quota.to_string(), "uid=1000-60000:8388608");
+
+        assert_eq!(
+            UserQuota::parse(" group=students : 4194304 ")
+                .unwrap()
+                .selector,
+            QuotaSelector::Group("students".to_string())
+        );
+        assert_eq!(
+            UserQuota::parse("user=alice:1").unwrap().selector,
+            QuotaSelector::User("alice".to_string())
+        );
+
+        assert!(UserQuota::parse("uid=1000").is_err());
+        assert!(UserQuota::parse("uid=60000-1000:1").is_err());
+        assert!(UserQuota::parse("gid=100:1").is_err());
+        assert!(UserQuota::parse("user=:1").is_err());
+        assert!(UserQuota::parse("user=alice:0").is_err());
+        assert!(UserQuota::parse("user=alice:8GiB").is_err());
+    }
+}
@@ -0,0 +1,1039 @@
+// Per-process policy rules: ordered "match => action" lines
+//
+// A rule is a list of matchers, all of which must hold, followed by "=>"
+// and a list of actions:
+//
+//   name=^chrom(e|ium)$ => prefer score*2
+//   user=^postgres$ cgroup=postgres => avoid signals=SIGINT,SIGTERM,SIGKILL
+//   unit=^backup- => kill=cgroup notify=/usr/local/bin/page-oncall
+//   qos=^BestEffort$ => prefer
+//   name=^sshd$ => adj=-900
+//
+// Values containing spaces are quoted: cmdline="java .*-Xmx".
+
+use super::compile_safe_regex;
+use crate::killer::signals::parse_signal;
+use crate::killer::KillScope;
+use crate::monitor::cgroup::slices_from_path;
+use crate::monitor::ProcessInfo;
+use anyhow::{bail, Context, Result};
+use nix::sys::signal::Signal;
+use regex::Regex;
+use std::fs;
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+/// How the actions of several matching rules are combined
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RuleMode {
+    /// Only the first matching configured rule applies; rules built from
+    /// options still all apply
+    First,
+    /// Every matching rule applies, in order
+    #[default]
+    All,
+}
+
+impl std::str::FromStr for RuleMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "first" => Ok(Self::First),
+            "all" => Ok(Self::All),
+            _ => bail!("Invalid rule mode '{s}' (expected first or all)"),
+        }
+    }
+}
+
+impl std::fmt::Display for RuleMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::First => "first",
+            Self::All => "all",
+        })
+    }
+}
+
+/// Process identity matched by the --ignore-*, --avoid-* and --prefer-*
+/// options, which unlike name and command line cannot be set by the
+/// process itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessField {
+    Exe,
+    User,
+    Unit,
+    Container,
+}
+
+impl ProcessField {
+    /// Suffix of the option names and key in rules
+    pub const fn key(self) -> &'static str {
+        match self {
+            Self::Exe => "exe",
+            Self::User => "user",
+            Self::Unit => "unit",
+            Self::Container => "container",
+        }
+    }
+}
+
+/// One condition of a rule
+#[derive(Debug, Clone)]
+enum Matcher {
+    Name(Regex),
+    Cmdline(Regex),
+    /// Name or command line, as matched by --ignore, --avoid and --prefer
+    NameOrCmdline(Regex),
+    Field(ProcessField, Regex),
+    Cgroup(Regex),
+    /// Any systemd slice the cgroup is nested in
+    Slice(Regex),
+    /// A slice of that exact name, as given to --slice-weight
+    SliceName(String),
+    /// Container runtime: docker, containerd, cri-o or podman
+    Runtime(Regex),
+    /// Kubernetes pod UID
+    Pod(Regex),
+    /// Kubernetes pod QoS class: Guaranteed, Burstable or BestEffort
+    Qos(Regex),
+    /// Name of the parent process
+    Parent(Regex),
+    Uid(RangeInclusive<u32>),
+    /// In the foreground process group of its terminal
+    Foreground(bool),
+    /// In an active logind session
+    ActiveSession(bool),
+}
+
+impl Matcher {
+    fn parse(key: &str, value: &str) -> Result<Self> {
+        let regex = || compile_safe_regex(value);
+        Ok(match key {
+            "name" => Self::Name(regex()?),
+            "cmdline" => Self::Cmdline(regex()?),
+            "exe" => Self::Field(ProcessField::Exe, regex()?),
+            "user" => Self::Field(ProcessField::User, regex()?),
+            "unit" => Self::Field(ProcessField::Unit, regex()?),
+            "container" => Self::Field(ProcessField::Container, regex()?),
+            "cgroup" => Self::Cgroup(regex()?),
+            "slice" => Self::Slice(regex()?),
+            "runtime" => Self::Runtime(regex()?),
+            "pod" => Self::Pod(regex()?),
+            "qos" => Self::Qos(regex()?),
+            "parent" => Self::Parent(regex()?),
+            "uid" => Self::Uid(parse_uid_range(value)?),
+            "foreground" => Self::Foreground(parse_yes_no(value)?),
+            "active-session" => Self::ActiveSession(parse_yes_no(value)?),
+            _ => bail!("unknown matcher '{key}'"),
+        })
+    }
+
+    fn matches(&self, process: &ProcessInfo, parent: Option<&str>) -> bool {
+        match self {
+            Self::Name(re) => re.is_match(&process.name),
+            Self::Cmdline(re) => re.is_match(&process.cmdline),
+            Self::NameOrCmdline(re) => re.is_match(&process.cmdline) || re.is_match(&process.name),
+            Self::Field(field, re) => re.is_match(match field {
+                ProcessField::Exe => &process.exe,
+                ProcessField::User => &process.user,
+                ProcessField::Unit => &process.unit,
+                ProcessField::Container => &process.container,
+            }),
+            Self::Cgroup(re) => re.is_match(&process.cgroup),
+            Self::Slice(re) => slices_from_path(&process.cgroup).any(|slice| re.is_match(slice)),
+            Self::SliceName(name) => slices_from_path(&process.cgroup).any(|slice| slice == name),
+            // Outside containers and pods these fields are empty, which
+            // must not match patterns like "^(Burstable|)$"
+            Self::Runtime(re) => !process.runtime.is_empty() && re.is_match(&process.runtime),
+            Self::Pod(re) => !process.pod.is_empty() && re.is_match(&process.pod),
+            Self::Qos(re) => !process.qos.is_empty() && re.is_match(&process.qos),
+            Self::Parent(re) => parent.is_some_and(|name| re.is_match(name)),
+            Self::Uid(range) => range.contains(&process.uid),
+            Self::Foreground(yes) => process.is_foreground() == *yes,
+            Self::ActiveSession(yes) => process.active_session == *yes,
+        }
+    }
+}
+
+/// What a matching rule does
+#[derive(Debug, Clone, PartialEq)]
+enum RuleAction {
+    Ignore,
+    Prefer,
+    Avoid,
+    ScoreMultiply(f64),
+    ScoreAdd(i64),
+    Signals(Vec<Signal>),
+    Kill(KillScope),
+    Notify(String),
+    /// oom_score_adj the daemon keeps the process at
+    OomScoreAdj(i32),
+}
+
+impl RuleAction {
+    fn parse(token: &str) -> Result<Self> {
+        if let Some(factor) = token.strip_prefix("score*") {
+            let factor: f64 = factor.parse().context("invalid score multiplier")?;
+            if !factor.is_finite() || factor < 0.0 {
+                bail!("score multiplier must not be negative");
+            }
+            return Ok(Self::ScoreMultiply(factor));
+        }
+        if let Some(offset) = token.strip_prefix("score") {
+            return Ok(Self::ScoreAdd(
+                offset.parse().context("invalid score offset")?,
+            ));
+        }
+
+        match token.split_once('=') {
+            Some(("signals", names)) => Ok(Self::Signals(
+                names.split(',').map(parse_signal).collect::<Result<_>>()?,
+            )),
+            Some(("kill", scope)) => Ok(Self::Kill(scope.parse()?)),
+            Some(("notify", path)) if !path.is_empty() => Ok(Self::Notify(path.to_string())),
+            Some(("adj", adj)) => Ok(Self::OomScoreAdj(parse_oom_score_adj(adj)?)),
+            _ => match token {
+                "ignore" => Ok(Self::Ignore),
+                "prefer" => Ok(Self::Prefer),
+                "avoid" => Ok(Self::Avoid),
+                _ => bail!("unknown action '{token}'"),
+            },
+        }
+    }
+}
+
+/// A per-process policy rule
+#[derive(Debug, Clone)]
+pub struct Rule {
+    /// How the rule is referred to in logs, e.g. "rule 2 (name=java => avoid)"
+    pub name: String,
+    matchers: Vec<Matcher>,
+    actions: Vec<RuleAction>,
+}
+
+impl Rule {
+    /// Parse a rule of the form "MATCHERS => ACTIONS"
+    pub fn parse(text: &str, name: impl Into<String>) -> Result<Self> {
+        let name = name.into();
+        Self::parse_tokens(&tokenize(text)?)
+            .with_context(|| format!("Invalid {name}"))
+            .map(|(matchers, actions)| Self {
+                name,
+                matchers,
+                actions,
+            })
+    }
+
+    fn parse_tokens(tokens: &[String]) -> Result<(Vec<Matcher>, Vec<RuleAction>)> {
+        let Some(arrow) = tokens.iter().position(|t| t == "=>") else {
+            bail!("missing \"=>\" between matchers and actions");
+        };
+        let (matchers, actions) = (&tokens[..arrow], &tokens[arrow + 1..]);
+        if matchers.is_empty() {
+            bail!("a rule needs at least one matcher");
+        }
+        if actions.is_empty() {
+            bail!("a rule needs at least one action");
+        }
+
+        let matchers = matchers
+            .iter()
+            .map(|token| {
+                let (key, value) = token
+                    .split_once('=')
+                    .with_context(|| format!("expected KEY=VALUE, got '{token}'"))?;
+                Matcher::parse(key, value)
+            })
+            .collect::<Result<_>>()?;
+        let actions = actions
+            .iter()
+            .map(|token| RuleAction::parse(token))
+            .collect::<Result<_>>()?;
+        Ok((matchers, actions))
+    }
+
+    /// The rule equivalent to one --ignore, --avoid or --prefer pattern,
+    /// or to one of their --*-exe, --*-user, ... variants given `field`
+    fn from_option(
+        option: &str,
+        index: usize,
+        field: Option<ProcessField>,
+        pattern: &Regex,
+        action: RuleAction,
+    ) -> Self {
+        let matcher = field.map_or_else(
+            || Matcher::NameOrCmdline(pattern.clone()),
+            |field| Matcher::Field(field, pattern.clone()),
+        );
+        Self {
+            name: format!("{option} pattern {} ({})", index + 1, pattern.as_str()),
+            matchers: vec![matcher],
+            actions: vec![action],
+        }
+    }
+
+    /// The rule equivalent to one --slice-weight option: weight 0 ignores
+    /// the processes in the slice, any other weight scales their score
+    fn from_slice_weight(slice: &str, weight: f64) -> Self {
+        let action = if weight == 0.0 {
+            RuleAction::Ignore
+        } else {
+            RuleAction::ScoreMultiply(weight)
+        };
+        Self {
+            name: format!("--slice-weight {slice}={weight}"),
+            matchers: vec![Matcher::SliceName(slice.to_string())],
+            actions: vec![action],
+        }
+    }
+
+    /// The rules equivalent to --interactive-penalty: foreground processes
+    /// get `foreground`, other processes of active sessions `session`.
+    /// Weight 1 adds no rule
+    fn from_interactive_penalty(foreground: f64, session: f64) -> Vec<Self> {
+        [
+            ("foreground", foreground, vec![Matcher::Foreground(true)]),
+            (
+                "active-session",
+                session,
+                vec![Matcher::Foreground(false), Matcher::ActiveSession(true)],
+            ),
+        ]
+        .into_iter()
+        .filter(|(_, weight, _)| (*weight - 1.0).abs() > f64::EPSILON)
+        .map(|(kind, weight, matchers)| Self {
+            name: format!("--interactive-penalty {kind}={weight}"),
+            matchers,
+            actions: vec![RuleAction::ScoreMultiply(weight)],
+        })
+        .collect()
+    }
+
+    /// The rule equivalent to one --oom-score-adj option
+    fn from_oom_score_adj(pattern: &Regex, adj: i32) -> Self {
+        Self {
+            name: format!("--oom-score-adj {}={adj:+}", pattern.as_str()),
+            matchers: vec![Matcher::NameOrCmdline(pattern.clone())],
+            actions: vec![RuleAction::OomScoreAdj(adj)],
+        }
+    }
+
+    fn matches(&self, process: &ProcessInfo, parent: Option<&str>) -> bool {
+        self.matchers.iter().all(|m| m.matches(process, parent))
+    }
+
+    fn uses_parent(&self) -> bool {
+        self.matchers
+            .iter()
+            .any(|m| matches!(m, Matcher::Parent(_)))
+    }
+}
+
+/// Load rules from a file, one per line; blank lines and lines starting
+/// with '#' are skipped
+pub fn load_rules_file(path: &str) -> Result<Vec<Rule>> {
+    let content = fs::read_to_string(Path::new(path))
+        .with_context(|| format!("Failed to read rules file {path}"))?;
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(index, line)| {
+            Rule::parse(line, format!("rule {path}:{} ({})", index + 1, line.trim()))
+        })
+        .collect()
+}
+
+/// Combined effect of the rules matching one process
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleOutcome {
+    /// First rule that made the process ignored
+    pub ignored_by: Option<String>,
+    /// First rule that made the process preferred
+    pub preferred_by: Option<String>,
+    /// First rule that made the process avoided; avoid wins over prefer
+    pub avoided_by: Option<String>,
+    pub multiplier: f64,
+    pub offset: i64,
+    /// Signal ladder replacing SIGTERM, SIGKILL
+    pub signals: Option<Vec<Signal>>,
+    pub kill_scope: Option<KillScope>,
+    /// Scripts to run after the process is killed
+    pub notify: Vec<String>,
+    /// oom_score_adj to keep the process at, from the last rule setting
+    /// one, and that rule
+    pub oom_score_adj: Option<(i32, String)>,
+    /// Every rule that matched, in order
+    pub matched: Vec<String>,
+}
+
+impl Default for RuleOutcome {
+    fn default() -> Self {
+        Self {
+            ignored_by: None,
+            preferred_by: None,
+            avoided_by: None,
+            multiplier: 1.0,
+            offset: 0,
+            signals: None,
+            kill_scope: None,
+            notify: Vec::new(),
+            oom_score_adj: None,
+            matched: Vec::new(),
+        }
+    }
+}
+
+impl RuleOutcome {
+    fn apply(&mut self, rule: &Rule) {
+        for action in &rule.actions {
+            match action {
+                RuleAction::Ignore => {
+                    self.ignored_by.get_or_insert_with(|| rule.name.clone());
+                }
+                RuleAction::Prefer => {
+                    self.preferred_by.get_or_insert_with(|| rule.name.clone());
+                }
+                RuleAction::Avoid => {
+                    self.avoided_by.get_or_insert_with(|| rule.name.clone());
+                }
+                RuleAction::ScoreMultiply(factor) => self.multiplier *= factor,
+                RuleAction::ScoreAdd(offset) => self.offset = self.offset.saturating_add(*offset),
+                RuleAction::Signals(signals) => self.signals = Some(signals.clone()),
+                RuleAction::Kill(scope) => self.kill_scope = Some(*scope),
+                RuleAction::Notify(path) => self.notify.push(path.clone()),
+                RuleAction::OomScoreAdj(adj) => {
+                    self.oom_score_adj = Some((*adj, rule.name.clone()));
+                }
+            }
+        }
+        self.matched.push(rule.name.clone());
+    }
+
+    /// Apply the score multiplier and offset to a ranking value
+    pub fn adjust(&self, base: i64) -> i64 {
+        ((base as f64 * self.multiplier).round() as i64).saturating_add(self.offset)
+    }
+}
+
+/// Ordered rules with their combination mode
+#[derive(Debug, Clone, Default)]
+pub struct RuleSet {
+    rules: Vec<Rule>,
+    mode: RuleMode,
+    /// Number of leading rules that came from the rules configuration
+    configured: usize,
+}
+
+impl RuleSet {
+    /// The configured rules followed by the --ignore*, --avoid* and
+    /// --prefer* patterns, the --slice-weight options, the interactive
+    /// penalty and the --oom-score-adj options, in that order
+    pub fn from_config(config: &super::Config) -> Self {
+        let mut rules = config.rules.clone();
+        for (option, patterns, fields, action) in [
+            (
+                "ignore",
+                &config.ignore,
+                &config.ignore_fields,
+                RuleAction::Ignore,
+            ),
+            (
+                "avoid",
+                &config.avoid,
+                &config.avoid_fields,
+                RuleAction::Avoid,
+            ),
+            (
+                "prefer",
+                &config.prefer,
+                &config.prefer_fields,
+                RuleAction::Prefer,
+            ),
+        ] {
+            rules.extend(patterns.iter().enumerate().map(|(index, pattern)| {
+                Rule::from_option(&format!("--{option}"), index, None, pattern, action.clone())
+            }));
+            rules.extend(fields.iter().enumerate().map(|(index, (field, pattern))| {
+                let name = format!("--{option}-{}", field.key());
+                // Patterns are numbered per option, like --ignore ones
+                let index = fields[..index].iter().filter(|(f, _)| f == field).count();
+                Rule::from_option(&name, index, Some(*field), pattern, action.clone())
+            }));
+        }
+        rules.extend(
+            config
+                .slice_weights
+                .iter()
+                .map(|(slice, weight)| Rule::from_slice_weight(slice, *weight)),
+        );
+        rules.extend(Rule::from_interactive_penalty(
+            config.foreground_weight,
+            config.active_session_weight,
+        ));
+        rules.extend(
+            config
+                .oom_score_adj
+                .iter()
+                .map(|(pattern, adj)| Rule::from_oom_score_adj(pattern, *adj)),
+        );
+        Self {
+            rules,
+            mode: config.rule_mode,
+            configured: config.rules.len(),
+        }
+    }
+
+    /// Whether any rule matches on the parent process name
+    pub fn uses_parent(&self) -> bool {
+        self.rules.iter().any(Rule::uses_parent)
+    }
+
+    /// Whether any rule sets an oom_score_adj
+    pub fn sets_oom_score_adj(&self) -> bool {
+        self.rules.iter().any(|rule| {
+            rule.actions
+                .iter()
+                .any(|action| matches!(action, RuleAction::OomScoreAdj(_)))
+        })
+    }
+
+    /// Combine the actions of the rules matching a process whose parent
+    /// is named `parent`
+    pub fn evaluate(&self, process: &ProcessInfo, parent: Option<&str>) -> RuleOutcome {
+        let mut outcome = RuleOutcome::default();
+        let (configured, options) = self.rules.split_at(self.configured);
+        for rule in configured.iter().filter(|r| r.matches(process, parent)) {
+            outcome.apply(rule);
+            if self.mode == RuleMode::First {
+                break;
+            }
+        }
+        // --ignore and the other options always apply, so that a
+        // configured rule cannot take away their protection
+        for rule in options.iter().filter(|r| r.matches(process, parent)) {
+            outcome.apply(rule);
+        }
+        outcome
+    }
+}
+
+/// Parse an oom_score_adj value, -1000 to 1000, e.g. "-900" or "+500"
+pub(super) fn parse_oom_score_adj(value: &str) -> Result<i32> {
+    let adj: i32 = value.trim().parse().context("invalid oom_score_adj")?;
+    if !(-1000..=1000).contains(&adj) {
+        bail!("oom_score_adj must be between -1000 and 1000");
+    }
+    Ok(adj)
+}
+
+/// Parse "yes" or "no"
+fn parse_yes_no(value: &str) -> Result<bool> {
+    match value {
+        "yes" => Ok(true),
+        "no" => Ok(false),
+        _ => bail!("expected yes or no, got '{value}'"),
+    }
+}
+
+/// Parse "N" or "N-M"
+pub(super) fn parse_uid_range(value: &str) -> Result<RangeInclusive<u32>> {
+    let (low, high) = value.split_once('-').unwrap_or((value, value));
+    let low: u32 = low.parse().context("invalid uid")?;
+    let high: u32 = high.parse().context("invalid uid")?;
+    if low > high {
+        bail!("invalid uid range {value}");
+    }
+    Ok(low..=high)
+}
+
+/// Split on whitespace, keeping double-quoted text together
+fn tokenize(text: &str) -> Result<Vec<String>> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut in_token = false;
+
+    for c in text.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                in_token = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if in_token {
+                    tokens.push(std::mem::take(&mut current));
+                    in_token = false;
+                }
+            }
+            c => {
+                current.push(c);
+                in_token = true;
+            }
+        }
+    }
+    if quoted {
+        bail!("unterminated quote");
+    }
+    if in_token {
+        tokens.push(current);
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn process(name: &str, cmdline: &str, uid: u32) -> ProcessInfo {
+        ProcessInfo {
+            pid: 100,
+            ppid: 1,
+            name: name.to_string(),
+            cmdline: cmdline.to_string(),
+            exe: format!("/usr/bin/{name}"),
+            uid,
+            user: format!("user{uid}"),
+            ..Default::default()
+        }
+        .with_cgroup(format!("/system.slice/{name}.service"))
+    }
+
+    fn rules(mode: RuleMode, lines: &[&str]) -> RuleSet {
+        RuleSet {
+            rules: lines
+                .iter()
+                .enumerate()
+                .map(|(i, line)| Rule::parse(line, format!("rule {}", i + 1)).unwrap())
+                .collect(),
+            mode,
+            configured: lines.len(),
+        }
+    }
+
+    #[test]
+    fn test_parse_rule() {
+        let rule = Rule::parse(
+            r#"name=^java$ cmdline="-Xmx[0-9]+g .*" uid=1000-1999 => avoid score*0.5 score-100 signals=INT,SIGKILL kill=cgroup notify=/bin/true adj=+500"#,
+            "rule 1",
+        )
+        .unwrap();
+        assert_eq!(rule.matchers.len(), 3);
+        assert_eq!(
+            rule.actions,
+            vec![
+                RuleAction::Avoid,
+                RuleAction::ScoreMultiply(0.5),
+                RuleAction::ScoreAdd(-100),
+                RuleAction::Signals(vec![Signal::SIGINT, Signal::SIGKILL]),
+                RuleAction::Kill(KillScope::Cgroup),
+                RuleAction::Notify("/bin/true".to_string()),
+                RuleAction::OomScoreAdj(500),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_rule_errors() {
+        assert!(Rule::parse("name=a", "r").is_err());
+        assert!(Rule::parse("=> ignore", "r").is_err());
+        assert!(Rule::parse("name=a =>", "r").is_err());
+        assert!(Rule::parse("colour=red => ignore", "r").is_err());
+        assert!(Rule::parse("name=a => explode", "r").is_err());
+        assert!(Rule::parse("name=a => signals=SIGNOPE", "r").is_err());
+        assert!(Rule::parse("name=a => adj=-1001", "r").is_err());
+        assert!(Rule::parse("name=a => adj=low", "r").is_err());
+        assert!(Rule::parse("uid=9-1 => ignore", "r").is_err());
+        assert!(Rule::parse("name=[ => ignore", "r").is_err());
+        assert!(Rule::parse(r#"cmdline="open => ignore"#, "r").is_err());
+    }
+
+    #[test]
+    fn test_matchers() {
+        let p = process("postgres", "postgres -D /var/lib/pg", 70);
+        let matches = |line: &str| {
+            let rule = Rule::parse(&format!("{line} => ignore"), "r").unwrap();
+            rule.matches(&p, Some("systemd"))
+        };
+        assert!(matches("name=^postgres$"));
+        assert!(matches("cmdline=/var/lib/pg"));
+        assert!(matches("exe=^/usr/bin/"));
+        assert!(matches("cgroup=^/system[.]slice/"));
+        assert!(matches("unit=^postgres[.]service$"));
+        assert!(matches("user=^user70$"));
+        assert!(!matches("container=."));
+        assert!(matches("parent=^systemd$"));
+        assert!(matches("uid=70"));
+        assert!(matches("uid=0-99 name=^post"));
+        assert!(!matches("uid=71-80"));
+        assert!(!matches("name=^postgres$ uid=0"));
+        assert!(!matches("parent=^bash$"));
+    }
+
+    #[test]
+    fn test_field_matchers_ignore_spoofed_names() {
+        // "vim sshd_config" mentions sshd in its command line, a process
+        // can name itself sshd, but only the real one runs /usr/sbin/sshd
+        let mut real = process("sshd", "sshd: /usr/sbin/sshd -D", 0);
+        real.exe = "/usr/sbin/sshd".to_string();
+        let editor = process("vim", "vim /etc/ssh/sshd_config", 1000);
+        let spoof = process("sshd", "sshd", 1000);
+
+        let by_name = rules(
+            RuleMode::All,
+            &["name=sshd => ignore", "cmdline=sshd => ignore"],
+        );
+        let by_exe = rules(RuleMode::All, &["exe=^/usr/sbin/sshd$ => ignore"]);
+        for p in [&real, &editor, &spoof] {
+            assert!(by_name.evaluate(p, None).ignored_by.is_some());
+        }
+        assert!(by_exe.evaluate(&real, None).ignored_by.is_some());
+        assert!(by_exe.evaluate(&editor, None).ignored_by.is_none());
+        assert!(by_exe.evaluate(&spoof, None).ignored_by.is_none());
+    }
+
+    #[test]
+    fn test_field_options_become_rules() {
+        let config = Config {
+            ignore_fields: vec![
+                (ProcessField::Exe, Regex::new("^/usr/s?bin/sshd$").unwrap()),
+                (ProcessField::User, Regex::new("^(root|postgres)$").unwrap()),
+                (
+                    ProcessField::Exe,
+                    Regex::new("^/usr/bin/(dockerd|containerd)$").unwrap(),
+                ),
+            ],
+            prefer_fields: vec![(ProcessField::Unit, Regex::new("^app-.*[.]scope$").unwrap())],
+            ..Config::default()
+        };
+        let set = RuleSet::from_config(&config);
+        assert_eq!(
+            set.rules
+                .iter()
+                .map(|r| r.name.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "--ignore-exe pattern 1 (^/usr/s?bin/sshd$)",
+                "--ignore-user pattern 1 (^(root|postgres)$)",
+                "--ignore-exe pattern 2 (^/usr/bin/(dockerd|containerd)$)",
+                "--prefer-unit pattern 1 (^app-.*[.]scope$)",
+            ]
+        );
+
+        let mut dockerd = process("dockerd", "dockerd", 1000);
+        dockerd.exe = "/usr/bin/dockerd".to_string();
+        assert!(set.evaluate(&dockerd, None).ignored_by.is_some());
+
+        let app = process("firefox", "firefox", 1000).with_cgroup(
+            "/user.slice/user-1000.slice/user@1000.service/app.slice/app-firefox-1.scope"
+                .to_string(),
+        );
+        assert!(set.evaluate(&app, None).preferred_by.is_some());
+    }
+
+    #[test]
+    fn test_slice_matches_any_enclosing_slice() {
+        let app = process("firefox", "firefox", 1000).with_cgroup(
+            "/user.slice/user-1000.slice/user@1000.service/app.slice/app-firefox-1.scope"
+                .to_string(),
+        );
+        let matches = |line: &str| {
+            let rule = Rule::parse(&format!("{line} => ignore"), "r").unwrap();
+            rule.matches(&app, None)
+        };
+        assert!(matches("slice=^app[.]slice$"));
+        assert!(matches("slice=^user-[0-9]+[.]slice$"));
+        assert!(!matches("slice=^system[.]slice$"));
+    }
+
+    #[test]
+    fn test_interactive_penalty() {
+        let config = Config {
+            foreground_weight: 0.1,
+            active_session_weight: 0.5,
+            ..Config::default()
+        };
+        let set = RuleSet::from_config(&config);
+
+        // vim in the foreground of its terminal, in the active session
+        let mut vim = process("vim", "vim notes.txt", 1000);
+        vim.pgrp = 300;
+        vim.tty_nr = 34_816;
+        vim.tpgid = 300;
+        vim.active_session = true;
+        let outcome = set.evaluate(&vim, None);
+        assert_eq!(outcome.matched, ["--interactive-penalty foreground=0.1"]);
+        assert_eq!(outcome.adjust(800), 80);
+
+        // A background job of the same session
+        vim.tpgid = 301;
+        let outcome = set.evaluate(&vim, None);
+        assert_eq!(
+            outcome.matched,
+            ["--interactive-penalty active-session=0.5"]
+        );
+
+        vim.active_session = false;
+        assert!(set.evaluate(&vim, None).matched.is_empty());
+        assert!(RuleSet::from_config(&Config::default())
+            .evaluate(&vim, None)
+            .matched
+            .is_empty());
+
+        let rule = Rule::parse("foreground=yes active-session=no => avoid", "r").unwrap();
+        vim.tpgid = 300;
+        assert!(rule.matches(&vim, None));
+        assert!(Rule::parse("foreground=maybe => avoid", "r").is_err());
+    }
+
+    #[test]
+    fn test_slice_weights() {
+        let config = Config {
+            slice_weights: vec![
+                ("app.slice".to_string(), 2.0),
+                ("user.slice".to_string(), 0.5),
+                ("system.slice".to_string(), 0.0),
+            ],
+            ..Config::default()
+        };
+        let set = RuleSet::from_config(&config);
+
+        let app = process("firefox", "firefox", 1000).with_cgroup(
+            "/user.slice/user-1000.slice/user@1000.service/app.slice/app-firefox-1.scope"
+                .to_string(),
+        );
+        let outcome = set.evaluate(&app, None);
+        assert_eq!(outcome.adjust(100), 100);
+        assert_eq!(
+            outcome.matched,
+            [
+                "--slice-weight app.slice=2",
+                "--slice-weight user.slice=0.5"
+            ]
+        );
+
+        let outcome = set.evaluate(&process("nginx", "nginx", 33), None);
+        assert_eq!(
+            outcome.ignored_by.as_deref(),
+            Some("--slice-weight system.slice=0")
+        );
+    }
+
+    #[test]
+    fn test_oom_score_adj() {
+        let config = Config {
+            rules: vec![Rule::parse("user=^user0$ => adj=-500", "rule 1").unwrap()],
+            oom_score_adj: vec![
+                (Regex::new("^sshd?$").unwrap(), -900),
+                (Regex::new("chrome.*renderer").unwrap(), 500),
+            ],
+            ..Config::default()
+        };
+        let set = RuleSet::from_config(&config);
+        assert!(set.sets_oom_score_adj());
+        assert!(!RuleSet::from_config(&Config::default()).sets_oom_score_adj());
+
+        // The last rule setting a value wins
+        let outcome = set.evaluate(&process("sshd", "/usr/sbin/sshd -D", 0), None);
+        assert_eq!(
+            outcome.oom_score_adj,
+            Some((-900, "--oom-score-adj ^sshd?$=-900".to_string()))
+        );
+        let outcome = set.evaluate(&process("chrome", "chrome --type=renderer", 1000), None);
+        assert_eq!(
+            outcome.oom_score_adj.as_ref().map(|(adj, _)| *adj),
+            Some(500)
+        );
+        assert_eq!(outcome.adjust(100), 100);
+        let outcome = set.evaluate(&process("bash", "bash", 1000), None);
+        assert_eq!(outcome.oom_score_adj, None);
+    }
+
+    #[test]
+    fn test_pod_qos_rules() {
+        let set = rules(
+            RuleMode::All,
+            &["qos=^BestEffort$ => prefer", "qos=^Guaranteed$ => avoid"],
+        );
+        let id = "4f3c2a1b".repeat(8);
+        let pod = |qos: &str| {
+            process("java", "java", 1000).with_cgroup(format!(
+                "/kubepods.slice/kubepods-{qos}.slice/kubepods-{qos}-pod12_34.slice/cri-containerd-{id}.scope"
+            ))
+        };
+        assert!(set
+            .evaluate(&pod("besteffort"), None)
+            .preferred_by
+            .is_some());
+        let burstable = set.evaluate(&pod("burstable"), None);
+        assert!(burstable.preferred_by.is_none() && burstable.avoided_by.is_none());
+
+        let guaranteed = process("java", "java", 1000).with_cgroup(format!(
+            "/kubepods.slice/kubepods-pod12_34.slice/cri-containerd-{id}.scope"
+        ));
+        assert!(set.evaluate(&guaranteed, None).avoided_by.is_some());
+
+        let matches = |line: &str, p: &ProcessInfo| {
+            Rule::parse(&format!("{line} => ignore"), "r")
+                .unwrap()
+                .matches(p, None)
+        };
+        assert!(matches("runtime=^containerd$ pod=^12-34$", &guaranteed));
+        assert!(!matches(
+            "qos=^(Burstable|)$",
+            &process("java", "java", 1000)
+        ));
+    }
+
+    #[test]
+    fn test_accumulate_mode_combines_rules() {
+        let set = rules(
+            RuleMode::All,
+            &[
+                "name=^java$ => score*2 signals=SIGINT,SIGKILL",
+                "uid=1000 => score*1.5 score+10 prefer",
+                "cmdline=batch => avoid kill=group notify=/bin/a",
+                "name=^java$ => signals=SIGTERM notify=/bin/b",
+            ],
+        );
+        let outcome = set.evaluate(&process("java", "java batch", 1000), None);
+
+        assert_eq!(outcome.matched.len(), 4);
+        assert_eq!(outcome.adjust(100), 310);
+        assert_eq!(outcome.signals, Some(vec![Signal::SIGTERM]));
+        assert_eq!(outcome.kill_scope, Some(KillScope::Group));
+        assert_eq!(outcome.notify, vec!["/bin/a", "/bin/b"]);
+        assert_eq!(outcome.preferred_by.as_deref(), Some("rule 2"));
+        assert_eq!(outcome.avoided_by.as_deref(), Some("rule 3"));
+        assert_eq!(outcome.ignored_by, None);
+    }
+
+    #[test]
+    fn test_first_mode_stops_at_first_match() {
+        let lines = [
+            "name=^nomatch$ => ignore",
+            "name=^java$ => score*2",
+            "uid=1000 => ignore",
+        ];
+        let p = process("java", "java", 1000);
+
+        let first = rules(RuleMode::First, &lines).evaluate(&p, None);
+        assert_eq!(first.matched, vec!["rule 2"]);
+        assert_eq!(first.adjust(100), 200);
+        assert_eq!(first.ignored_by, None);
+
+        let all = rules(RuleMode::All, &lines).evaluate(&p, None);
+        assert_eq!(all.matched, vec!["rule 2", "rule 3"]);
+        assert_eq!(all.ignored_by.as_deref(), Some("rule 3"));
+    }
+
+    #[test]
+    fn test_first_mode_still_applies_option_rules() {
+        let config = Config {
+            rules: vec![Rule::parse("uid=1000 => prefer", "rule 1").unwrap()],
+            rule_mode: RuleMode::First,
+            slice_weights: vec![("system.slice".to_string(), 0.0)],
+            oom_score_adj: vec![(Regex::new("^(db|mysqld)$").unwrap(), -900)],
+            ..Config::default()
+        };
+        let set = RuleSet::from_config(&config);
+
+        let db = set.evaluate(&process("db", "db", 1000), None);
+        assert_eq!(db.preferred_by.as_deref(), Some("rule 1"));
+        assert!(db.ignored_by.is_some());
+        assert_eq!(db.oom_score_adj.map(|(adj, _)| adj), Some(-900));
+    }
+
+    #[test]
+    fn test_score_offsets_saturate() {
+        let set = rules(
+            RuleMode::All,
+            &[
+                &format!("uid=1000 => score+{}", i64::MAX),
+                &format!("uid=1000 => score+{}", i64::MAX),
+            ],
+        );
+        let outcome = set.evaluate(&process("java", "java", 1000), None);
+        assert_eq!(outcome.adjust(1000), i64::MAX);
+    }
+
+    #[test]
+    fn test_no_match_is_neutral() {
+        let set = rules(RuleMode::All, &["name=^java$ => ignore score*3"]);
+        let outcome = set.evaluate(&process("bash", "bash", 1000), None);
+        assert_eq!(outcome, RuleOutcome::default());
+        assert_eq!(outcome.adjust(123), 123);
+    }
+
+    #[test]
+    fn test_legacy_patterns_become_rules() {
+        let config = Config {
+            rules: vec![Rule::parse("name=^cron$ => score+5", "rule 1").unwrap()],
+            ignore: vec![Regex::new("^(sshd|cron)$").unwrap()],
+            avoid: vec![Regex::new("^cro?n$").unwrap()],
+            prefer: vec![Regex::new("--tabs|^browser$").unwrap()],
+            ..Config::default()
+        };
+        let set = RuleSet::from_config(&config);
+
+        let cron = set.evaluate(&process("cron", "/usr/sbin/cron", 0), None);
+        assert_eq!(
+            cron.ignored_by.as_deref(),
+            Some("--ignore pattern 1 (^(sshd|cron)$)")
+        );
+        assert_eq!(
+            cron.avoided_by.as_deref(),
+            Some("--avoid pattern 1 (^cro?n$)")
+        );
+        assert_eq!(cron.matched[0], "rule 1");
+
+        // Patterns match the command line as well as the name
+        let tab = set.evaluate(&process("web", "web --tabs", 1000), None);
+        assert_eq!(
+            tab.preferred_by.as_deref(),
+            Some("--prefer pattern 1 (--tabs|^browser$)")
+        );
+
+        // In first-match mode --ignore still wins over a matching user rule
+        let first = RuleSet {
+            mode: RuleMode::First,
+            ..set
+        };
+        let cron = first.evaluate(&process("cron", "/usr/sbin/cron", 0), None);
+        assert_eq!(
+            cron.ignored_by.as_deref(),
+            Some("--ignore pattern 1 (^(sshd|cron)$)")
+        );
+        assert_eq!(cron.matched[0], "rule 1");
+    }
+
+    #[test]
+    fn test_load_rules_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rules.conf");
+        fs::write(
+            &path,
+            "# browsers go first\nname=^firefox$ => prefer\n\n  name=^db$ => avoid\n",
+        )
+        .unwrap();
+        let path = path.to_string_lossy().into_owned();
+
+        let rules = load_rules_file(&path).unwrap();
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[1].name, format!("rule {path}:4 (name=^db$ => avoid)"));
+
+        fs::write(&path, "name=^a$ => prefer\nbroken\n").unwrap();
+        let err = load_rules_file(&path).unwrap_err();
+        assert!(format!("{err:#}").contains(":2"));
+    }
+
+    #[test]
+    fn test_parse_rule_mode() {
+        assert_eq!("first".parse::<RuleMode>().unwrap(), RuleMode::First);
+        assert_eq!("all".parse::<RuleMode>().unwrap(), RuleMode::All);
+        assert!("some".parse::<RuleMode>().is_err());
+        assert_eq!(RuleMode::default(), RuleMode::All);
+    }
+}
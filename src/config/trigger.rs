@@ -0,0 +1,615 @@
+// Threshold trigger: how the memory and swap conditions combine
+//
+// By default oom_guard acts when memory and swap are both below their
+// thresholds. --trigger picks another combination, or a condition such as
+// "mem < 5% && (swap < 10% || psi_full > 30)". A machine without swap has
+// none to fall back on, so "all" and "any" then look at memory alone,
+// while swap comparisons in an expression never match, like pressure
+// comparisons on kernels without PSI.
+
+use crate::monitor::{MemInfo, MemoryPressure};
+use anyhow::{bail, Context, Result};
+
+/// When thresholds trigger action
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Trigger {
+    /// Memory and swap both below their thresholds
+    #[default]
+    All,
+    /// Memory or swap below its threshold
+    Any,
+    /// Memory below its threshold, whatever the swap
+    MemOnly,
+    /// A condition over memory, swap and pressure. Processes are killed
+    /// rather than terminated once memory is below its kill threshold
+    Expr(TriggerExpr),
+}
+
+impl Trigger {
+    /// Whether one threshold level fires, given whether memory and swap are
+    /// below its thresholds; `swap` is None without swap. An expression is
+    /// evaluated with [`TriggerExpr::matches`] instead: here memory decides
+    pub fn combine(&self, mem: bool, swap: Option<bool>) -> bool {
+        match self {
+            Self::All => mem && swap.unwrap_or(true),
+            Self::Any => mem || swap.unwrap_or(false),
+            Self::MemOnly | Self::Expr(_) => mem,
+        }
+    }
+
+    /// Whether free swap can make the trigger fire, on a machine with or
+    /// without swap
+    pub fn uses_swap(&self, has_swap: bool) -> bool {
+        has_swap
+            && match self {
+                Self::All | Self::Any => true,
+                Self::MemOnly => false,
+                Self::Expr(expr) => expr.uses(|metric| metric == Metric::Swap),
+            }
+    }
+
+    /// Whether evaluating the trigger needs memory pressure
+    pub fn uses_pressure(&self) -> bool {
+        matches!(self, Self::Expr(expr) if expr.uses(Metric::is_pressure))
+    }
+
+    /// Warnings about a trigger that cannot work as intended on a machine
+    /// with or without swap and PSI
+    pub fn check_layout(&self, has_swap: bool, has_pressure: bool) -> Vec<String> {
+        let Self::Expr(expr) = self else {
+            return Vec::new();
+        };
+        let mut missing = Vec::new();
+        if !has_swap && expr.uses(|metric| metric == Metric::Swap) {
+            missing.push("no swap");
+        }
+        if !has_pressure && expr.uses(Metric::is_pressure) {
+            missing.push("no memory PSI");
+        }
+        if missing.is_empty() {
+            return Vec::new();
+        }
+        let missing = missing.join(", ");
+
+        let available = |metric: Metric| match metric {
+            Metric::Mem => true,
+            Metric::Swap => has_swap,
+            _ => has_pressure,
+        };
+        vec![match expr.root.reach(&available) {
+            Reach::Never => {
+                format!("Trigger '{expr}' can never fire on this machine ({missing})")
+            }
+            Reach::Always => {
+                format!("Trigger '{expr}' always holds on this machine ({missing}) and fires on every check")
+            }
+            Reach::Sometimes => format!(
+                "Trigger '{expr}': comparisons on unavailable values never match on this machine ({missing})"
+            ),
+        }]
+    }
+}
+
+impl std::str::FromStr for Trigger {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim() {
+            "all" => Ok(Self::All),
+            "any" => Ok(Self::Any),
+            "mem-only" => Ok(Self::MemOnly),
+            expr => Ok(Self::Expr(expr.parse()?)),
+        }
+    }
+}
+
+impl std::fmt::Display for Trigger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::All => f.write_str("all"),
+            Self::Any => f.write_str("any"),
+            Self::MemOnly => f.write_str("mem-only"),
+            Self::Expr(expr) => write!(f, "{expr}"),
+        }
+    }
+}
+
+/// Value compared in a trigger expression
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Metric {
+    /// Available memory
+    Mem,
+    /// Free swap
+    Swap,
+    /// PSI "some" and "full" averages over 10 and 60 seconds, in percent
+    PsiSome,
+    PsiFull,
+    PsiSome60,
+    PsiFull60,
+}
+
+impl Metric {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "mem" => Self::Mem,
+            "swap" => Self::Swap,
+            "psi_some" => Self::PsiSome,
+            "psi_full" => Self::PsiFull,
+            "psi_some60" => Self::PsiSome60,
+            "psi_full60" => Self::PsiFull60,
+            _ => return None,
+        })
+    }
+
+    const fn is_pressure(self) -> bool {
+        !matches!(self, Self::Mem | Self::Swap)
+    }
+
+    /// Current value in the unit of `threshold`, None if unavailable
+    fn value(
+        self,
+        threshold: Threshold,
+        meminfo: &MemInfo,
+        pressure: Option<&MemoryPressure>,
+    ) -> Option<f64> {
+        let percent = matches!(threshold, Threshold::Percent(_));
+        match self {
+            Self::Mem if percent => Some(meminfo.mem_available_percent()),
+            Self::Mem => Some(meminfo.mem_available as f64),
+            Self::Swap if meminfo.swap_total == 0 => None,
+            Self::Swap if percent => Some(meminfo.swap_free_percent()),
+            Self::Swap => Some(meminfo.swap_free as f64),
+            Self::PsiSome => pressure.map(|p| p.some_avg10),
+            Self::PsiFull => pressure.map(|p| p.full_avg10),
+            Self::PsiSome60 => pressure.map(|p| p.some_avg60),
+            Self::PsiFull60 => pressure.map(|p| p.full_avg60),
+        }
+    }
+}
+
+/// Right-hand side of a comparison
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Threshold {
+    Percent(f64),
+    Kib(u64),
+}
+
+impl Threshold {
+    const fn value(self) -> f64 {
+        match self {
+            Self::Percent(percent) => percent,
+            Self::Kib(kb) => kb as f64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CmpOp {
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Compare(Metric, CmpOp, Threshold),
+    Not(Box<Self>),
+    And(Box<Self>, Box<Self>),
+    Or(Box<Self>, Box<Self>),
+}
+
+/// Whether a condition can hold, given which values are available
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Reach {
+    Never,
+    Sometimes,
+    Always,
+}
+
+impl Node {
+    fn matches(&self, meminfo: &MemInfo, pressure: Option<&MemoryPressure>) -> bool {
+        match self {
+            Self::Compare(metric, op, threshold) => metric
+                .value(*threshold, meminfo, pressure)
+                .is_some_and(|value| {
+                    let threshold = threshold.value();
+                    match op {
+                        CmpOp::Less => value < threshold,
+                        CmpOp::LessEqual => value <= threshold,
+                        CmpOp::Greater => value > threshold,
+                        CmpOp::GreaterEqual => value >= threshold,
+                    }
+                }),
+            Self::Not(node) => !node.matches(meminfo, pressure),
+            Self::And(a, b) => a.matches(meminfo, pressure) && b.matches(meminfo, pressure),
+            Self::Or(a, b) => a.matches(meminfo, pressure) || b.matches(meminfo, pressure),
+        }
+    }
+
+    fn uses(&self, pred: &impl Fn(Metric) -> bool) -> bool {
+        match self {
+            Self::Compare(metric, _, _) => pred(*metric),
+            Self::Not(node) => node.uses(pred),
+            Self::And(a, b) | Self::Or(a, b) => a.uses(pred) || b.uses(pred),
+        }
+    }
+
+    fn reach(&self, available: &impl Fn(Metric) -> bool) -> Reach {
+        match self {
+            Self::Compare(metric, _, _) if available(*metric) => Reach::Sometimes,
+            Self::Compare(..) => Reach::Never,
+            Self::Not(node) => match node.reach(available) {
+                Reach::Never => Reach::Always,
+                Reach::Sometimes => Reach::Sometimes,
+                Reach::Always => Reach::Never,
+            },
+            Self::And(a, b) => match (a.reach(available), b.reach(available)) {
+                (Reach::Never, _) | (_, Reach::Never) => Reach::Never,
+                (Reach::Always, Reach::Always) => Reach::Always,
+                _ => Reach::Sometimes,
+            },
+            Self::Or(a, b) => match (a.reach(available), b.reach(available)) {
+                (Reach::Always, _) | (_, Reach::Always) => Reach::Always,
+                (Reach::Never, Reach::Never) => Reach::Never,
+                _ => Reach::Sometimes,
+            },
+        }
+    }
+}
+
+/// A trigger condition, e.g. "mem < 5% && (swap < 10% || psi_full > 30)"
+///
+/// Memory and swap compare to a percentage or a size (512M, 2G), pressure
+/// to a percentage; comparisons combine with &&, ||, ! and parentheses
+#[derive(Debug, Clone, PartialEq)]
+pub struct TriggerExpr {
+    source: String,
+    root: Node,
+}
+
+impl TriggerExpr {
+    /// Whether the condition holds
+    pub fn matches(&self, meminfo: &MemInfo, pressure: Option<&MemoryPressure>) -> bool {
+        self.root.matches(meminfo, pressure)
+    }
+
+    fn uses(&self, pred: impl Fn(Metric) -> bool) -> bool {
+        self.root.uses(&pred)
+    }
+}
+
+impl std::str::FromStr for TriggerExpr {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let tokens = tokenize(s).with_context(|| format!("Invalid trigger '{s}'"))?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            depth: 0,
+        };
+        let root = parser
+            .parse_or()
+            .and_then(|root| match parser.peek() {
+                None => Ok(root),
+                Some(token) => bail!("unexpected '{token}'"),
+            })
+            .with_context(|| {
+                format!("Invalid trigger '{s}' (expected all, any, mem-only or an expression)")
+            })?;
+        Ok(Self {
+            source: s.trim().to_string(),
+            root,
+        })
+    }
+}
+
+impl std::fmt::Display for TriggerExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    /// Number with its unit, e.g. "%", "M" or ""
+    Number(f64, String),
+    Op(&'static str),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ident(name) => f.write_str(name),
+            Self::Number(value, unit) => write!(f, "{value}{unit}"),
+            Self::Op(op) => f.write_str(op),
+        }
+    }
+}
+
+const OPERATORS: [&str; 9] = ["&&", "||", "<=", ">=", "<", ">", "!", "(", ")"];
+
+fn tokenize(s: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = s.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let len = if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+            tokens.push(Token::Op(op));
+            op.len()
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..len].to_string()));
+            len
+        } else if c.is_ascii_digit() || c == '.' {
+            let digits = rest
+                .find(|c: char| !c.is_ascii_digit() && c != '.')
+                .unwrap_or(rest.len());
+            let len = rest[digits..]
+                .find(|c: char| !c.is_ascii_alphabetic() && c != '%')
+                .map_or(rest.len(), |unit| digits + unit);
+            let value = rest[..digits]
+                .parse()
+                .with_context(|| format!("invalid number '{}'", &rest[..digits]))?;
+            tokens.push(Token::Number(value, rest[digits..len].to_string()));
+            len
+        } else {
+            bail!("unexpected '{c}'");
+        };
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+/// Deepest nesting of "!" and parentheses, so that a hostile expression
+/// cannot overflow the stack of the recursive parser
+const MAX_DEPTH: usize = 64;
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, op: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Op(o)) if *o == op);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn parse_or(&mut self) -> Result<Node> {
+        let mut node = self.parse_and()?;
+        while self.eat("||") {
+            node = Node::Or(Box::new(node), Box::new(self.parse_and()?));
+        }
+        Ok(node)
+    }
+
+    fn parse_and(&mut self) -> Result<Node> {
+        let mut node = self.parse_unary()?;
+        while self.eat("&&") {
+            node = Node::And(Box::new(node), Box::new(self.parse_unary()?));
+        }
+        Ok(node)
+    }
+
+    fn parse_unary(&mut self) -> Result<Node> {
+        if !matches!(self.peek(), Some(Token::Op("!" | "("))) {
+            return self.parse_comparison();
+        }
+        if self.depth == MAX_DEPTH {
+            bail!("nested deeper than {MAX_DEPTH} levels");
+        }
+        self.depth += 1;
+        let node = if self.eat("!") {
+            self.parse_unary().map(|node| Node::Not(Box::new(node)))
+        } else {
+            self.pos += 1;
+            self.parse_or().and_then(|node| {
+                if self.eat(")") {
+                    Ok(node)
+                } else {
+                    bail!("missing ')'")
+                }
+            })
+        };
+        self.depth -= 1;
+        node
+    }
+
+    fn parse_comparison(&mut self) -> Result<Node> {
+        let metric = match self.next() {
+            Some(Token::Ident(name)) => Metric::parse(&name).with_context(|| {
+                format!("unknown value '{name}' (expected mem, swap, psi_some, psi_full, psi_some60 or psi_full60)")
+            })?,
+            Some(token) => bail!("expected a comparison, found '{token}'"),
+            None => bail!("expected a comparison"),
+        };
+        let op = match self.next() {
+            Some(Token::Op("<")) => CmpOp::Less,
+            Some(Token::Op("<=")) => CmpOp::LessEqual,
+            Some(Token::Op(">")) => CmpOp::Greater,
+            Some(Token::Op(">=")) => CmpOp::GreaterEqual,
+            _ => bail!("expected <, <=, > or >= after '{}'", metric_name(metric)),
+        };
+        let threshold = match self.next() {
+            Some(Token::Number(value, unit)) => parse_threshold(metric, value, &unit)?,
+            _ => bail!("expected a number after '{}'", metric_name(metric)),
+        };
+        Ok(Node::Compare(metric, op, threshold))
+    }
+}
+
+const fn metric_name(metric: Metric) -> &'static str {
+    match metric {
+        Metric::Mem => "mem",
+        Metric::Swap => "swap",
+        Metric::PsiSome => "psi_some",
+        Metric::PsiFull => "psi_full",
+        Metric::PsiSome60 => "psi_some60",
+        Metric::PsiFull60 => "psi_full60",
+    }
+}
+
+/// Threshold of a comparison: a percentage, or for memory and swap a size
+/// in KiB, MiB or GiB
+fn parse_threshold(metric: Metric, value: f64, unit: &str) -> Result<Threshold> {
+    let name = metric_name(metric);
+    let kib = match unit {
+        "%" => {
+            if !(0.0..=100.0).contains(&value) {
+                bail!("{value}% is not a percentage");
+            }
+            return Ok(Threshold::Percent(value));
+        }
+        "" if metric.is_pressure() => return Ok(Threshold::Percent(value)),
+        "" => bail!("{name} needs a unit: a percentage like 5% or a size like 512M"),
+        "K" | "KiB" => 1.0,
+        "M" | "MiB" => 1024.0,
+        "G" | "GiB" => 1024.0 * 1024.0,
+        _ => bail!("unknown unit '{unit}' (expected %, K, M or G)"),
+    };
+    if metric.is_pressure() {
+        bail!("{name} is a percentage, not a size");
+    }
+    Ok(Threshold::Kib((value * kib) as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meminfo(mem_available: u64, swap_total: u64, swap_free: u64) -> MemInfo {
+        MemInfo {
+            mem_total: 16_000_000,
+            mem_available,
+            swap_total,
+            swap_free,
+        }
+    }
+
+    fn expr(s: &str) -> TriggerExpr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_trigger() {
+        assert_eq!("all".parse::<Trigger>().unwrap(), Trigger::All);
+        assert_eq!(" any ".parse::<Trigger>().unwrap(), Trigger::Any);
+        assert_eq!("mem-only".parse::<Trigger>().unwrap(), Trigger::MemOnly);
+        let trigger: Trigger = "mem < 5% && (swap < 10% || psi_full > 30)".parse().unwrap();
+        assert_eq!(
+            trigger.to_string(),
+            "mem < 5% && (swap < 10% || psi_full > 30)"
+        );
+        assert!(trigger.uses_pressure());
+        assert!(!Trigger::All.uses_pressure());
+
+        for invalid in [
+            "",
+            "mem",
+            "mem < 5",
+            "mem < 5% &&",
+            "(mem < 5%",
+            "mem < 5% swap < 1%",
+            "ram < 5%",
+            "mem = 5%",
+            "mem < 120%",
+            "mem < 5X",
+            "psi_full > 1G",
+            "mem < 5% # comment",
+        ] {
+            assert!(invalid.parse::<Trigger>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_parse_nesting_limit() {
+        let nested = |open: &str, close: &str, levels: usize| {
+            format!("{}mem < 5%{}", open.repeat(levels), close.repeat(levels))
+        };
+        assert!(nested("!", "", MAX_DEPTH).parse::<Trigger>().is_ok());
+        assert!(nested("(", ")", MAX_DEPTH).parse::<Trigger>().is_ok());
+        for deep in [
+            nested("!", "", MAX_DEPTH + 1),
+            nested("(", ")", MAX_DEPTH + 1),
+            nested("!(", ")", 100_000),
+        ] {
+            let err = deep.parse::<Trigger>().unwrap_err();
+            assert!(format!("{err:#}").contains("nested deeper"), "{err:#}");
+        }
+    }
+
+    #[test]
+    fn test_combine() {
+        assert!(Trigger::All.combine(true, Some(true)));
+        assert!(!Trigger::All.combine(true, Some(false)));
+        assert!(Trigger::Any.combine(false, Some(true)));
+        assert!(Trigger::MemOnly.combine(true, Some(false)));
+        // Without swap, only memory counts
+        assert!(Trigger::All.combine(true, None));
+        assert!(!Trigger::Any.combine(false, None));
+    }
+
+    #[test]
+    fn test_expression_matches() {
+        let pressure = MemoryPressure {
+            full_avg10: 40.0,
+            ..MemoryPressure::default()
+        };
+        let trigger = expr("mem < 5% && (swap < 10% || psi_full > 30)");
+        // 4% available, 50% swap free
+        let low = meminfo(640_000, 8_000_000, 4_000_000);
+        assert!(trigger.matches(&low, Some(&pressure)));
+        assert!(!trigger.matches(&low, None));
+        assert!(trigger.matches(&meminfo(640_000, 8_000_000, 400_000), None));
+        assert!(!trigger.matches(&meminfo(1_600_000, 8_000_000, 400_000), None));
+
+        // Sizes, negation and precedence of && over ||
+        let sized = expr("mem <= 512M || !(swap >= 1G) && psi_some60 > 5");
+        assert!(sized.matches(&meminfo(524_288, 0, 0), None));
+        assert!(!sized.matches(&meminfo(600_000, 8_000_000, 400_000), None));
+        assert!(sized.matches(
+            &meminfo(600_000, 8_000_000, 400_000),
+            Some(&MemoryPressure {
+                some_avg60: 6.0,
+                ..MemoryPressure::default()
+            })
+        ));
+    }
+
+    #[test]
+    fn test_check_layout() {
+        let trigger: Trigger = "mem < 5% && swap < 10%".parse().unwrap();
+        assert!(trigger.check_layout(true, false).is_empty());
+        assert_eq!(
+            trigger.check_layout(false, true),
+            ["Trigger 'mem < 5% && swap < 10%' can never fire on this machine (no swap)"]
+        );
+
+        let trigger: Trigger = "mem < 5% && (swap < 10% || psi_full > 30)".parse().unwrap();
+        assert!(trigger.check_layout(false, true)[0].contains("never match"));
+        assert!(trigger.check_layout(false, false)[0].contains("can never fire"));
+
+        let trigger: Trigger = "mem < 5% || !(psi_some > 10)".parse().unwrap();
+        assert!(trigger.check_layout(true, false)[0].contains("fires on every check"));
+
+        assert!(Trigger::All.check_layout(false, false).is_empty());
+    }
+}
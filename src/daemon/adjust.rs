@@ -0,0 +1,157 @@
+// Automatic oom_score_adj management
+//
+// Processes matched by --oom-score-adj options or adj= rule actions are
+// kept at their configured oom_score_adj, so the kernel OOM killer ranks
+// them the way oom_guard does. New processes are adjusted on the first
+// scan that sees them. Original values are remembered by PID and start
+// time in clock ticks, so that a PID reused within the same second is
+// never mistaken for the process adjusted earlier, and can be restored on
+// shutdown.
+
+use crate::monitor::ProcessInfo;
+use std::collections::{HashMap, HashSet};
+
+/// A process oom_guard set, or tried to set, the oom_score_adj of
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Adjusted {
+    start_ticks: u64,
+    target: i32,
+    /// Value before the first change, None if it was never written (dry
+    /// run, or the write failed)
+    original: Option<i32>,
+}
+
+/// Processes whose oom_score_adj is managed
+#[derive(Debug, Clone, Default)]
+pub struct AdjustTracker {
+    adjusted: HashMap<i32, Adjusted>,
+}
+
+impl AdjustTracker {
+    /// Whether setting `process` to `target` was already tried without
+    /// writing it, so that it is reported only once
+    pub fn is_pending(&self, process: &ProcessInfo, target: i32) -> bool {
+        self.adjusted.get(&process.pid).is_some_and(|adjusted| {
+            adjusted.start_ticks == process.start_ticks
+                && adjusted.target == target
+                && adjusted.original.is_none()
+        })
+    }
+
+    /// Remember that `process` was set to `target`, or only tried to be
+    /// when `written` is false. The first original value is kept
+    pub fn record(&mut self, process: &ProcessInfo, target: i32, written: bool) {
+        let earlier = self
+            .adjusted
+            .get(&process.pid)
+            .filter(|adjusted| adjusted.start_ticks == process.start_ticks)
+            .and_then(|adjusted| adjusted.original);
+        let original = earlier.or_else(|| written.then_some(process.oom_score_adj));
+        self.adjusted.insert(
+            process.pid,
+            Adjusted {
+                start_ticks: process.start_ticks,
+                target,
+                original,
+            },
+        );
+    }
+
+    /// Forget processes that exited, including PIDs now used by another
+    /// process
+    pub fn forget_exited(&mut self, processes: &[ProcessInfo]) {
+        let running: HashSet<(i32, u64)> =
+            processes.iter().map(|p| (p.pid, p.start_ticks)).collect();
+        self.adjusted
+            .retain(|pid, adjusted| running.contains(&(*pid, adjusted.start_ticks)));
+    }
+
+    /// Number of processes whose oom_score_adj was changed
+    pub fn len(&self) -> usize {
+        self.adjusted
+            .values()
+            .filter(|adjusted| adjusted.original.is_some())
+            .count()
+    }
+
+    /// PID, start ticks and original value of every changed process, sorted
+    /// by PID; the tracker is left empty
+    pub fn take_originals(&mut self) -> Vec<(i32, u64, i32)> {
+        let mut originals: Vec<(i32, u64, i32)> = self
+            .adjusted
+            .drain()
+            .filter_map(|(pid, adjusted)| {
+                adjusted
+                    .original
+                    .map(|original| (pid, adjusted.start_ticks, original))
+            })
+            .collect();
+        originals.sort_unstable();
+        originals
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(pid: i32, start_ticks: u64, oom_score_adj: i32) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            oom_score_adj,
+            start_ticks,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_keeps_first_original_value() {
+        let mut tracker = AdjustTracker::default();
+        tracker.record(&process(10, 100, 0), -900, true);
+        // Changed again later, e.g. after something else reset it
+        tracker.record(&process(10, 100, 200), -900, true);
+        tracker.record(&process(11, 100, 300), 500, false);
+
+        assert_eq!(tracker.len(), 1);
+        assert!(tracker.is_pending(&process(11, 100, 300), 500));
+        assert!(!tracker.is_pending(&process(11, 100, 300), 600));
+        assert!(!tracker.is_pending(&process(10, 100, 200), -900));
+        assert_eq!(tracker.take_originals(), [(10, 100, 0)]);
+        assert_eq!(tracker.len(), 0);
+    }
+
+    #[test]
+    fn test_forgets_exited_and_reused_pids() {
+        let mut tracker = AdjustTracker::default();
+        tracker.record(&process(10, 100, 0), -900, true);
+        tracker.record(&process(11, 100, 0), -900, true);
+        tracker.record(&process(12, 100, 0), -900, true);
+
+        // 11 exited, and 12 is now another process
+        tracker.forget_exited(&[process(10, 100, -900), process(12, 200, 0)]);
+        assert_eq!(tracker.take_originals(), [(10, 100, 0)]);
+
+        // A new process on a reused PID gets its own original value
+        tracker.record(&process(12, 100, 0), 500, true);
+        tracker.record(&process(12, 200, 100), 500, true);
+        assert_eq!(tracker.take_originals(), [(12, 200, 100)]);
+    }
+
+    #[test]
+    fn test_pid_reused_within_a_second_is_another_process() {
+        let mut tracker = AdjustTracker::default();
+        let first = ProcessInfo {
+            start_time: 1_700_000_000,
+            ..process(10, 5_000, 0)
+        };
+        tracker.record(&first, -900, true);
+
+        // Same PID and start second, but started 20 ticks later
+        let second = ProcessInfo {
+            start_time: first.start_time,
+            ..process(10, 5_020, 0)
+        };
+        tracker.forget_exited(std::slice::from_ref(&second));
+        assert_eq!(tracker.len(), 0);
+    }
+}
@@ -0,0 +1,66 @@
+// Time source for the daemon: the system clock or a virtual clock for replay
+
+use std::time::{Duration, Instant};
+
+/// Clock used for cooldowns, budgets and timeouts
+#[derive(Debug, Clone, Copy)]
+pub enum Clock {
+    /// Real monotonic time
+    System,
+    /// Time that only moves when advanced explicitly
+    Virtual { start: Instant, elapsed: Duration },
+}
+
+impl Clock {
+    /// A virtual clock starting at zero
+    pub fn virtual_clock() -> Self {
+        Self::Virtual {
+            start: Instant::now(),
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    /// Current time
+    pub fn now(&self) -> Instant {
+        match self {
+            Self::System => Instant::now(),
+            Self::Virtual { start, elapsed } => *start + *elapsed,
+        }
+    }
+
+    /// Time passed since `earlier`
+    pub fn since(&self, earlier: Instant) -> Duration {
+        self.now().saturating_duration_since(earlier)
+    }
+
+    /// Move a virtual clock to `elapsed` after its start (no-op for the
+    /// system clock)
+    pub const fn set_elapsed(&mut self, to: Duration) {
+        if let Self::Virtual { elapsed, .. } = self {
+            *elapsed = to;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_virtual_clock_only_moves_when_set() {
+        let mut clock = Clock::virtual_clock();
+        let start = clock.now();
+        assert_eq!(clock.now(), start);
+
+        clock.set_elapsed(Duration::from_secs(90));
+        assert_eq!(clock.since(start), Duration::from_secs(90));
+    }
+
+    #[test]
+    fn test_system_clock_ignores_set_elapsed() {
+        let mut clock = Clock::System;
+        let before = Instant::now();
+        clock.set_elapsed(Duration::from_secs(3600));
+        assert!(clock.since(before) < Duration::from_secs(60));
+    }
+}
@@ -0,0 +1,84 @@
+// Candidate ranking table for the explain command
+
+use super::service::{Explanation, Verdict};
+use crate::sanitize_for_log;
+use std::fmt::Write;
+
+/// Format explanations as a table, one process per line
+pub fn format_table(explanations: &[Explanation]) -> String {
+    let mut out = format!(
+        "{:>4}  {:>7}  {:>6}  {:>10}  {:>5}  {:>5}  {:<9}  {:<16}  {}\n",
+        "RANK", "PID", "UID", "RSS(KiB)", "OOM", "ADJ", "VERDICT", "NAME", "REASON"
+    );
+
+    for e in explanations {
+        let rank = e.rank.map_or_else(|| "-".to_string(), |r| r.to_string());
+        let _ = writeln!(
+            out,
+            "{:>4}  {:>7}  {:>6}  {:>10}  {:>5}  {:>5}  {:<9}  {:<16}  {}",
+            rank,
+            e.process.pid,
+            e.process.uid,
+            e.process.rss_kb,
+            e.process.oom_score,
+            e.process.oom_score_adj,
+            e.verdict,
+            sanitize_for_log(&e.process.name),
+            sanitize_for_log(&e.reason)
+        );
+    }
+
+    let killable = explanations
+        .iter()
+        .filter(|e| e.verdict != Verdict::Ignored)
+        .count();
+    let _ = write!(
+        out,
+        "{} process(es), {killable} killable, {} ignored",
+        explanations.len(),
+        explanations.len() - killable
+    );
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monitor::ProcessInfo;
+
+    fn explanation(pid: i32, rank: Option<usize>, verdict: Verdict, reason: &str) -> Explanation {
+        Explanation {
+            process: ProcessInfo {
+                pid,
+                name: format!("app-{pid}"),
+                cmdline: format!("/usr/bin/app-{pid}"),
+                rss_kb: 1024,
+                oom_score: 100,
+                oom_score_adj: 0,
+                uid: 1000,
+                state: 'S',
+                is_zombie: false,
+                ..Default::default()
+            },
+            rank,
+            verdict,
+            reason: reason.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_format_table() {
+        let table = format_table(&[
+            explanation(10, Some(1), Verdict::Candidate, "candidate; oom_score 100"),
+            explanation(1, None, Verdict::Ignored, "pid 1 (init)"),
+        ]);
+        let lines: Vec<&str> = table.lines().collect();
+
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("RANK"));
+        assert!(lines[1].contains("candidate; oom_score 100"));
+        assert!(lines[2].trim_start().starts_with("- "));
+        assert!(lines[2].ends_with("pid 1 (init)"));
+        assert_eq!(lines[3], "2 process(es), 1 killable, 1 ignored");
+    }
+}
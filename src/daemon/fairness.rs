@@ -0,0 +1,204 @@
+// Per-user fairness: victims come from the user using the most memory
+//
+// On shared machines the process with the highest score is not always the
+// one to blame: a user's editor may outscore another user's runaway
+// notebook. With --fair-users, killable processes are first summed per uid
+// and victims are taken from the heaviest user, relative to the user's
+// allowance when one is configured.
+
+use crate::config::Config;
+use crate::monitor::{MemInfo, ProcessInfo};
+use std::collections::HashMap;
+
+/// Memory used by the killable processes of one user
+#[derive(Debug, Clone)]
+pub struct UserUsage {
+    pub uid: u32,
+    pub user: String,
+    pub rss_kb: u64,
+    pub processes: usize,
+    /// Memory the user may use before being ranked by it, from --user-allowance
+    pub allowance_kb: Option<u64>,
+}
+
+impl UserUsage {
+    /// Value users are ranked by: RSS as a fraction of the allowance, or
+    /// plain RSS without allowances
+    fn load(&self) -> f64 {
+        self.allowance_kb.map_or(self.rss_kb as f64, |allowance| {
+            self.rss_kb as f64 / allowance.max(1) as f64
+        })
+    }
+
+    /// Name of the user, or the uid if it has none
+    pub fn name(&self) -> String {
+        if self.user.is_empty() {
+            format!("uid {}", self.uid)
+        } else {
+            self.user.clone()
+        }
+    }
+
+    /// Summary of the usage, e.g. "9.00 GiB in 12 processes (112% of 8.00 GiB)"
+    pub fn summary(&self) -> String {
+        let used = MemInfo::format_size(self.rss_kb);
+        let processes = if self.processes == 1 {
+            "1 process".to_string()
+        } else {
+            format!("{} processes", self.processes)
+        };
+        self.allowance_kb.map_or_else(
+            || format!("{used} in {processes}"),
+            |allowance| {
+                format!(
+                    "{used} in {processes} ({:.0}% of {})",
+                    self.load() * 100.0,
+                    MemInfo::format_size(allowance)
+                )
+            },
+        )
+    }
+}
+
+/// Users ordered from the heaviest to the lightest
+#[derive(Debug, Clone, Default)]
+pub struct UserRanking {
+    users: Vec<UserUsage>,
+}
+
+impl UserRanking {
+    /// Sum the memory of `processes` per uid and rank the users
+    pub fn new<'a>(processes: impl IntoIterator<Item = &'a ProcessInfo>, config: &Config) -> Self {
+        let mut by_uid: HashMap<u32, UserUsage> = HashMap::new();
+        for process in processes {
+            let usage = by_uid.entry(process.uid).or_insert_with(|| UserUsage {
+                uid: process.uid,
+                user: process.user.clone(),
+                rss_kb: 0,
+                processes: 0,
+                allowance_kb: allowance_kb(config, process.uid, &process.user),
+            });
+            usage.rss_kb += process.rss_kb;
+            usage.processes += process.tree_size.max(1);
+        }
+
+        let mut users: Vec<UserUsage> = by_uid.into_values().collect();
+        users.sort_by(|a, b| b.load().total_cmp(&a.load()).then(a.uid.cmp(&b.uid)));
+        Self { users }
+    }
+
+    /// Position of a user in the ranking, 0 for the heaviest
+    pub fn rank(&self, uid: u32) -> Option<usize> {
+        self.users.iter().position(|u| u.uid == uid)
+    }
+
+    /// Why a user's processes rank where they do, e.g. "user alice ranks
+    /// 1 of 3 with 9.00 GiB in 12 processes"
+    pub fn describe(&self, uid: u32) -> Option<String> {
+        let rank = self.rank(uid)?;
+        let usage = &self.users[rank];
+        Some(format!(
+            "user {} ranks {} of {} with {}",
+            usage.name(),
+            rank + 1,
+            self.users.len(),
+            usage.summary()
+        ))
+    }
+
+    /// Users from the heaviest
+    pub fn iter(&self) -> impl Iterator<Item = &UserUsage> {
+        self.users.iter()
+    }
+}
+
+/// Allowance of a user, by name or uid, falling back to the default one
+fn allowance_kb(config: &Config, uid: u32, user: &str) -> Option<u64> {
+    let uid = uid.to_string();
+    config
+        .user_allowances
+        .iter()
+        .find(|(name, _)| *name == user || *name == uid)
+        .map(|(_, kb)| *kb)
+        .or(config.user_allowance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(uid: u32, user: &str, rss_kb: u64) -> ProcessInfo {
+        ProcessInfo {
+            uid,
+            user: user.to_string(),
+            rss_kb,
+            ..Default::default()
+        }
+    }
+
+    fn processes() -> Vec<ProcessInfo> {
+        vec![
+            // alice: one large editor
+            process(1000, "alice", 3_000_000),
+            // bob: many notebook kernels, more memory in total
+            process(1001, "bob", 2_000_000),
+            process(1001, "bob", 2_000_000),
+            process(1001, "bob", 1_500_000),
+            process(1002, "", 10_000),
+        ]
+    }
+
+    #[test]
+    fn test_ranks_users_by_total_memory() {
+        let processes = processes();
+        let ranking = UserRanking::new(&processes, &Config::default());
+        let order: Vec<(u32, u64, usize)> = ranking
+            .iter()
+            .map(|u| (u.uid, u.rss_kb, u.processes))
+            .collect();
+        assert_eq!(
+            order,
+            [
+                (1001, 5_500_000, 3),
+                (1000, 3_000_000, 1),
+                (1002, 10_000, 1)
+            ]
+        );
+        assert_eq!(ranking.rank(1000), Some(1));
+        assert_eq!(ranking.rank(0), None);
+        assert!(ranking
+            .describe(1001)
+            .unwrap()
+            .starts_with("user bob ranks 1 of 3 with "));
+        assert!(ranking
+            .describe(1002)
+            .unwrap()
+            .starts_with("user uid 1002 ranks 3 of 3"));
+    }
+
+    #[test]
+    fn test_ranks_users_relative_to_allowance() {
+        let processes = processes();
+        let config = Config {
+            user_allowance: Some(2_000_000),
+            user_allowances: vec![
+                ("bob".to_string(), 8_000_000),
+                ("1000".to_string(), 1_000_000),
+            ],
+            ..Default::default()
+        };
+        let ranking = UserRanking::new(&processes, &config);
+        let order: Vec<(u32, Option<u64>)> =
+            ranking.iter().map(|u| (u.uid, u.allowance_kb)).collect();
+        // alice uses 300% of the allowance, bob 69%, uid 1002 almost nothing
+        assert_eq!(
+            order,
+            [
+                (1000, Some(1_000_000)),
+                (1001, Some(8_000_000)),
+                (1002, Some(2_000_000))
+            ]
+        );
+        assert!(ranking.describe(1000).unwrap().contains("(300% of "));
+    }
+}
@@ -1,11 +1,23 @@
 // Daemon module - main monitoring loop and service
 
+mod adjust;
+pub mod clock;
+mod explain;
+mod fairness;
+mod oomwatch;
+mod quota;
+mod ratelimit;
+pub mod replay;
+mod respawn;
 mod service;
 
-pub use service::DaemonService;
+pub use service::{Action, DaemonService, Explanation, Verdict};
 
 use crate::config::Config;
+use crate::monitor::recording::read_recording;
+use crate::monitor::{ProcfsSource, SystemSource};
 use anyhow::Result;
+use std::path::Path;
 
 /// Run the OOM Guard daemon with the given configuration
 pub fn run(config: Config) -> Result<()> {
@@ -23,3 +35,22 @@ pub fn run(config: Config) -> Result<()> {
     let mut service = DaemonService::new(config);
     service.run()
 }
+
+/// Replay a recording with the given configuration and print the actions
+/// that would have been taken
+pub fn run_replay(config: Config, recording: &Path) -> Result<()> {
+    let samples = read_recording(recording)?;
+    let report = replay::replay(config, samples)?;
+    println!("{report}");
+    Ok(())
+}
+
+/// Print every process with its rank or the reason it is ignored, using
+/// the given configuration
+pub fn run_explain(config: Config) -> Result<()> {
+    let meminfo = ProcfsSource.meminfo()?;
+    let service = DaemonService::new(config);
+    println!("{meminfo}");
+    println!("{}", explain::format_table(&service.explain()?));
+    Ok(())
+}
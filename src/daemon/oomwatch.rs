@@ -0,0 +1,251 @@
+// Kernel OOM kills that happened despite the guard
+//
+// Every kill by the kernel OOM killer means oom_guard acted too late or
+// not at all. The kernel's counters are compared on every scan: the
+// system-wide one, and those of the cgroups given with --watch-cgroup,
+// which also catch kills caused by a cgroup's own memory limit.
+//
+// The kernel log is drained on every scan too, and its kill messages are
+// matched to counter increases: a message may be read one scan before or
+// after the counter moves, and messages no counter accounts for (kills in
+// unwatched cgroups) are dropped rather than reported with a later kill.
+
+use crate::monitor::oomkill::KernelOomKill;
+use crate::monitor::SystemSource;
+
+/// Kernel OOM kills since the previous scan
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissedOomKills {
+    /// Processes killed, system-wide when counted, else in watched cgroups
+    pub count: u64,
+    /// Watched cgroups that saw kills, with how many
+    pub cgroups: Vec<(String, u64)>,
+}
+
+/// Last seen value of the kernel OOM kill counters
+#[derive(Debug, Clone)]
+pub struct OomKillWatcher {
+    system: Option<u64>,
+    cgroups: Vec<(String, Option<u64>)>,
+    missed: u64,
+    /// Kills logged during the last check that no increase accounted for
+    logged: Vec<KernelOomKill>,
+}
+
+impl OomKillWatcher {
+    /// Start counting from the current values of the counters
+    pub fn new(source: &impl SystemSource, cgroups: &[String]) -> Self {
+        Self {
+            system: source.oom_kills(),
+            cgroups: cgroups
+                .iter()
+                .map(|cgroup| (cgroup.clone(), source.cgroup_oom_kills(cgroup)))
+                .collect(),
+            missed: 0,
+            logged: Vec::new(),
+        }
+    }
+
+    /// Whether any counter is available
+    pub fn is_counting(&self) -> bool {
+        self.system.is_some() || self.cgroups.iter().any(|(_, count)| count.is_some())
+    }
+
+    /// Kernel OOM kills since the last check, if any
+    pub fn check(&mut self, source: &impl SystemSource) -> Option<MissedOomKills> {
+        let system = increase(&mut self.system, source.oom_kills());
+        let cgroups: Vec<(String, u64)> = self
+            .cgroups
+            .iter_mut()
+            .filter_map(|(cgroup, last)| {
+                let kills = increase(last, source.cgroup_oom_kills(cgroup));
+                (kills > 0).then(|| (cgroup.clone(), kills))
+            })
+            .collect();
+
+        // The system-wide counter includes kills in every cgroup
+        let count = if self.system.is_some() {
+            system
+        } else {
+            cgroups.iter().map(|(_, kills)| kills).sum()
+        };
+        if count == 0 && cgroups.is_empty() {
+            return None;
+        }
+        self.missed += count;
+        Some(MissedOomKills { count, cgroups })
+    }
+
+    /// The logged kills behind the counter increases of this check, from
+    /// the kills logged since the last check and the unmatched ones logged
+    /// just before it. Logged kills are kept one check for a counter that
+    /// goes up late, then dropped
+    pub fn match_logged(
+        &mut self,
+        missed: Option<&MissedOomKills>,
+        logged: Vec<KernelOomKill>,
+    ) -> Vec<KernelOomKill> {
+        let carried = std::mem::take(&mut self.logged);
+        let Some(missed) = missed else {
+            self.logged = logged;
+            return Vec::new();
+        };
+        let fresh_from = carried.len();
+        let mut wanted = missed.count;
+        let mut kills = Vec::new();
+        for (index, kill) in carried.into_iter().chain(logged).enumerate() {
+            if wanted > 0 && self.accounts_for(&kill) {
+                wanted -= 1;
+                kills.push(kill);
+            } else if index >= fresh_from {
+                self.logged.push(kill);
+            }
+        }
+        kills
+    }
+
+    /// Whether the counters include a logged kill: all kills when the
+    /// system-wide counter is read, else those within a watched cgroup
+    fn accounts_for(&self, kill: &KernelOomKill) -> bool {
+        self.system.is_some()
+            || kill.cgroup.as_deref().is_some_and(|cgroup| {
+                self.cgroups.iter().any(|(watched, _)| {
+                    cgroup
+                        .strip_prefix(watched.as_str())
+                        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+                })
+            })
+    }
+
+    /// Kernel OOM kills seen since the daemon started
+    pub const fn missed(&self) -> u64 {
+        self.missed
+    }
+}
+
+/// Increase of a counter since its last value, which is then updated. A
+/// counter that went down (e.g. a recreated cgroup) counts from there
+const fn increase(last: &mut Option<u64>, current: Option<u64>) -> u64 {
+    let kills = match (*last, current) {
+        (Some(last), Some(current)) => current.saturating_sub(last),
+        (None, Some(current)) => current,
+        _ => 0,
+    };
+    if current.is_some() {
+        *last = current;
+    }
+    kills
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monitor::FixtureSource;
+
+    #[test]
+    fn test_counts_increases_since_start() {
+        let dir = tempfile::tempdir().unwrap();
+        let fixture = FixtureSource::new(dir.path());
+        fixture.write_oom_kills(5).unwrap();
+        fixture
+            .write_cgroup_oom_kills("/system.slice/db.service", 1)
+            .unwrap();
+        let cgroups = [
+            "/system.slice/db.service".to_string(),
+            "/system.slice/gone.service".to_string(),
+        ];
+        let mut watcher = OomKillWatcher::new(&fixture, &cgroups);
+        assert!(watcher.is_counting());
+        assert_eq!(watcher.check(&fixture), None);
+
+        fixture.write_oom_kills(7).unwrap();
+        fixture
+            .write_cgroup_oom_kills("/system.slice/db.service", 2)
+            .unwrap();
+        assert_eq!(
+            watcher.check(&fixture),
+            Some(MissedOomKills {
+                count: 2,
+                cgroups: vec![("/system.slice/db.service".to_string(), 1)],
+            })
+        );
+        assert_eq!(watcher.check(&fixture), None);
+        assert_eq!(watcher.missed(), 2);
+    }
+
+    #[test]
+    fn test_cgroups_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let fixture = FixtureSource::new(dir.path());
+        let cgroups = ["/app.slice/app.service".to_string()];
+        let mut watcher = OomKillWatcher::new(&fixture, &cgroups);
+        assert!(!watcher.is_counting());
+
+        // A cgroup created after startup counts from zero
+        fixture
+            .write_cgroup_oom_kills("/app.slice/app.service", 3)
+            .unwrap();
+        assert_eq!(watcher.check(&fixture).map(|m| m.count), Some(3));
+        fixture
+            .write_cgroup_oom_kills("/app.slice/app.service", 1)
+            .unwrap();
+        assert_eq!(watcher.check(&fixture), None);
+        assert_eq!(watcher.missed(), 3);
+    }
+
+    fn logged_kill(pid: i32, cgroup: &str) -> KernelOomKill {
+        KernelOomKill {
+            pid,
+            name: format!("task{pid}"),
+            cgroup: Some(cgroup.to_string()),
+            ..KernelOomKill::default()
+        }
+    }
+
+    #[test]
+    fn test_match_logged_kills_to_increases() {
+        let dir = tempfile::tempdir().unwrap();
+        let fixture = FixtureSource::new(dir.path());
+        fixture.write_oom_kills(0).unwrap();
+        let mut watcher = OomKillWatcher::new(&fixture, &[]);
+        let missed = |count| MissedOomKills {
+            count,
+            cgroups: Vec::new(),
+        };
+
+        // Logged before the counter went up: kept for the next check
+        let kills = watcher.match_logged(None, vec![logged_kill(1, "/a")]);
+        assert!(kills.is_empty());
+        let kills = watcher.match_logged(Some(&missed(1)), vec![logged_kill(2, "/b")]);
+        assert_eq!(kills, vec![logged_kill(1, "/a")]);
+
+        // The surplus logged with an increase waits one check, then is dropped
+        let kills = watcher.match_logged(None, Vec::new());
+        assert!(kills.is_empty());
+        let kills = watcher.match_logged(Some(&missed(1)), vec![logged_kill(3, "/c")]);
+        assert_eq!(kills, vec![logged_kill(3, "/c")]);
+    }
+
+    #[test]
+    fn test_match_logged_kills_in_watched_cgroups() {
+        let dir = tempfile::tempdir().unwrap();
+        let fixture = FixtureSource::new(dir.path());
+        let cgroups = ["/app.slice".to_string()];
+        let mut watcher = OomKillWatcher::new(&fixture, &cgroups);
+        let missed = MissedOomKills {
+            count: 1,
+            cgroups: vec![("/app.slice".to_string(), 1)],
+        };
+
+        // Kills outside the watched cgroups are not what the counters saw
+        let kills = watcher.match_logged(
+            Some(&missed),
+            vec![
+                logged_kill(1, "/app.slice.old"),
+                logged_kill(2, "/user.slice"),
+                logged_kill(3, "/app.slice/app.service"),
+            ],
+        );
+        assert_eq!(kills, vec![logged_kill(3, "/app.slice/app.service")]);
+    }
+}
@@ -0,0 +1,219 @@
+// Per-user memory quotas enforced on every scan
+//
+// A user over quota is warned about once, and if still over after the
+// grace period one of the user's processes is killed; the grace period
+// then starts again. System-wide thresholds do not need to be breached.
+
+use crate::config::{QuotaSelector, UserQuota};
+use crate::monitor::ProcessInfo;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+/// What to do about a user over quota
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuotaAction {
+    /// The user just went over quota
+    Warn,
+    /// The user stayed over quota for the grace period
+    Kill,
+}
+
+/// A user over quota
+#[derive(Debug, Clone)]
+pub struct QuotaBreach {
+    pub uid: u32,
+    pub user: String,
+    pub used_kb: u64,
+    pub quota: UserQuota,
+    pub action: QuotaAction,
+}
+
+impl std::fmt::Display for QuotaBreach {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "user {} (uid {}) uses {} KiB, over quota {}",
+            self.user, self.uid, self.used_kb, self.quota
+        )
+    }
+}
+
+/// Quotas with their groups resolved, and the users currently over quota
+pub struct QuotaEnforcer {
+    quotas: Vec<(UserQuota, HashSet<u32>)>,
+    grace: Duration,
+    over_since: HashMap<u32, Instant>,
+}
+
+impl QuotaEnforcer {
+    /// Enforce `quotas`, resolving group selectors with `group_members`
+    pub fn new(
+        quotas: &[UserQuota],
+        grace: Duration,
+        group_members: impl Fn(&str) -> HashSet<u32>,
+    ) -> Self {
+        let quotas = quotas
+            .iter()
+            .map(|quota| {
+                let members = match &quota.selector {
+                    QuotaSelector::Group(group) => group_members(group),
+                    QuotaSelector::Uids(_) | QuotaSelector::User(_) => HashSet::new(),
+                };
+                (quota.clone(), members)
+            })
+            .collect();
+        Self {
+            quotas,
+            grace,
+            over_since: HashMap::new(),
+        }
+    }
+
+    /// The first quota applying to a user
+    fn quota_for(&self, uid: u32, user: &str) -> Option<&UserQuota> {
+        self.quotas
+            .iter()
+            .find(|(quota, members)| match &quota.selector {
+                QuotaSelector::Uids(range) => range.contains(&uid),
+                QuotaSelector::User(name) => name == user || *name == uid.to_string(),
+                QuotaSelector::Group(_) => members.contains(&uid),
+            })
+            .map(|(quota, _)| quota)
+    }
+
+    /// Sum memory per user over all their processes and return the users
+    /// to warn about or kill within. Users back under quota are forgotten
+    pub fn check(&mut self, processes: &[ProcessInfo], now: Instant) -> Vec<QuotaBreach> {
+        let mut usage: HashMap<u32, (String, u64)> = HashMap::new();
+        for process in processes.iter().filter(|p| !p.is_zombie) {
+            let entry = usage
+                .entry(process.uid)
+                .or_insert_with(|| (process.user.clone(), 0));
+            entry.1 += process.rss_kb;
+        }
+
+        let mut breaches: Vec<QuotaBreach> = usage
+            .into_iter()
+            .filter_map(|(uid, (user, used_kb))| {
+                let quota = self.quota_for(uid, &user)?;
+                (used_kb > quota.limit_kb).then(|| QuotaBreach {
+                    uid,
+                    user,
+                    used_kb,
+                    quota: quota.clone(),
+                    action: QuotaAction::Warn,
+                })
+            })
+            .collect();
+
+        self.over_since
+            .retain(|uid, _| breaches.iter().any(|b| b.uid == *uid));
+        breaches.retain_mut(|breach| match self.over_since.get(&breach.uid) {
+            None => {
+                self.over_since.insert(breach.uid, now);
+                true
+            }
+            Some(since) if now.saturating_duration_since(*since) >= self.grace => {
+                breach.action = QuotaAction::Kill;
+                true
+            }
+            Some(_) => false,
+        });
+        breaches.sort_by_key(|b| b.uid);
+        breaches
+    }
+
+    /// Start the grace period of a user again, after acting on a breach
+    pub fn restart_grace(&mut self, uid: u32, now: Instant) {
+        self.over_since.insert(uid, now);
+    }
+
+    /// Users over quota, with how long they have been
+    pub fn over_quota(&self, now: Instant) -> Vec<(u32, Duration)> {
+        let mut users: Vec<(u32, Duration)> = self
+            .over_since
+            .iter()
+            .map(|(uid, since)| (*uid, now.saturating_duration_since(*since)))
+            .collect();
+        users.sort_unstable();
+        users
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(uid: u32, user: &str, rss_kb: u64) -> ProcessInfo {
+        ProcessInfo {
+            uid,
+            user: user.to_string(),
+            rss_kb,
+            ..Default::default()
+        }
+    }
+
+    fn enforcer() -> QuotaEnforcer {
+        let quotas = [
+            UserQuota::parse("user=alice:16000000").unwrap(),
+            UserQuota::parse("group=students:4000000").unwrap(),
+            UserQuota::parse("uid=1000-60000:8000000").unwrap(),
+        ];
+        QuotaEnforcer::new(&quotas, Duration::from_secs(30), |group| {
+            if group == "students" {
+                HashSet::from([1002])
+            } else {
+                HashSet::new()
+            }
+        })
+    }
+
+    #[test]
+    fn test_first_matching_quota_applies() {
+        let enforcer = enforcer();
+        let limit = |uid, user| enforcer.quota_for(uid, user).map(|q| q.limit_kb);
+        assert_eq!(limit(1000, "alice"), Some(16_000_000));
+        assert_eq!(limit(1002, "carol"), Some(4_000_000));
+        assert_eq!(limit(1001, "bob"), Some(8_000_000));
+        assert_eq!(limit(0, "root"), None);
+    }
+
+    #[test]
+    fn test_warn_then_kill_after_grace() {
+        let mut enforcer = enforcer();
+        let start = Instant::now();
+        let processes = vec![
+            process(1000, "alice", 10_000_000),
+            process(1001, "bob", 5_000_000),
+            process(1001, "bob", 5_000_000),
+            process(1002, "carol", 3_000_000),
+            process(0, "root", 50_000_000),
+        ];
+
+        let breaches = enforcer.check(&processes, start);
+        assert_eq!(breaches.len(), 1);
+        assert_eq!(breaches[0].uid, 1001);
+        assert_eq!(breaches[0].used_kb, 10_000_000);
+        assert_eq!(breaches[0].action, QuotaAction::Warn);
+
+        // Within the grace period, nothing new to report
+        assert!(enforcer
+            .check(&processes, start + Duration::from_secs(10))
+            .is_empty());
+
+        let later = start + Duration::from_secs(30);
+        let breaches = enforcer.check(&processes, later);
+        assert_eq!(breaches[0].action, QuotaAction::Kill);
+        enforcer.restart_grace(1001, later);
+        assert!(enforcer.check(&processes, later).is_empty());
+        assert_eq!(enforcer.over_quota(later), [(1001, Duration::ZERO)]);
+
+        // Back under quota: forgotten, and warned about again next time
+        assert!(enforcer.check(&processes[..2], later).is_empty());
+        assert!(enforcer.over_quota(later).is_empty());
+        assert_eq!(
+            enforcer.check(&processes, later)[0].action,
+            QuotaAction::Warn
+        );
+    }
+}
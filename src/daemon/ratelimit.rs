@@ -0,0 +1,120 @@
+// Kill rate limiting over a rolling time window
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Limits the number of kills within a rolling time window
+#[derive(Debug, Clone)]
+pub struct KillBudget {
+    max_kills: usize,
+    window: Duration,
+    history: VecDeque<Instant>,
+}
+
+impl KillBudget {
+    /// Create a budget allowing `max_kills` kills per `window`
+    pub const fn new(max_kills: usize, window: Duration) -> Self {
+        Self {
+            max_kills,
+            window,
+            history: VecDeque::new(),
+        }
+    }
+
+    /// Record a kill at the given time
+    pub fn record(&mut self, now: Instant) {
+        self.prune(now);
+        self.history.push_back(now);
+    }
+
+    /// Number of kills still allowed within the current window
+    pub fn remaining(&mut self, now: Instant) -> usize {
+        self.prune(now);
+        self.max_kills.saturating_sub(self.history.len())
+    }
+
+    /// Number of kills recorded within the current window
+    pub fn used(&mut self, now: Instant) -> usize {
+        self.prune(now);
+        self.history.len()
+    }
+
+    /// Time until the oldest kill leaves the window, freeing one slot
+    pub fn time_until_available(&mut self, now: Instant) -> Duration {
+        self.prune(now);
+        if self.history.len() < self.max_kills {
+            return Duration::ZERO;
+        }
+        self.history.front().map_or(Duration::ZERO, |oldest| {
+            self.window
+                .saturating_sub(now.saturating_duration_since(*oldest))
+        })
+    }
+
+    pub const fn max_kills(&self) -> usize {
+        self.max_kills
+    }
+
+    pub const fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Drop kills that are older than the window
+    fn prune(&mut self, now: Instant) {
+        while let Some(oldest) = self.history.front() {
+            if now.saturating_duration_since(*oldest) >= self.window {
+                self.history.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budget_exhausted_within_window() {
+        let start = Instant::now();
+        let mut budget = KillBudget::new(3, Duration::from_secs(60));
+
+        for i in 0..3 {
+            assert!(budget.remaining(start + Duration::from_secs(i)) > 0);
+            budget.record(start + Duration::from_secs(i));
+        }
+
+        assert_eq!(budget.remaining(start + Duration::from_secs(10)), 0);
+        assert_eq!(budget.used(start + Duration::from_secs(10)), 3);
+    }
+
+    #[test]
+    fn test_budget_recovers_after_window() {
+        let start = Instant::now();
+        let mut budget = KillBudget::new(2, Duration::from_secs(60));
+
+        budget.record(start);
+        budget.record(start + Duration::from_secs(30));
+        assert_eq!(budget.remaining(start + Duration::from_secs(31)), 0);
+
+        // First kill leaves the window after 60s
+        assert_eq!(budget.remaining(start + Duration::from_secs(60)), 1);
+        // Both kills have left the window
+        assert_eq!(budget.remaining(start + Duration::from_secs(90)), 2);
+    }
+
+    #[test]
+    fn test_time_until_available() {
+        let start = Instant::now();
+        let mut budget = KillBudget::new(1, Duration::from_secs(60));
+
+        assert_eq!(budget.time_until_available(start), Duration::ZERO);
+
+        budget.record(start);
+        assert_eq!(
+            budget.time_until_available(start + Duration::from_secs(20)),
+            Duration::from_secs(40)
+        );
+    }
+}
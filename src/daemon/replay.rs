@@ -0,0 +1,359 @@
+// Replay of recorded memory incidents through the decision logic
+
+use super::clock::Clock;
+use super::service::{Action, DaemonService};
+use crate::config::Config;
+use crate::monitor::recording::Sample;
+use crate::monitor::{
+    MemDetails, MemInfo, MemoryPressure, ProcessInfo, ProcessSource, SystemSource, ZramSwap,
+};
+use anyhow::{Context, Result};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// Serves the current sample of a recording to the daemon
+///
+/// Processes killed during the replay are hidden from later samples and
+/// their RSS is counted as available memory for as long as the recording
+/// still shows them running. This approximates the effect of the kill; the
+/// memory the rest of the system allocates afterwards is taken as recorded.
+#[derive(Debug, Clone, Default)]
+struct ReplaySource {
+    state: Rc<RefCell<ReplayState>>,
+}
+
+#[derive(Debug, Default)]
+struct ReplayState {
+    sample: Option<Sample>,
+    killed: HashSet<i32>,
+}
+
+impl ReplaySource {
+    fn set_sample(&self, sample: Sample) {
+        self.state.borrow_mut().sample = Some(sample);
+    }
+
+    fn mark_killed(&self, pid: i32) {
+        self.state.borrow_mut().killed.insert(pid);
+    }
+}
+
+impl SystemSource for ReplaySource {
+    fn meminfo(&self) -> Result<MemInfo> {
+        let state = self.state.borrow();
+        let sample = state.sample.as_ref().context("No sample to replay")?;
+
+        let freed_kb: u64 = sample
+            .processes
+            .iter()
+            .filter(|p| state.killed.contains(&p.pid))
+            .map(|p| p.rss_kb)
+            .sum();
+        let mut meminfo = sample.meminfo;
+        meminfo.mem_available = (meminfo.mem_available + freed_kb).min(meminfo.mem_total);
+
+        Ok(meminfo)
+    }
+
+    fn pressure(&self) -> Option<MemoryPressure> {
+        self.state.borrow().sample.as_ref()?.pressure
+    }
+
+    // Neither memory details, zram nor kernel OOM kills are recorded
+    fn mem_details(&self) -> Option<MemDetails> {
+        None
+    }
+
+    fn zram_swap(&self) -> Option<ZramSwap> {
+        None
+    }
+
+    fn oom_kills(&self) -> Option<u64> {
+        None
+    }
+
+    fn cgroup_oom_kills(&self, _cgroup: &str) -> Option<u64> {
+        None
+    }
+}
+
+impl ProcessSource for ReplaySource {
+    fn processes(&self) -> Result<Vec<ProcessInfo>> {
+        let state = self.state.borrow();
+        let sample = state.sample.as_ref().context("No sample to replay")?;
+
+        Ok(sample
+            .processes
+            .iter()
+            .filter(|p| !state.killed.contains(&p.pid))
+            .cloned()
+            .collect())
+    }
+
+    fn set_oom_score_adj(&self, _pid: i32, _adj: i32) -> Result<()> {
+        anyhow::bail!("Recorded processes cannot be changed")
+    }
+
+    // A process is alive for as long as the recording shows it, started at
+    // the same time, and it was not killed during the replay
+    fn is_alive(&self, process: &ProcessInfo) -> bool {
+        let state = self.state.borrow();
+        !state.killed.contains(&process.pid)
+            && state.sample.as_ref().is_some_and(|sample| {
+                sample
+                    .processes
+                    .iter()
+                    .any(|p| p.pid == process.pid && p.start_time == process.start_time)
+            })
+    }
+
+    // Cgroup files are not recorded
+    fn cgroup_dir(&self, _process: &ProcessInfo) -> Option<PathBuf> {
+        None
+    }
+}
+
+/// An action taken during a replay, relative to the start of the recording
+#[derive(Debug, Clone)]
+pub struct ReplayEvent {
+    pub elapsed: Duration,
+    pub action: Action,
+}
+
+/// Outcome of replaying a recording
+#[derive(Debug, Clone, Default)]
+pub struct ReplayReport {
+    pub samples: usize,
+    pub duration: Duration,
+    pub events: Vec<ReplayEvent>,
+}
+
+impl std::fmt::Display for ReplayEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let action = &self.action;
+        write!(
+            f,
+            "{:>9.1}s  {:<8}  pid {} ({})  RSS {}  mem {:.1}% available, swap {:.1}% free  [{}]",
+            self.elapsed.as_secs_f64(),
+            action.strategy,
+            action.victim.pid,
+            crate::sanitize_for_log(&action.victim.name),
+            MemInfo::format_size(action.victim.rss_kb),
+            action.meminfo.mem_available_percent(),
+            action.meminfo.swap_free_percent(),
+            crate::sanitize_for_log(&action.reason)
+        )
+    }
+}
+
+impl std::fmt::Display for ReplayReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for event in &self.events {
+            writeln!(f, "{event}")?;
+        }
+        write!(
+            f,
+            "{} action(s) over {} sample(s) spanning {:.1}s",
+            self.events.len(),
+            self.samples,
+            self.duration.as_secs_f64()
+        )
+    }
+}
+
+/// Drives the daemon's decision logic (threshold checks, victim selection,
+/// cooldown and kill budget) through a sequence of samples on a virtual clock
+///
+/// Nothing is acted on: the daemon always runs in dry-run mode.
+pub struct SampleDriver {
+    source: ReplaySource,
+    service: DaemonService<ReplaySource, ReplaySource>,
+    clock: Clock,
+    start: Instant,
+}
+
+impl SampleDriver {
+    pub fn new(config: Config) -> Self {
+        let config = Config {
+            dry_run: true,
+            ..config
+        };
+        let source = ReplaySource::default();
+        let mut service = DaemonService::with_sources(config, source.clone(), source.clone());
+        service.enable_action_log();
+
+        let clock = Clock::virtual_clock();
+        Self {
+            source,
+            service,
+            start: clock.now(),
+            clock,
+        }
+    }
+
+    /// Feed one sample and return the actions the daemon took in response
+    pub fn step(&mut self, sample: Sample) -> Vec<ReplayEvent> {
+        self.clock.set_elapsed(sample.elapsed);
+        self.service.set_clock(self.clock);
+        self.source.set_sample(sample);
+
+        if let Err(e) = self.service.check_once() {
+            log::warn!("Replay step failed: {e:#}");
+        }
+
+        self.service
+            .take_actions()
+            .into_iter()
+            .map(|action| ReplayEvent {
+                elapsed: action.at.saturating_duration_since(self.start),
+                action,
+            })
+            .collect()
+    }
+
+    /// Hide a process from later samples and count its RSS as available
+    pub fn mark_killed(&self, pid: i32) {
+        self.source.mark_killed(pid);
+    }
+}
+
+/// Feed recorded samples through the daemon's decision logic
+pub fn replay(config: Config, samples: Vec<Sample>) -> Result<ReplayReport> {
+    let mut driver = SampleDriver::new(config);
+    let mut report = ReplayReport {
+        samples: samples.len(),
+        duration: samples.last().map_or(Duration::ZERO, |s| s.elapsed),
+        events: Vec::new(),
+    };
+
+    for sample in samples {
+        for event in driver.step(sample) {
+            if event.action.strategy.terminates() {
+                driver.mark_killed(event.action.victim.pid);
+            }
+            report.events.push(event);
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::killer::KillStrategy;
+
+    fn process(pid: i32, rss_kb: u64, oom_score: i32) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            name: format!("app-{pid}"),
+            cmdline: format!("/usr/bin/app {pid}"),
+            rss_kb,
+            oom_score,
+            oom_score_adj: 0,
+            uid: 1000,
+            state: 'S',
+            is_zombie: false,
+            ..Default::default()
+        }
+    }
+
+    fn sample(secs: u64, available_percent: u64) -> Sample {
+        Sample {
+            elapsed: Duration::from_secs(secs),
+            time: 1_700_000_000 + secs,
+            meminfo: MemInfo {
+                mem_total: 10_000_000,
+                mem_available: available_percent * 100_000,
+                swap_total: 1_000_000,
+                swap_free: available_percent * 10_000,
+            },
+            pressure: None,
+            processes: vec![process(5001, 4_000_000, 700), process(5002, 1_000_000, 300)],
+        }
+    }
+
+    #[test]
+    fn test_replay_source_answers_from_the_recording() {
+        let source = ReplaySource::default();
+        let mut recorded = sample(0, 50);
+        recorded.processes[0] = recorded.processes[0]
+            .clone()
+            .with_cgroup("/user.slice/app.scope".to_string());
+        source.set_sample(recorded.clone());
+
+        let [first, second] = [&recorded.processes[0], &recorded.processes[1]];
+        assert!(source.is_alive(first));
+        assert!(!source.is_alive(&ProcessInfo {
+            pid: std::process::id() as i32,
+            ..first.clone()
+        }));
+        assert!(!source.is_alive(&first.clone().with_start_time(1_000, 1_090)));
+        assert_eq!(source.cgroup_dir(first), None);
+
+        source.mark_killed(5001);
+        assert!(!source.is_alive(first));
+        assert!(source.is_alive(second));
+    }
+
+    #[test]
+    fn test_replay_no_actions_when_healthy() {
+        let samples = (0..10).map(|t| sample(t, 50)).collect();
+        let report = replay(Config::default(), samples).unwrap();
+
+        assert_eq!(report.samples, 10);
+        assert_eq!(report.duration, Duration::from_secs(9));
+        assert!(report.events.is_empty());
+    }
+
+    #[test]
+    fn test_replay_kills_top_candidate_at_crossing_time() {
+        let samples = vec![sample(0, 50), sample(1, 30), sample(2, 8), sample(3, 8)];
+        let report = replay(Config::default(), samples).unwrap();
+
+        assert_eq!(report.events.len(), 1);
+        let event = &report.events[0];
+        assert_eq!(event.elapsed, Duration::from_secs(2));
+        assert_eq!(event.action.victim.pid, 5001);
+        assert_eq!(event.action.strategy, KillStrategy::Graceful);
+    }
+
+    #[test]
+    fn test_replay_hides_killed_processes() {
+        let samples = (0..30)
+            .map(|t| {
+                let mut s = sample(t, 2);
+                s.processes.retain(|p| p.pid == 5002);
+                s
+            })
+            .collect();
+        let report = replay(Config::default(), samples).unwrap();
+
+        // The only candidate is killed once and stays gone afterwards
+        assert_eq!(report.events.len(), 1);
+        assert_eq!(report.events[0].action.strategy, KillStrategy::Forceful);
+    }
+
+    #[test]
+    fn test_replay_cooldown_spaces_kills() {
+        let samples = (0..25)
+            .map(|t| {
+                let mut s = sample(t, 2);
+                // A new hog appears every second
+                s.processes = vec![process(6000 + t as i32, 100_000, 500)];
+                s
+            })
+            .collect();
+        let config = Config {
+            cooldown_kill: Duration::from_secs(10),
+            ..Config::default()
+        };
+        let report = replay(config, samples).unwrap();
+
+        let times: Vec<u64> = report.events.iter().map(|e| e.elapsed.as_secs()).collect();
+        assert_eq!(times, vec![0, 10, 20]);
+    }
+}
@@ -0,0 +1,196 @@
+// Respawn loop detection: track kills per victim identity
+
+use crate::monitor::ProcessInfo;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+
+/// Identity of a victim that survives restarts (unlike its PID)
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VictimIdentity {
+    /// Resolved executable path, if readable
+    pub exe: Option<String>,
+    /// Hash of the full command line
+    pub cmdline_hash: u64,
+    /// Owning systemd unit (service or scope), if any
+    pub unit: Option<String>,
+}
+
+impl VictimIdentity {
+    /// Build the identity of a process from the executable, command line
+    /// and unit read with it
+    pub fn of(process: &ProcessInfo) -> Self {
+        let known = |s: &str| (!s.is_empty()).then(|| s.to_string());
+        Self::new(known(&process.exe), &process.cmdline, known(&process.unit))
+    }
+
+    pub fn new(exe: Option<String>, cmdline: &str, unit: Option<String>) -> Self {
+        let mut hasher = DefaultHasher::new();
+        cmdline.hash(&mut hasher);
+
+        Self {
+            exe,
+            cmdline_hash: hasher.finish(),
+            unit,
+        }
+    }
+}
+
+impl std::fmt::Display for VictimIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.exe.as_deref().unwrap_or("<unknown exe>"))?;
+        if let Some(unit) = &self.unit {
+            write!(f, " in {unit}")?;
+        }
+        write!(f, " [cmdline {:016x}]", self.cmdline_hash)
+    }
+}
+
+/// Counts kills per victim identity over a rolling window
+#[derive(Debug, Clone)]
+pub struct RespawnTracker {
+    max_kills: usize,
+    window: Duration,
+    kills: HashMap<VictimIdentity, VecDeque<Instant>>,
+}
+
+impl RespawnTracker {
+    /// Create a tracker that flags identities killed more than `max_kills`
+    /// times within `window`
+    pub fn new(max_kills: usize, window: Duration) -> Self {
+        Self {
+            max_kills,
+            window,
+            kills: HashMap::new(),
+        }
+    }
+
+    /// Record a kill and return the number of kills of this identity in the window
+    pub fn record(&mut self, identity: VictimIdentity, now: Instant) -> usize {
+        let window = self.window;
+        let kills = self.kills.entry(identity).or_default();
+        kills.retain(|t| now.saturating_duration_since(*t) < window);
+        kills.push_back(now);
+        kills.len()
+    }
+
+    /// Number of kills of this identity within the window
+    pub fn count(&self, identity: &VictimIdentity, now: Instant) -> usize {
+        self.kills.get(identity).map_or(0, |kills| {
+            kills
+                .iter()
+                .filter(|t| now.saturating_duration_since(**t) < self.window)
+                .count()
+        })
+    }
+
+    /// Whether this identity has been killed more than the allowed number of times
+    pub fn is_looping(&self, identity: &VictimIdentity, now: Instant) -> bool {
+        self.count(identity, now) > self.max_kills
+    }
+
+    /// Whether any identity is currently looping
+    pub fn has_loops(&self, now: Instant) -> bool {
+        self.kills
+            .keys()
+            .any(|identity| self.is_looping(identity, now))
+    }
+
+    /// Per-identity kill counters within the window, highest first
+    pub fn counters(&self, now: Instant) -> Vec<(&VictimIdentity, usize)> {
+        let mut counters: Vec<_> = self
+            .kills
+            .keys()
+            .map(|identity| (identity, self.count(identity, now)))
+            .filter(|(_, count)| *count > 0)
+            .collect();
+        counters.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        counters
+    }
+
+    pub const fn max_kills(&self) -> usize {
+        self.max_kills
+    }
+
+    pub const fn window(&self) -> Duration {
+        self.window
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity(exe: &str) -> VictimIdentity {
+        VictimIdentity::new(
+            Some(exe.to_string()),
+            &format!("{exe} --serve"),
+            Some("worker.service".to_string()),
+        )
+    }
+
+    #[test]
+    fn test_same_cmdline_same_identity() {
+        assert_eq!(identity("/usr/bin/worker"), identity("/usr/bin/worker"));
+        assert_ne!(identity("/usr/bin/worker"), identity("/usr/bin/other"));
+    }
+
+    #[test]
+    fn test_identity_of_process() {
+        let process = ProcessInfo {
+            pid: 4242,
+            cmdline: "/usr/bin/worker --serve".to_string(),
+            exe: "/usr/bin/worker".to_string(),
+            unit: "worker.service".to_string(),
+            ..ProcessInfo::default()
+        };
+        assert_eq!(VictimIdentity::of(&process), identity("/usr/bin/worker"));
+
+        // Unknown executable and unit
+        let process = ProcessInfo {
+            exe: String::new(),
+            unit: String::new(),
+            ..process
+        };
+        assert_eq!(
+            VictimIdentity::of(&process),
+            VictimIdentity::new(None, "/usr/bin/worker --serve", None)
+        );
+    }
+
+    #[test]
+    fn test_respawn_loop_detected_after_limit() {
+        let start = Instant::now();
+        let mut tracker = RespawnTracker::new(2, Duration::from_secs(600));
+        let worker = identity("/usr/bin/worker");
+
+        tracker.record(worker.clone(), start);
+        tracker.record(worker.clone(), start + Duration::from_secs(30));
+        assert!(!tracker.is_looping(&worker, start + Duration::from_secs(31)));
+        assert!(!tracker.has_loops(start + Duration::from_secs(31)));
+
+        assert_eq!(
+            tracker.record(worker.clone(), start + Duration::from_secs(60)),
+            3
+        );
+        assert!(tracker.is_looping(&worker, start + Duration::from_secs(61)));
+        assert!(tracker.has_loops(start + Duration::from_secs(61)));
+        assert!(!tracker.is_looping(&identity("/usr/bin/other"), start));
+    }
+
+    #[test]
+    fn test_respawn_counters_expire() {
+        let start = Instant::now();
+        let mut tracker = RespawnTracker::new(1, Duration::from_secs(60));
+        let worker = identity("/usr/bin/worker");
+
+        tracker.record(worker.clone(), start);
+        tracker.record(worker.clone(), start + Duration::from_secs(10));
+        assert!(tracker.is_looping(&worker, start + Duration::from_secs(11)));
+
+        let later = start + Duration::from_secs(120);
+        assert!(!tracker.is_looping(&worker, later));
+        assert!(tracker.counters(later).is_empty());
+    }
+}
@@ -1,18 +1,40 @@
 // Main daemon service implementation
 
-use crate::config::Config;
-use crate::killer::{kill_process, KillInfo, KillStrategy};
-use crate::monitor::{MemInfo, ProcessInfo};
+use super::adjust::AdjustTracker;
+use super::clock::Clock;
+use super::fairness::UserRanking;
+use super::oomwatch::{MissedOomKills, OomKillWatcher};
+use super::quota::{QuotaAction, QuotaBreach, QuotaEnforcer};
+use super::ratelimit::KillBudget;
+use super::respawn::{RespawnTracker, VictimIdentity};
+use crate::config::{Config, RuleOutcome, RuleSet, Trigger};
+use crate::killer::freeze::is_freezable;
+use crate::killer::reclaim::{is_reclaimable, pageout_process, reclaim_cgroup};
+use crate::killer::throttle::{is_throttleable, throttle_cgroup};
+use crate::killer::{
+    freeze_process, kill_process_with, kill_tree, FreezeTarget, KillInfo, KillResult, KillScope,
+    KillStrategy, ThrottleTarget,
+};
+use crate::monitor::oomkill::{KernelOomKill, KmsgReader};
+use crate::monitor::recording::Recorder;
+use crate::monitor::{
+    aggregate, format_age, group_members, MemInfo, ProcessInfo, ProcessSource, ProcessTree,
+    ProcfsSource, SystemSource, SHMEM_WARN_SHARE,
+};
 use crate::notify::NotificationManager;
 use crate::sanitize_for_log;
 use anyhow::{anyhow, Context, Result};
 use nix::libc::{setpriority, PRIO_PROCESS};
+use std::collections::HashMap;
 use std::fs;
 use std::io::Error;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+/// Number of users listed in status reports with --fair-users
+const STATUS_USERS: usize = 5;
+
 /// Set daemon priority using the configured value
 fn set_daemon_priority(priority: i32) -> Result<()> {
     // SAFETY: setpriority is a standard POSIX function. We pass valid arguments:
@@ -41,31 +63,328 @@ fn set_daemon_priority(priority: i32) -> Result<()> {
 }
 
 /// Daemon service that monitors memory and kills processes
-pub struct DaemonService {
+///
+/// Memory and process information come from `S` and `P`, the live /proc
+/// by default, or a fixture tree for deterministic tests.
+pub struct DaemonService<S: SystemSource = ProcfsSource, P: ProcessSource = ProcfsSource> {
     config: Config,
+    rules: RuleSet,
+    system: S,
+    process_source: P,
     notification_manager: NotificationManager,
     last_report: Instant,
+    clock: Clock,
     last_kill: Option<Instant>,
+    kill_episodes: u64,
+    kill_budget: Option<KillBudget>,
+    kill_budget_alerted: bool,
+    shmem_warned: bool,
+    respawn_tracker: Option<RespawnTracker>,
+    quotas: Option<QuotaEnforcer>,
+    adjusted: AdjustTracker,
+    oom_watcher: OomKillWatcher,
+    kmsg: Option<KmsgReader>,
+    frozen: Vec<FrozenVictim>,
+    throttled: Vec<ThrottledVictim>,
+    recorder: Option<Recorder>,
+    action_log: Option<Vec<Action>>,
     running: Arc<AtomicBool>,
 }
 
+/// An action taken against a victim (or that would have been taken, in
+/// dry-run mode), as collected by the action log
+#[derive(Debug, Clone)]
+pub struct Action {
+    pub at: Instant,
+    pub strategy: KillStrategy,
+    pub victim: ProcessInfo,
+    /// Memory state the decision was based on
+    pub meminfo: MemInfo,
+    /// Why this victim was selected
+    pub reason: String,
+    /// Other processes killed along with the victim (--kill-tree)
+    pub members: Vec<i32>,
+}
+
+/// A victim suspended by the Freeze strategy, waiting to be thawed or killed
+struct FrozenVictim {
+    victim: RankedVictim,
+    target: FreezeTarget,
+    frozen_at: Instant,
+}
+
+/// Thaw a frozen target, logging (but otherwise ignoring) failures
+fn thaw_target(target: &FreezeTarget) {
+    match target.thaw() {
+        Ok(()) => log::info!("Thawed {target}"),
+        Err(e) => log::debug!("Could not thaw {target}: {e:#}"),
+    }
+}
+
+/// A victim whose cgroup memory.high was lowered by the Throttle strategy,
+/// waiting for memory pressure to clear
+struct ThrottledVictim {
+    victim: ProcessInfo,
+    target: ThrottleTarget,
+    throttled_at: Instant,
+}
+
+/// Selection tier of a ranked victim, mirroring prefer/avoid semantics,
+/// in selection order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum VictimTier {
+    Preferred,
+    Candidate,
+    Avoided,
+}
+
+/// A killable process as ranked by one scan of the process table, with
+/// its rule outcome and the reason for its rank, so that acting on it
+/// uses what ranked it rather than a later scan
+#[derive(Debug, Clone)]
+struct RankedVictim {
+    process: ProcessInfo,
+    outcome: RuleOutcome,
+    reason: String,
+}
+
+impl RankedVictim {
+    const fn tier(&self) -> VictimTier {
+        VictimTier::of(&self.outcome)
+    }
+}
+
+/// Selection verdict for one process, as shown by `explain`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Preferred,
+    Candidate,
+    Avoided,
+    /// Killed along with the root of its application (--kill-tree)
+    Member,
+    Ignored,
+}
+
+impl std::fmt::Display for Verdict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            Self::Preferred => "preferred",
+            Self::Candidate => "candidate",
+            Self::Avoided => "avoided",
+            Self::Member => "member",
+            Self::Ignored => "ignored",
+        })
+    }
+}
+
+/// A process with its place in the victim ranking and the reason for it
+#[derive(Debug, Clone)]
+pub struct Explanation {
+    pub process: ProcessInfo,
+    /// Position in selection order, None for ignored processes
+    pub rank: Option<usize>,
+    pub verdict: Verdict,
+    pub reason: String,
+}
+
+/// Names of processes by pid, for matching rules on the parent name
+type ParentNames = HashMap<i32, String>;
+
+/// Replace killable processes by the roots of their applications, each
+/// standing for its whole subtree (--kill-tree)
+///
+/// An application is avoided as soon as any of its members is, since
+/// killing it kills that member too.
+fn group_applications(
+    killable: Vec<(ProcessInfo, RuleOutcome)>,
+) -> Vec<(ProcessInfo, RuleOutcome)> {
+    let processes: Vec<ProcessInfo> = killable.iter().map(|(p, _)| p.clone()).collect();
+    let applications = ProcessTree::new(&processes).applications();
+    let avoided: HashMap<i32, String> = killable
+        .iter()
+        .filter_map(|(p, outcome)| Some((p.pid, outcome.avoided_by.clone()?)))
+        .collect();
+    killable
+        .into_iter()
+        .filter_map(|(process, mut outcome)| {
+            let members = applications.get(&process.pid)?;
+            if outcome.avoided_by.is_none() {
+                outcome.avoided_by = members.iter().find_map(|member| {
+                    let rule = avoided.get(&member.pid)?;
+                    Some(format!("{rule} in its tree (PID {})", member.pid))
+                });
+                outcome.matched.extend(outcome.avoided_by.clone());
+            }
+            Some((aggregate(&process, members), outcome))
+        })
+        .collect()
+}
+
+impl VictimTier {
+    const fn of(outcome: &RuleOutcome) -> Self {
+        if outcome.avoided_by.is_some() {
+            Self::Avoided
+        } else if outcome.preferred_by.is_some() {
+            Self::Preferred
+        } else {
+            Self::Candidate
+        }
+    }
+}
+
+/// Take victims in rank order until their expected reclaim (RSS) covers
+/// `deficit_kb`, killing at most `max_kills` processes.
+///
+/// Avoided processes are never part of a batch; as in single-victim mode,
+/// one avoided process is only chosen when no other candidate exists.
+fn select_reclaim_batch(
+    ranked: Vec<RankedVictim>,
+    deficit_kb: u64,
+    max_kills: usize,
+) -> Vec<RankedVictim> {
+    let (avoided, ranked): (Vec<_>, Vec<_>) = ranked
+        .into_iter()
+        .partition(|victim| victim.tier() == VictimTier::Avoided);
+
+    let mut batch = Vec::new();
+    let mut expected_kb = 0u64;
+    for victim in ranked {
+        if batch.len() >= max_kills || (!batch.is_empty() && expected_kb >= deficit_kb) {
+            break;
+        }
+        expected_kb += victim.process.rss_kb;
+        batch.push(victim);
+    }
+
+    if batch.is_empty() {
+        if let Some(victim) = avoided.into_iter().next() {
+            log::warn!(
+                "No candidates available, selecting from avoided: {}",
+                victim.process
+            );
+            batch.push(victim);
+        }
+    }
+
+    batch
+}
+
 impl DaemonService {
-    /// Create a new daemon service
+    /// Create a new daemon service monitoring the live system
     pub fn new(config: Config) -> Self {
+        Self::with_sources(config, ProcfsSource, ProcfsSource)
+    }
+}
+
+impl<S: SystemSource, P: ProcessSource> DaemonService<S, P> {
+    /// Create a daemon service reading memory and process information
+    /// from the given sources
+    pub fn with_sources(config: Config, system: S, process_source: P) -> Self {
         let notification_manager = NotificationManager::new(
             config.notify_dbus,
             config.pre_kill_script.clone(),
             config.post_kill_script.clone(),
         );
+        let kill_budget = config
+            .kill_budget
+            .map(|max_kills| KillBudget::new(max_kills, config.kill_budget_window));
+        let respawn_tracker = (config.respawn_limit > 0)
+            .then(|| RespawnTracker::new(config.respawn_limit, config.respawn_window));
+        let quotas = (!config.user_quotas.is_empty())
+            .then(|| QuotaEnforcer::new(&config.user_quotas, config.quota_grace, group_members));
+        let oom_watcher = OomKillWatcher::new(&system, &config.watch_cgroups);
         Self {
+            rules: RuleSet::from_config(&config),
             config,
+            system,
+            process_source,
             notification_manager,
             last_report: Instant::now(),
+            clock: Clock::System,
             last_kill: None,
+            kill_episodes: 0,
+            kill_budget,
+            kill_budget_alerted: false,
+            shmem_warned: false,
+            respawn_tracker,
+            quotas,
+            adjusted: AdjustTracker::default(),
+            oom_watcher,
+            kmsg: None,
+            frozen: Vec::new(),
+            throttled: Vec::new(),
+            recorder: None,
+            action_log: None,
             running: Arc::new(AtomicBool::new(false)),
         }
     }
 
+    /// Use a different clock, e.g. a virtual clock when replaying recordings
+    pub const fn set_clock(&mut self, clock: Clock) {
+        self.clock = clock;
+    }
+
+    /// Start collecting the actions taken, see `take_actions`
+    pub fn enable_action_log(&mut self) {
+        self.action_log.get_or_insert_with(Vec::new);
+    }
+
+    /// Actions taken since the last call (empty unless the action log is enabled)
+    pub fn take_actions(&mut self) -> Vec<Action> {
+        self.action_log
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Add an action to the action log, if enabled
+    fn log_action(
+        &mut self,
+        strategy: KillStrategy,
+        victim: &ProcessInfo,
+        meminfo: &MemInfo,
+        reason: String,
+    ) {
+        if self.action_log.is_none() {
+            return;
+        }
+        let members = if self.config.kill_tree && strategy.terminates() && victim.tree_size > 1 {
+            self.tree_members(victim)
+                .into_iter()
+                .filter(|&pid| pid != victim.pid)
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let action = Action {
+            at: self.clock.now(),
+            strategy,
+            victim: victim.clone(),
+            meminfo: *meminfo,
+            reason,
+            members,
+        };
+        if let Some(log) = self.action_log.as_mut() {
+            log.push(action);
+        }
+    }
+
+    /// Append the current state of the system to the recording, if enabled
+    fn record_sample(&mut self, meminfo: &MemInfo) {
+        let Some(recorder) = self.recorder.as_mut() else {
+            return;
+        };
+
+        let result = self
+            .process_source
+            .processes()
+            .and_then(|processes| recorder.record(meminfo, self.system.pressure(), processes));
+        if let Err(e) = result {
+            log::warn!("Failed to record sample, recording stopped: {e:#}");
+            self.recorder = None;
+        }
+    }
+
     /// Get the running flag for signal handling
     pub fn running_flag(&self) -> Arc<AtomicBool> {
         Arc::clone(&self.running)
@@ -83,15 +402,25 @@ impl DaemonService {
         // Print startup information
         self.print_startup_info()?;
 
+        if let Some(path) = &self.config.record {
+            self.recorder = Some(Recorder::create(path)?);
+            log::info!("Recording samples to {path}");
+        }
+
+        match KmsgReader::open() {
+            Ok(kmsg) => self.kmsg = Some(kmsg),
+            Err(e) => log::info!("Kernel OOM kills will be reported without details: {e:#}"),
+        }
+
         self.running.store(true, Ordering::SeqCst);
-        self.last_report = Instant::now();
+        self.last_report = self.clock.now();
 
         // Setup signal handlers
         self.setup_signal_handlers()?;
 
         while self.running.load(Ordering::SeqCst) {
             // Read memory info once per iteration
-            let meminfo = match MemInfo::read() {
+            let meminfo = match self.system.meminfo() {
                 Ok(m) => m,
                 Err(e) => {
                     log::error!("Failed to read memory info: {e}");
@@ -100,15 +429,18 @@ impl DaemonService {
                 }
             };
 
+            self.record_sample(&meminfo);
+            let meminfo = self.account_zram(meminfo);
+
             // Check memory and act if needed
             if let Err(e) = self.check_and_act_with_meminfo(&meminfo) {
                 log::error!("Error in main loop: {e}");
             }
 
             // Periodic status report
-            if self.last_report.elapsed() >= self.config.report_interval {
+            if self.clock.since(self.last_report) >= self.config.report_interval {
                 self.report_status()?;
-                self.last_report = Instant::now();
+                self.last_report = self.clock.now();
             }
 
             // Use adaptive sleep or fixed interval based on configuration
@@ -122,6 +454,9 @@ impl DaemonService {
         }
 
         log::info!("OOM Guard daemon shutting down gracefully");
+        self.thaw_all();
+        self.restore_all_throttled();
+        self.restore_oom_score_adj();
         Ok(())
     }
 
@@ -140,10 +475,165 @@ impl DaemonService {
         Ok(())
     }
 
+    /// Print process selection settings: patterns, rules, slice weights,
+    /// per-user fairness and quotas
+    #[allow(clippy::cognitive_complexity)]
+    fn print_selection_info(&self) {
+        if !self.config.prefer.is_empty() {
+            log::info!("Prefer killing: {} pattern(s)", self.config.prefer.len());
+        }
+        if !self.config.avoid.is_empty() {
+            log::info!("Avoid killing: {} pattern(s)", self.config.avoid.len());
+        }
+        if !self.config.ignore.is_empty() {
+            log::info!("Ignore processes: {} pattern(s)", self.config.ignore.len());
+        }
+        let field_patterns = self.config.prefer_fields.len()
+            + self.config.avoid_fields.len()
+            + self.config.ignore_fields.len();
+        if field_patterns > 0 {
+            log::info!("Executable/user/unit/container patterns: {field_patterns}");
+        }
+        if !self.config.rules.is_empty() {
+            log::info!(
+                "Rules: {} (apply {} matching)",
+                self.config.rules.len(),
+                self.config.rule_mode
+            );
+        }
+        for (slice, weight) in &self.config.slice_weights {
+            log::info!("Slice weight: {slice} x{weight}");
+        }
+        if self.config.fair_users {
+            log::info!(
+                "Per-user fairness enabled{}",
+                self.config
+                    .user_allowance
+                    .map_or_else(String::new, |kb| format!(" (allowance {kb} KiB)"))
+            );
+            for (user, kb) in &self.config.user_allowances {
+                log::info!("User allowance: {user} {kb} KiB");
+            }
+        }
+        if self.config.foreground_weight < 1.0 || self.config.active_session_weight < 1.0 {
+            log::info!(
+                "Interactive penalty: foreground x{}, active session x{}",
+                self.config.foreground_weight,
+                self.config.active_session_weight
+            );
+        }
+        for quota in &self.config.user_quotas {
+            log::info!(
+                "User quota: {quota} (grace {}s)",
+                self.config.quota_grace.as_secs()
+            );
+        }
+        for (pattern, adj) in &self.config.oom_score_adj {
+            log::info!("Keep oom_score_adj at {adj}: {}", pattern.as_str());
+        }
+        if self.config.restore_oom_score_adj {
+            log::info!("Original oom_score_adj values are restored on shutdown");
+        }
+        if let Some(scale) = self.config.prefer_young {
+            log::info!("Prefer young processes: x1.5 at {}s", scale.as_secs());
+        }
+        if self.config.min_age.is_some() || self.config.max_age.is_some() {
+            let secs = |age: Option<Duration>| {
+                age.map_or_else(|| "-".to_string(), |a| format!("{}s", a.as_secs()))
+            };
+            log::info!(
+                "Process age window: min {}, max {}",
+                secs(self.config.min_age),
+                secs(self.config.max_age)
+            );
+        }
+    }
+
+    /// Status of quotas, oom_score_adj management and missed kernel OOM
+    /// kills
+    fn report_protection_status(&self) {
+        if let Some(quotas) = self.quotas.as_ref() {
+            for (uid, over_for) in quotas.over_quota(self.clock.now()) {
+                log::info!("Over quota: uid {uid} for {}s", over_for.as_secs());
+            }
+        }
+
+        if self.adjusted.len() > 0 {
+            log::info!(
+                "Managing the oom_score_adj of {} process(es)",
+                self.adjusted.len()
+            );
+        }
+
+        if self.oom_watcher.is_counting() {
+            log::info!(
+                "Missed: {} kernel OOM kill(s) since startup",
+                self.oom_watcher.missed()
+            );
+        }
+    }
+
+    /// Log the thresholds and how they trigger action, warning about
+    /// combinations that cannot work on this machine
+    fn print_trigger_info(&self, meminfo: &MemInfo) {
+        let config = &self.config;
+        let (mem_warn, mem_kill, swap_warn, swap_kill) = if config.mem_size_warn.is_some() {
+            (
+                format!("{} KiB", config.mem_size_warn.unwrap_or(0)),
+                format!("{} KiB", config.mem_size_kill.unwrap_or(0)),
+                format!("{} KiB", config.swap_size_warn.unwrap_or(0)),
+                format!("{} KiB", config.swap_size_kill.unwrap_or(0)),
+            )
+        } else {
+            (
+                format!("{:.1}%", config.mem_threshold_warn),
+                format!("{:.1}%", config.mem_threshold_kill),
+                format!("{:.1}%", config.swap_threshold_warn),
+                format!("{:.1}%", config.swap_threshold_kill),
+            )
+        };
+        let has_swap = meminfo.swap_total > 0;
+        let level = |mem: &str, swap: &str| match &config.trigger {
+            Trigger::All if has_swap => format!("mem <= {mem} AND swap <= {swap}"),
+            Trigger::Any if has_swap => format!("mem <= {mem} OR swap <= {swap}"),
+            _ => format!("mem <= {mem}"),
+        };
+        if let Trigger::Expr(expr) = &config.trigger {
+            log::info!("  SIGTERM when {expr}");
+            log::info!("  SIGKILL when {expr} AND {}", level(&mem_kill, &swap_kill));
+        } else {
+            log::info!("  SIGTERM when {}", level(&mem_warn, &swap_warn));
+            log::info!("  SIGKILL when {}", level(&mem_kill, &swap_kill));
+        }
+        if !has_swap && matches!(config.trigger, Trigger::All | Trigger::Any) {
+            log::info!("  No swap: memory thresholds alone trigger action");
+        }
+
+        let has_pressure = !config.trigger.uses_pressure() || self.system.pressure().is_some();
+        for warning in config.trigger.check_layout(has_swap, has_pressure) {
+            log::warn!("{warning}");
+        }
+    }
+
+    /// Log zram swap, and whether it counts as swap
+    fn print_zram_info(&self) {
+        let Some(zram) = self.system.zram_swap() else {
+            return;
+        };
+        log::info!("{zram}");
+        if self.config.zram_as_memory {
+            log::info!("zram left out of swap thresholds (--zram-as-memory)");
+        } else {
+            log::warn!(
+                "zram swap is held in RAM and counted as free swap; consider --zram-as-memory"
+            );
+        }
+    }
+
     /// Print startup information
     #[allow(clippy::cognitive_complexity)]
     fn print_startup_info(&self) -> Result<()> {
-        let meminfo = MemInfo::read()?;
+        let meminfo = self.system.meminfo()?;
 
         log::info!("=== OOM Guard v{} starting ===", env!("CARGO_PKG_VERSION"));
         log::info!(
@@ -158,43 +648,12 @@ impl DaemonService {
             meminfo.swap_free / 1024,
             meminfo.swap_free_percent()
         );
+        self.print_zram_info();
 
         log::info!("Thresholds:");
+        self.print_trigger_info(&self.read_meminfo()?);
 
-        // Display thresholds based on configuration
-        if self.config.mem_size_warn.is_some() {
-            log::info!(
-                "  SIGTERM when mem <= {} KiB AND swap <= {} KiB",
-                self.config.mem_size_warn.unwrap_or(0),
-                self.config.swap_size_warn.unwrap_or(0)
-            );
-            log::info!(
-                "  SIGKILL when mem <= {} KiB AND swap <= {} KiB",
-                self.config.mem_size_kill.unwrap_or(0),
-                self.config.swap_size_kill.unwrap_or(0)
-            );
-        } else {
-            log::info!(
-                "  SIGTERM when mem <= {:.1}% AND swap <= {:.1}%",
-                self.config.mem_threshold_warn,
-                self.config.swap_threshold_warn
-            );
-            log::info!(
-                "  SIGKILL when mem <= {:.1}% AND swap <= {:.1}%",
-                self.config.mem_threshold_kill,
-                self.config.swap_threshold_kill
-            );
-        }
-
-        if !self.config.prefer.is_empty() {
-            log::info!("Prefer killing: {} pattern(s)", self.config.prefer.len());
-        }
-        if !self.config.avoid.is_empty() {
-            log::info!("Avoid killing: {} pattern(s)", self.config.avoid.len());
-        }
-        if !self.config.ignore.is_empty() {
-            log::info!("Ignore processes: {} pattern(s)", self.config.ignore.len());
-        }
+        self.print_selection_info();
 
         if self.config.dry_run {
             log::warn!("DRY RUN MODE - will not actually kill processes");
@@ -204,6 +663,8 @@ impl DaemonService {
             log::info!("Kill process groups enabled");
         }
 
+        self.print_mitigation_info();
+
         if let Some(priority) = self.config.priority {
             log::info!("Daemon priority: {priority}");
         }
@@ -225,14 +686,113 @@ impl DaemonService {
         Ok(())
     }
 
+    /// Log the configured kill limits and non-lethal mitigations
+    fn print_mitigation_info(&self) {
+        log::info!(
+            "Cooldown after kill: {:.1}s at warn threshold, {:.1}s at kill threshold",
+            self.config.cooldown_warn.as_secs_f64(),
+            self.config.cooldown_kill.as_secs_f64()
+        );
+        if let Some(max_kills) = self.config.kill_budget {
+            log::info!(
+                "Kill budget: at most {max_kills} kill(s) per {} minute(s)",
+                self.config.kill_budget_window.as_secs() / 60
+            );
+        }
+
+        if self.config.proactive_reclaim {
+            log::info!(
+                "Proactive reclaim: top {} candidate(s) at warn threshold before killing",
+                self.config.proactive_reclaim_candidates
+            );
+        }
+
+        if self.config.throttle {
+            log::info!(
+                "Throttle mode: memory.high of the top candidate set to {:.0}% of its usage at warn threshold, lifted at {:.1}% available, escalating after {}s",
+                self.config.throttle_percent,
+                self.config.throttle_release_percent,
+                self.config.throttle_timeout.as_secs()
+            );
+        }
+
+        if self.config.freeze {
+            log::info!(
+                "Freeze mode: suspend victims at warn threshold, thaw at {:.1}% available, kill after {}s",
+                self.config.freeze_thaw_percent,
+                self.config.freeze_timeout.as_secs()
+            );
+        }
+
+        if let Some(margin) = self.config.reclaim_margin {
+            log::info!(
+                "Reclaim target mode: recover to warn threshold + {margin:.1}% (max {} kill(s) per episode)",
+                self.config.reclaim_max_kills
+            );
+        }
+    }
+
+    /// Memory information from the system source, with zram left out of
+    /// swap when --zram-as-memory is set
+    fn read_meminfo(&self) -> Result<MemInfo> {
+        Ok(self.account_zram(self.system.meminfo()?))
+    }
+
+    /// Leave zram devices out of swap with --zram-as-memory: the data they
+    /// hold is in RAM, already missing from MemAvailable
+    fn account_zram(&self, meminfo: MemInfo) -> MemInfo {
+        if !self.config.zram_as_memory {
+            return meminfo;
+        }
+        self.system
+            .zram_swap()
+            .map_or(meminfo, |zram| zram.exclude_from(&meminfo))
+    }
+
+    /// Run a single monitoring iteration: read memory information from the
+    /// system source and take action if thresholds are exceeded
+    pub fn check_once(&mut self) -> Result<()> {
+        let meminfo = self.read_meminfo()?;
+        self.check_and_act_with_meminfo(&meminfo)
+    }
+
     /// Check memory and take action if thresholds are exceeded
     fn check_and_act_with_meminfo(&mut self, meminfo: &MemInfo) -> Result<()> {
         log::debug!("Current memory status: {meminfo}");
 
-        // Check if we're in cooldown period after a recent kill
+        // Thaw or escalate previously frozen victims
+        self.manage_frozen(meminfo)?;
+
+        // Lift throttling once memory pressure has cleared
+        self.manage_throttled(meminfo);
+
+        // Per-user quotas apply whatever the state of the system, and
+        // configured processes are kept at their oom_score_adj; both work
+        // from one scan of the process table
+        if let Some(processes) = self.upkeep_processes() {
+            self.enforce_quotas(&processes);
+            self.manage_oom_score_adj(&processes);
+        }
+
+        // Report kills by the kernel OOM killer since the last check. The
+        // kernel log is drained every time so that its messages are matched
+        // to the counter increases they belong to
+        let missed = self.oom_watcher.check(&self.system);
+        let logged = self
+            .kmsg
+            .as_mut()
+            .map(KmsgReader::read_oom_kills)
+            .unwrap_or_default();
+        let kills = self.oom_watcher.match_logged(missed.as_ref(), logged);
+        if let Some(missed) = missed {
+            self.report_kernel_oom_kills(&missed, &kills);
+        }
+
+        // Check if we're in cooldown period after a recent kill. The cooldown
+        // is shorter while the kill threshold is breached than at warn level.
         if let Some(last_kill_time) = self.last_kill {
-            let cooldown = Duration::from_secs(10); // 10 second cooldown
-            let elapsed = last_kill_time.elapsed();
+            let cooldown = self.cooldown_for(self.breached_threshold(meminfo));
+            let elapsed = self.clock.since(last_kill_time);
             if elapsed < cooldown {
                 let remaining = cooldown.saturating_sub(elapsed);
                 log::debug!(
@@ -245,14 +805,65 @@ impl DaemonService {
 
         // Determine if we need to kill and what strategy to use
         let kill_strategy = self.determine_kill_strategy(meminfo)?;
+        if kill_strategy.is_none() {
+            self.shmem_warned = false;
+        }
 
         if let Some(strategy) = kill_strategy {
             log::warn!("Memory threshold exceeded - using {strategy:?} strategy");
+            self.warn_if_mostly_shmem(meminfo);
+
+            // At the warn threshold, first try to free memory without killing
+            if strategy != KillStrategy::Forceful
+                && self.config.proactive_reclaim
+                && self.try_proactive_reclaim(meminfo)?
+            {
+                return Ok(());
+            }
+
+            // Throttling is the softest action; escalate to the regular warn
+            // action once there is nothing left to throttle
+            let strategy = if strategy == KillStrategy::Throttle {
+                if self.throttle_next_victim()? {
+                    self.last_kill = Some(self.clock.now());
+                    return Ok(());
+                }
+                self.warn_strategy()
+            } else {
+                strategy
+            };
+
+            // Freezing is not a kill and does not use up the kill budget
+            if strategy == KillStrategy::Freeze {
+                self.freeze_next_victim()?;
+                self.last_kill = Some(self.clock.now());
+                return Ok(());
+            }
+
+            // Stop killing once the kill budget is used up
+            let allowed = self.kills_allowed();
+            if allowed == 0 {
+                self.alert_kill_budget_exhausted();
+                return Ok(());
+            }
+            self.kill_budget_alerted = false;
+
+            // At the kill threshold, reclaim target mode frees enough memory
+            // in one episode instead of one victim per cooldown period
+            if let (KillStrategy::Forceful, Some(margin)) = (strategy, self.config.reclaim_margin) {
+                let max_kills = self.config.reclaim_max_kills.min(allowed);
+                self.kill_until_recovered(meminfo, margin, max_kills)?;
+                self.last_kill = Some(self.clock.now());
+                return Ok(());
+            }
 
             // Select victim process
             if let Some(victim) = self.select_victim()? {
-                self.kill_victim(victim, strategy)?;
-                self.last_kill = Some(Instant::now());
+                let identity = VictimIdentity::of(&victim.process);
+                if self.kill_victim(victim, strategy)? {
+                    self.record_kill(identity);
+                }
+                self.last_kill = Some(self.clock.now());
             } else {
                 log::warn!("No suitable victim process found");
             }
@@ -261,408 +872,2808 @@ impl DaemonService {
         Ok(())
     }
 
-    /// Calculate adaptive sleep duration based on memory headroom
-    ///
-    /// Returns Duration between 100ms and 1000ms based on how far we are
-    /// from the warning thresholds. When memory is low (close to threshold),
-    /// we check more frequently. When memory is plentiful, we check less often.
-    ///
-    /// Adaptive sleep algorithm (inspired by earlyoom):
-    /// - headroom <= 0: 100ms (critical, check frequently)
-    /// - headroom >= 20: 1000ms (safe, check less often)
-    /// - Linear interpolation between these values
-    fn calculate_adaptive_sleep(&self, meminfo: &MemInfo) -> Duration {
-        // Constants for adaptive sleep
-        const MIN_SLEEP_MS: u64 = 100; // Minimum sleep when critical
-        const MAX_SLEEP_MS: u64 = 1000; // Maximum sleep when safe
-        const MAX_HEADROOM: f64 = 20.0; // Headroom at which we use max sleep
+    /// Process table for quotas and oom_score_adj upkeep, None when neither
+    /// is configured or the table cannot be read
+    fn upkeep_processes(&self) -> Option<Vec<ProcessInfo>> {
+        if self.quotas.is_none() && !self.rules.sets_oom_score_adj() {
+            return None;
+        }
+        self.process_source
+            .processes()
+            .inspect_err(|e| {
+                log::warn!("Failed to read processes for quotas and oom_score_adj: {e:#}");
+            })
+            .ok()
+    }
 
-        // Calculate headroom (how far we are from thresholds)
-        let mem_headroom = meminfo.mem_available_percent() - self.config.mem_threshold_warn;
-        let swap_headroom = meminfo.swap_free_percent() - self.config.swap_threshold_warn;
+    /// Warn about users over their quota, and kill within those that stayed
+    /// over it for the grace period
+    fn enforce_quotas(&mut self, processes: &[ProcessInfo]) {
+        let Some(quotas) = self.quotas.as_mut() else {
+            return;
+        };
+        for breach in quotas.check(processes, self.clock.now()) {
+            match breach.action {
+                QuotaAction::Warn => {
+                    let message = format!(
+                        "{}; killing within the user in {}s unless usage drops",
+                        sanitize_for_log(&breach.to_string()),
+                        self.config.quota_grace.as_secs()
+                    );
+                    log::warn!("{message}");
+                    self.notification_manager
+                        .send_alert("OOM Guard: User Over Quota", &message);
+                }
+                QuotaAction::Kill => {
+                    if let Err(e) = self.kill_over_quota(&breach) {
+                        log::error!("Failed to enforce quota for uid {}: {e:#}", breach.uid);
+                    }
+                }
+            }
+        }
+    }
 
-        // Use the smaller headroom (most critical resource)
-        let headroom = mem_headroom.min(swap_headroom);
+    /// Set the oom_score_adj of processes matched by --oom-score-adj
+    /// options and adj= rules, including processes started since the last
+    /// scan
+    fn manage_oom_score_adj(&mut self, processes: &[ProcessInfo]) {
+        if !self.rules.sets_oom_score_adj() {
+            return;
+        }
+        self.adjusted.forget_exited(processes);
 
-        // Map headroom to sleep duration:
-        // headroom <= 0: MIN_SLEEP_MS (critical, check frequently)
-        // headroom >= MAX_HEADROOM: MAX_SLEEP_MS (safe, check less often)
-        let sleep_ms = if headroom <= 0.0 {
-            MIN_SLEEP_MS
-        } else if headroom >= MAX_HEADROOM {
-            MAX_SLEEP_MS
-        } else {
-            // Linear interpolation: MIN_SLEEP_MS to MAX_SLEEP_MS
-            MIN_SLEEP_MS + ((headroom / MAX_HEADROOM) * (MAX_SLEEP_MS - MIN_SLEEP_MS) as f64) as u64
-        };
+        let parents = self.parent_names(processes);
+        let own_pid = std::process::id() as i32;
+        for process in processes
+            .iter()
+            .filter(|p| !p.is_zombie && p.pid != own_pid)
+        {
+            let outcome = self.evaluate_rules(process, &parents);
+            let Some((target, rule)) = outcome.oom_score_adj else {
+                continue;
+            };
+            if process.oom_score_adj == target || self.adjusted.is_pending(process, target) {
+                continue;
+            }
 
-        Duration::from_millis(sleep_ms)
+            let change = format!(
+                "oom_score_adj of {} from {} to {target} ({rule})",
+                sanitize_for_log(&process.label()),
+                process.oom_score_adj
+            );
+            let written = if self.config.dry_run {
+                log::info!("DRY RUN: Would set {change}");
+                false
+            } else {
+                match self.process_source.set_oom_score_adj(process.pid, target) {
+                    Ok(()) => {
+                        log::info!("Set {change}");
+                        true
+                    }
+                    Err(e) => {
+                        log::warn!("Failed to set {change}: {e:#}");
+                        false
+                    }
+                }
+            };
+            self.adjusted.record(process, target, written);
+        }
     }
 
-    /// Determine if we need to kill a process and what strategy to use
-    fn determine_kill_strategy(&self, meminfo: &MemInfo) -> Result<Option<KillStrategy>> {
-        // Check kill threshold first (more aggressive - SIGKILL)
-        let mem_critical = if let Some(kb) = self.config.mem_size_kill {
-            meminfo.is_mem_below_threshold_kb(kb)
-        } else {
-            meminfo.is_mem_below_threshold(self.config.mem_threshold_kill)
-        };
-
-        let swap_critical = if let Some(kb) = self.config.swap_size_kill {
-            meminfo.is_swap_below_threshold_kb(kb)
-        } else {
-            meminfo.is_swap_below_threshold(self.config.swap_threshold_kill)
+    /// Warn once per low-memory episode when most of the used memory is
+    /// shmem, which killing processes does not free
+    fn warn_if_mostly_shmem(&mut self, meminfo: &MemInfo) {
+        if self.shmem_warned {
+            return;
+        }
+        let Some(details) = self.system.mem_details() else {
+            return;
         };
-
-        if mem_critical && swap_critical {
-            log::warn!(
-                "Critical thresholds exceeded: mem={:.1}%, swap={:.1}%",
-                meminfo.mem_available_percent(),
-                meminfo.swap_free_percent()
+        let share = details.shmem_share(meminfo);
+        if share < SHMEM_WARN_SHARE {
+            return;
+        }
+        self.shmem_warned = true;
+        let mut message = format!(
+            "{:.0}% of used memory is shmem ({}): files on tmpfs (/dev/shm, /tmp, /run) and shared memory segments are not freed by killing processes; delete the files or shrink the tmpfs mounts instead",
+            share * 100.0,
+            MemInfo::format_size(details.shmem)
+        );
+        if details.unevictable > 0 {
+            message = format!(
+                "{message}; another {} is unevictable ({} mlocked)",
+                MemInfo::format_size(details.unevictable),
+                MemInfo::format_size(details.mlocked)
             );
-            return Ok(Some(KillStrategy::Forceful));
         }
+        log::warn!("{message}");
+    }
 
-        // Check warn threshold (less aggressive - SIGTERM)
-        let mem_low = if let Some(kb) = self.config.mem_size_warn {
-            meminfo.is_mem_below_threshold_kb(kb)
-        } else {
-            meminfo.is_mem_below_threshold(self.config.mem_threshold_warn)
-        };
+    /// Alert about kernel OOM kills since the last check, with the killed
+    /// tasks from the kernel log when readable, and run the hook script
+    fn report_kernel_oom_kills(&self, missed: &MissedOomKills, kills: &[KernelOomKill]) {
+        let mut details = vec![format!(
+            "the kernel OOM killer killed {} process(es) despite oom_guard ({} missed since startup)",
+            missed.count,
+            self.oom_watcher.missed()
+        )];
+        details.extend(
+            missed
+                .cgroups
+                .iter()
+                .map(|(cgroup, count)| format!("{count} in {cgroup}")),
+        );
+        details.extend(kills.iter().map(|kill| format!("killed {kill}")));
+        self.notification_manager.send_alert(
+            "OOM Guard: Kernel OOM Kill",
+            &sanitize_for_log(&details.join("; ")),
+        );
 
-        let swap_low = if let Some(kb) = self.config.swap_size_warn {
-            meminfo.is_swap_below_threshold_kb(kb)
-        } else {
-            meminfo.is_swap_below_threshold(self.config.swap_threshold_warn)
+        let Some(script) = &self.config.kernel_oom_script else {
+            return;
         };
-
-        if mem_low && swap_low {
-            log::warn!(
-                "Warning thresholds exceeded: mem={:.1}%, swap={:.1}%",
-                meminfo.mem_available_percent(),
-                meminfo.swap_free_percent()
+        let count = missed.count.to_string();
+        let cgroup = missed
+            .cgroups
+            .iter()
+            .map(|(cgroup, _)| cgroup.as_str())
+            .collect::<Vec<_>>()
+            .join(",");
+        let unknown = KernelOomKill::default();
+        for kill in if kills.is_empty() {
+            std::slice::from_ref(&unknown)
+        } else {
+            kills
+        } {
+            let known = |value: String| if kill.pid == 0 { String::new() } else { value };
+            self.notification_manager.run_event_script(
+                script,
+                "kernel-oom-kill",
+                &[
+                    ("OOM_GUARD_COUNT", count.clone()),
+                    ("OOM_GUARD_PID", known(kill.pid.to_string())),
+                    ("OOM_GUARD_NAME", kill.name.clone()),
+                    (
+                        "OOM_GUARD_UID",
+                        kill.uid.map(|uid| uid.to_string()).unwrap_or_default(),
+                    ),
+                    (
+                        "OOM_GUARD_RSS",
+                        kill.anon_rss_kb
+                            .map(|kb| kb.to_string())
+                            .unwrap_or_default(),
+                    ),
+                    (
+                        "OOM_GUARD_CGROUP",
+                        kill.cgroup.clone().unwrap_or_else(|| cgroup.clone()),
+                    ),
+                ],
             );
-            return Ok(Some(KillStrategy::Graceful));
         }
+    }
 
-        Ok(None)
+    /// Give adjusted processes that are still running their original
+    /// oom_score_adj back, with --restore-oom-score-adj
+    fn restore_oom_score_adj(&mut self) {
+        let originals = self.adjusted.take_originals();
+        if !self.config.restore_oom_score_adj || originals.is_empty() {
+            return;
+        }
+        let processes = self.process_source.processes().unwrap_or_default();
+        let mut restored = 0;
+        for (pid, start_ticks, original) in originals {
+            if !processes
+                .iter()
+                .any(|p| p.pid == pid && p.start_ticks == start_ticks)
+            {
+                continue;
+            }
+            match self.process_source.set_oom_score_adj(pid, original) {
+                Ok(()) => restored += 1,
+                Err(e) => log::warn!("Failed to restore oom_score_adj of PID {pid}: {e:#}"),
+            }
+        }
+        log::info!("Restored the original oom_score_adj of {restored} process(es)");
     }
 
-    /// Select a victim process to kill
-    fn select_victim(&self) -> Result<Option<ProcessInfo>> {
-        let mut processes = ProcessInfo::all_processes().context("Failed to get process list")?;
+    /// Kill the highest ranked killable process of a user over quota
+    fn kill_over_quota(&mut self, breach: &QuotaBreach) -> Result<()> {
+        let now = self.clock.now();
+        if let Some(quotas) = self.quotas.as_mut() {
+            quotas.restart_grace(breach.uid, now);
+        }
+        if self.kills_allowed() == 0 {
+            self.alert_kill_budget_exhausted();
+            return Ok(());
+        }
 
-        // Filter out processes based on ignore patterns
-        processes.retain(|p| !self.should_ignore(p));
+        let victim = self
+            .rank_victims()?
+            .into_iter()
+            .find(|victim| victim.process.uid == breach.uid);
+        let Some(victim) = victim else {
+            log::warn!(
+                "{}, but none of the user's processes can be killed",
+                sanitize_for_log(&breach.to_string())
+            );
+            return Ok(());
+        };
 
-        // Filter out root processes if configured
-        if self.config.ignore_root_user {
-            processes.retain(|p| p.uid != 0);
+        let meminfo = self.read_meminfo()?;
+        let reason = format!("{breach}; {}", victim.reason);
+        let identity = VictimIdentity::of(&victim.process);
+        if self.execute_kill(
+            victim.process,
+            KillStrategy::Graceful,
+            &meminfo,
+            &victim.outcome,
+            reason,
+        )? {
+            self.record_kill(identity);
         }
+        Ok(())
+    }
 
-        // Apply avoid patterns with lower priority
-        let (avoided, mut candidates): (Vec<_>, Vec<_>) =
-            processes.into_iter().partition(|p| self.should_avoid(p));
-
-        // Apply prefer patterns
-        let mut preferred: Vec<_> = candidates
-            .iter()
-            .filter(|p| self.should_prefer(p))
-            .cloned()
+    /// Try to free memory non-destructively from the top candidates: reclaim
+    /// from their cgroup (memory.reclaim) or page out their memory with
+    /// process_madvise(MADV_PAGEOUT), re-measuring after each attempt.
+    ///
+    /// Returns true if memory is back above the warn threshold.
+    fn try_proactive_reclaim(&self, meminfo: &MemInfo) -> Result<bool> {
+        let candidates: Vec<ProcessInfo> = self
+            .rank_victims()?
+            .into_iter()
+            .map(|victim| victim.process)
+            .take(self.config.proactive_reclaim_candidates)
             .collect();
 
-        // Sort by selection criteria
-        if self.config.sort_by_rss {
-            preferred.sort_by(|a, b| b.rss_kb.cmp(&a.rss_kb));
-            candidates.sort_by(|a, b| b.rss_kb.cmp(&a.rss_kb));
-        } else {
-            preferred.sort_by(|a, b| b.oom_score.cmp(&a.oom_score));
-            candidates.sort_by(|a, b| b.oom_score.cmp(&a.oom_score));
+        if self.config.dry_run {
+            for candidate in &candidates {
+                log::info!("DRY RUN: Would reclaim memory from {candidate}");
+            }
+            return Ok(false);
         }
 
-        // Select from preferred first, then candidates, then avoided
-        if let Some(victim) = preferred.first() {
-            log::info!("Selected preferred victim: {victim}");
-            return Ok(Some(victim.clone()));
-        }
+        let mut current = *meminfo;
+        let mut reclaimed_cgroups = Vec::new();
 
-        if let Some(victim) = candidates.first() {
-            log::info!("Selected candidate victim: {victim}");
-            return Ok(Some(victim.clone()));
-        }
+        for candidate in candidates {
+            let available_before = current.mem_available;
+
+            let cgroup = self.process_source.cgroup_dir(&candidate);
+            let target = if let Some(dir) = cgroup.filter(|dir| is_reclaimable(dir)) {
+                if reclaimed_cgroups.contains(&dir) {
+                    continue;
+                }
+                let deficit_kb = self.reclaim_deficit_kb(&current, 0.0).max(1024);
+                let freed = reclaim_cgroup(&dir, deficit_kb * 1024).unwrap_or(0);
+                let target = format!(
+                    "cgroup {} ({} charged memory released)",
+                    dir.display(),
+                    MemInfo::format_size(freed / 1024)
+                );
+                reclaimed_cgroups.push(dir);
+                target
+            } else {
+                if let Err(e) = pageout_process(candidate.pid) {
+                    log::debug!("Proactive reclaim skipped {candidate}: {e:#}");
+                    continue;
+                }
+                format!(
+                    "process {} ({})",
+                    candidate.pid,
+                    sanitize_for_log(&candidate.name)
+                )
+            };
+
+            current = self.read_meminfo()?;
+            log::info!(
+                "Proactive reclaim on {target}: available memory {} -> {} (+{})",
+                MemInfo::format_size(available_before),
+                MemInfo::format_size(current.mem_available),
+                MemInfo::format_size(current.mem_available.saturating_sub(available_before))
+            );
 
-        if let Some(victim) = avoided.first() {
-            log::warn!("No candidates available, selecting from avoided: {victim}");
-            return Ok(Some(victim.clone()));
+            if self.breached_threshold(&current).is_none() {
+                log::info!(
+                    "Proactive reclaim recovered memory above the warn threshold ({:.1}% available), no kill needed",
+                    current.mem_available_percent()
+                );
+                return Ok(true);
+            }
         }
 
-        Ok(None)
+        log::warn!(
+            "Proactive reclaim recovered {} in total, still below the warn threshold ({:.1}% available)",
+            MemInfo::format_size(current.mem_available.saturating_sub(meminfo.mem_available)),
+            current.mem_available_percent()
+        );
+        Ok(false)
     }
 
-    /// Check if a process should be ignored completely
-    fn should_ignore(&self, process: &ProcessInfo) -> bool {
-        // Always ignore our own process
-        if process.pid == std::process::id() as i32 {
-            return true;
+    /// Throttle the best candidate whose cgroup is not throttled yet
+    ///
+    /// Returns false if no candidate can be throttled, or if an earlier
+    /// throttle has not relieved the pressure within --throttle-timeout.
+    fn throttle_next_victim(&mut self) -> Result<bool> {
+        if let Some(entry) = self
+            .throttled
+            .iter()
+            .find(|t| self.clock.since(t.throttled_at) >= self.config.throttle_timeout)
+        {
+            log::warn!(
+                "Process {} ({}) throttled for {}s without memory recovering, escalating",
+                entry.victim.pid,
+                sanitize_for_log(&entry.victim.name),
+                self.clock.since(entry.throttled_at).as_secs()
+            );
+            return Ok(false);
         }
 
-        // Always ignore PID 1 (init)
-        if process.pid == 1 {
-            return true;
-        }
+        let candidate = self.rank_victims()?.into_iter().find_map(|victim| {
+            self.process_source
+                .cgroup_dir(&victim.process)
+                .filter(|dir| is_throttleable(dir))
+                .filter(|dir| !self.throttled.iter().any(|t| t.target.dir == *dir))
+                .map(|dir| (victim, dir))
+        });
+
+        let Some((
+            RankedVictim {
+                process: victim,
+                reason,
+                ..
+            },
+            dir,
+        )) = candidate
+        else {
+            log::info!("No candidate left to throttle, escalating");
+            return Ok(false);
+        };
 
-        // Never kill protected processes (oom_score_adj = -1000)
-        if process.oom_score_adj == -1000 {
-            log::debug!(
-                "Ignoring process {} (protected with oom_score_adj=-1000)",
-                process.pid
+        let meminfo = self.read_meminfo()?;
+        self.log_action(KillStrategy::Throttle, &victim, &meminfo, reason);
+
+        if self.config.dry_run {
+            log::info!(
+                "DRY RUN: Would throttle process {} ({}) via cgroup {}",
+                victim.pid,
+                sanitize_for_log(&victim.name),
+                dir.display()
             );
-            return true;
+            return Ok(true);
         }
 
-        // Never kill zombie processes (already dead)
-        if process.is_zombie {
-            log::debug!("Ignoring process {} (zombie)", process.pid);
-            return true;
+        let target = throttle_cgroup(&dir, self.config.throttle_percent)
+            .with_context(|| format!("Failed to throttle process {}", victim.pid))?;
+        log::warn!(
+            "Throttled process {} ({}) via {target}: memory.high {} -> {} bytes",
+            victim.pid,
+            sanitize_for_log(&victim.name),
+            target.original_high,
+            target.applied_high
+        );
+        self.run_throttle_script("throttle", &victim, &target);
+
+        self.throttled.push(ThrottledVictim {
+            victim,
+            target,
+            throttled_at: self.clock.now(),
+        });
+
+        Ok(true)
+    }
+
+    /// Restore memory.high of throttled cgroups once memory has recovered
+    fn manage_throttled(&mut self, meminfo: &MemInfo) {
+        if self.throttled.is_empty() {
+            return;
         }
 
-        // Check ignore patterns
-        for pattern in &self.config.ignore {
-            if pattern.is_match(&process.cmdline) || pattern.is_match(&process.name) {
-                log::debug!("Ignoring process {} (matches ignore pattern)", process.pid);
-                return true;
+        let recovered = meminfo.mem_available_percent() >= self.config.throttle_release_percent;
+
+        for entry in std::mem::take(&mut self.throttled) {
+            if !entry.target.exists() {
+                log::info!(
+                    "Throttled {} of process {} ({}) is gone",
+                    entry.target,
+                    entry.victim.pid,
+                    sanitize_for_log(&entry.victim.name)
+                );
+                continue;
+            }
+
+            if recovered {
+                log::info!(
+                    "Memory recovered ({:.1}% available), lifting throttle on process {} ({})",
+                    meminfo.mem_available_percent(),
+                    entry.victim.pid,
+                    sanitize_for_log(&entry.victim.name)
+                );
+                self.restore_throttled(&entry);
+                continue;
             }
-        }
 
-        false
+            self.throttled.push(entry);
+        }
     }
 
-    /// Check if a process should be avoided (but can be killed if necessary)
-    fn should_avoid(&self, process: &ProcessInfo) -> bool {
-        for pattern in &self.config.avoid {
-            if pattern.is_match(&process.cmdline) || pattern.is_match(&process.name) {
-                log::debug!("Avoiding process {} (matches avoid pattern)", process.pid);
-                return true;
-            }
+    /// Restore every throttled cgroup (called on shutdown)
+    fn restore_all_throttled(&mut self) {
+        for entry in std::mem::take(&mut self.throttled) {
+            log::info!(
+                "Lifting throttle on process {} ({}) on shutdown",
+                entry.victim.pid,
+                sanitize_for_log(&entry.victim.name)
+            );
+            self.restore_throttled(&entry);
         }
-        false
     }
 
-    /// Check if a process should be preferred for killing
-    fn should_prefer(&self, process: &ProcessInfo) -> bool {
-        for pattern in &self.config.prefer {
-            if pattern.is_match(&process.cmdline) || pattern.is_match(&process.name) {
-                log::debug!(
-                    "Preferring process {} (matches prefer pattern)",
-                    process.pid
+    /// Restore the original memory.high of a throttled victim
+    fn restore_throttled(&self, entry: &ThrottledVictim) {
+        match entry.target.restore() {
+            Ok(()) => {
+                log::info!(
+                    "Restored memory.high of {} to {} after {}s",
+                    entry.target,
+                    entry.target.original_high,
+                    self.clock.since(entry.throttled_at).as_secs()
                 );
-                return true;
+                self.run_throttle_script("unthrottle", &entry.victim, &entry.target);
             }
+            Err(e) => log::warn!("Could not restore {}: {e:#}", entry.target),
         }
-        false
     }
 
-    /// Kill the selected victim process
-    fn kill_victim(&self, victim: ProcessInfo, strategy: KillStrategy) -> Result<()> {
-        // Double-check: re-verify memory situation before killing
-        let meminfo = MemInfo::read()?;
-        let still_critical = self.determine_kill_strategy(&meminfo)?;
-
-        if still_critical.is_none() {
-            log::info!(
-                "Memory situation improved, skipping kill of {} ({})",
-                victim.pid,
-                sanitize_for_log(&victim.name)
+    /// Run the throttle hook, if configured
+    fn run_throttle_script(&self, event: &str, victim: &ProcessInfo, target: &ThrottleTarget) {
+        if let Some(script) = &self.config.throttle_script {
+            self.notification_manager.run_event_script(
+                script,
+                event,
+                &[
+                    ("OOM_GUARD_PID", victim.pid.to_string()),
+                    ("OOM_GUARD_NAME", victim.name.clone()),
+                    ("OOM_GUARD_CGROUP", target.dir.display().to_string()),
+                    ("OOM_GUARD_MEMORY_HIGH", target.applied_high.to_string()),
+                    (
+                        "OOM_GUARD_ORIGINAL_MEMORY_HIGH",
+                        target.original_high.clone(),
+                    ),
+                ],
             );
-            return Ok(());
         }
+    }
 
-        log::warn!(
-            "Killing process {} ({}) - RSS: {} KiB, Strategy: {:?}",
-            victim.pid,
-            sanitize_for_log(&victim.name),
-            victim.rss_kb,
-            strategy
+    /// Freeze the best candidate that is not already frozen or stopped
+    fn freeze_next_victim(&mut self) -> Result<()> {
+        let ranked = self.rank_victims()?.into_iter().find(|v| {
+            v.process.state != 'T'
+                && !self
+                    .frozen
+                    .iter()
+                    .any(|f| f.victim.process.pid == v.process.pid)
+        });
+
+        let Some(ranked) = ranked else {
+            log::warn!("No suitable victim process found to freeze");
+            return Ok(());
+        };
+        let victim = &ranked.process;
+
+        let meminfo = self.read_meminfo()?;
+        self.log_action(
+            KillStrategy::Freeze,
+            victim,
+            &meminfo,
+            ranked.reason.clone(),
         );
 
         if self.config.dry_run {
             log::info!(
-                "DRY RUN: Would kill process {} ({})",
+                "DRY RUN: Would freeze process {} ({})",
                 victim.pid,
                 sanitize_for_log(&victim.name)
             );
             return Ok(());
         }
 
-        let result = kill_process(victim.pid, strategy, self.config.kill_group)
-            .context("Failed to kill process")?;
-
-        let kill_info = KillInfo::new(
+        let cgroup = self
+            .process_source
+            .cgroup_dir(victim)
+            .filter(|dir| is_freezable(dir));
+        let cgroup = if cgroup.is_some() && self.cgroup_all_killable(victim)? {
+            cgroup
+        } else {
+            None
+        };
+        let target = freeze_process(victim.pid, self.config.kill_group, cgroup.as_deref())
+            .with_context(|| format!("Failed to freeze process {}", victim.pid))?;
+        log::warn!(
+            "Froze process {} ({}) via {target} - RSS: {} KiB",
             victim.pid,
-            victim.name.clone(),
-            victim.cmdline.clone(),
-            victim.uid,
-            victim.rss_kb,
-            victim.oom_score,
-            strategy,
-            &result,
+            sanitize_for_log(&victim.name),
+            victim.rss_kb
         );
 
-        if result.is_success() {
+        if let Some(reclaimed) = target.swap_out() {
             log::info!(
-                "Successfully killed process {} ({}): {}",
-                victim.pid,
-                sanitize_for_log(&victim.name),
-                result.description()
+                "Swapped out {} from frozen {target}",
+                MemInfo::format_size(reclaimed / 1024)
             );
-
-            if self.config.notify {
-                self.send_notification(&kill_info)?;
-            }
+        } else if let Err(e) = pageout_process(victim.pid) {
+            log::debug!("Could not page out stopped process {}: {e:#}", victim.pid);
         } else {
-            log::error!(
-                "Failed to kill process {} ({}): {}",
-                victim.pid,
-                sanitize_for_log(&victim.name),
-                result.description()
-            );
+            log::info!("Paged out memory of stopped process {}", victim.pid);
         }
 
+        self.frozen.push(FrozenVictim {
+            victim: ranked,
+            target,
+            frozen_at: self.clock.now(),
+        });
+
         Ok(())
     }
 
-    /// Send notification about killed process via scripts and D-Bus
-    fn send_notification(&self, kill_info: &KillInfo) -> Result<()> {
-        self.notification_manager.send_post_kill_notification(
-            kill_info.pid,
-            &kill_info.name,
-            &kill_info.cmdline,
-            kill_info.uid,
-            kill_info.rss_kb,
-            kill_info.oom_score,
-        )
-    }
+    /// Thaw frozen victims once memory has recovered, and kill those that
+    /// stayed frozen past the timeout without memory recovering
+    fn manage_frozen(&mut self, meminfo: &MemInfo) -> Result<()> {
+        if self.frozen.is_empty() {
+            return Ok(());
+        }
 
-    /// Report current status
-    fn report_status(&self) -> Result<()> {
-        let meminfo = MemInfo::read().context("Failed to read memory info")?;
+        let recovered = meminfo.mem_available_percent() >= self.config.freeze_thaw_percent;
 
-        log::info!("Status Report: {meminfo}");
+        for entry in std::mem::take(&mut self.frozen) {
+            let name = sanitize_for_log(&entry.victim.process.name);
 
-        if let Some(last_kill_time) = self.last_kill {
+            // The PID of a victim that was killed by someone else may have
+            // been reused: signalling it now would hit another process
+            if !self.process_source.is_alive(&entry.victim.process) {
+                log::info!(
+                    "Frozen process {} ({name}) has exited",
+                    entry.victim.process.pid
+                );
+                // Release anything else left frozen with it: a process group
+                // ID stays taken while any member is left, unlike the PID
+                if !matches!(entry.target, FreezeTarget::Process(_)) {
+                    thaw_target(&entry.target);
+                }
+                continue;
+            }
+
+            if recovered {
+                log::info!(
+                    "Memory recovered ({:.1}% available), thawing process {} ({name})",
+                    meminfo.mem_available_percent(),
+                    entry.victim.process.pid
+                );
+                thaw_target(&entry.target);
+                continue;
+            }
+
+            if self.clock.since(entry.frozen_at) >= self.config.freeze_timeout {
+                if self.kills_allowed() == 0 {
+                    self.alert_kill_budget_exhausted();
+                    self.frozen.push(entry);
+                    continue;
+                }
+
+                log::warn!(
+                    "Process {} ({name}) frozen for {}s without memory recovering, escalating to kill",
+                    entry.victim.process.pid,
+                    self.clock.since(entry.frozen_at).as_secs()
+                );
+                let identity = VictimIdentity::of(&entry.victim.process);
+                if self.kill_victim(entry.victim.clone(), KillStrategy::Forceful)? {
+                    self.record_kill(identity);
+                    self.last_kill = Some(self.clock.now());
+                }
+                // Thaw whatever survived (other group/cgroup members, or
+                // the victim itself if the kill was skipped)
+                thaw_target(&entry.target);
+                continue;
+            }
+
+            self.frozen.push(entry);
+        }
+
+        Ok(())
+    }
+
+    /// Thaw every frozen victim (called on shutdown)
+    fn thaw_all(&mut self) {
+        for entry in self.frozen.drain(..) {
             log::info!(
-                "Last kill: {:.1}s ago",
-                last_kill_time.elapsed().as_secs_f64()
+                "Thawing process {} ({}) on shutdown",
+                entry.victim.process.pid,
+                sanitize_for_log(&entry.victim.process.name)
             );
-        } else {
-            log::info!("No kills yet");
+            thaw_target(&entry.target);
         }
+    }
 
-        Ok(())
+    /// Cooldown after a kill, depending on which threshold is breached
+    const fn cooldown_for(&self, strategy: Option<KillStrategy>) -> Duration {
+        match strategy {
+            Some(KillStrategy::Forceful) => self.config.cooldown_kill,
+            _ => self.config.cooldown_warn,
+        }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::config::Config;
+    /// Number of kills still allowed by the kill budget
+    fn kills_allowed(&mut self) -> usize {
+        self.kill_budget
+            .as_mut()
+            .map_or(usize::MAX, |budget| budget.remaining(self.clock.now()))
+    }
 
-    fn create_test_meminfo(mem_available_percent: f64, swap_free_percent: f64) -> MemInfo {
-        // Create meminfo with specific percentages
-        // mem_available / mem_total = mem_available_percent / 100
-        let mem_total = 16_000_000; // 16 GB in KiB
-        let mem_available = (mem_total as f64 * mem_available_percent / 100.0) as u64;
+    /// Record a successful kill against the kill budget and respawn tracker
+    fn record_kill(&mut self, identity: VictimIdentity) {
+        let now = self.clock.now();
+        if let Some(budget) = self.kill_budget.as_mut() {
+            budget.record(now);
+        }
 
-        let swap_total = 8_000_000; // 8 GB in KiB
-        let swap_free = (swap_total as f64 * swap_free_percent / 100.0) as u64;
+        if let Some(tracker) = self.respawn_tracker.as_mut() {
+            let count = tracker.record(identity.clone(), now);
+            // Escalate once, when the identity first crosses the limit
+            if count == tracker.max_kills() + 1 {
+                self.alert_respawn_loop(&identity, count);
+            }
+        }
+    }
 
-        MemInfo {
-            mem_total,
-            mem_available,
-            swap_total,
-            swap_free,
+    /// Notify loudly about a respawn loop and run the respawn hook, if configured
+    fn alert_respawn_loop(&self, identity: &VictimIdentity, count: usize) {
+        let window_minutes = self.config.respawn_window.as_secs() / 60;
+        let message = format!(
+            "{} was killed {count} times in {window_minutes} minute(s) and keeps coming back; choosing different victims",
+            sanitize_for_log(&identity.to_string())
+        );
+        self.notification_manager
+            .send_alert("OOM Guard: Respawn Loop Detected", &message);
+
+        if let Some(script) = &self.config.respawn_script {
+            self.notification_manager.run_event_script(
+                script,
+                "respawn-loop",
+                &[
+                    ("OOM_GUARD_EXE", identity.exe.clone().unwrap_or_default()),
+                    ("OOM_GUARD_UNIT", identity.unit.clone().unwrap_or_default()),
+                    ("OOM_GUARD_KILLS", count.to_string()),
+                ],
+            );
         }
     }
 
-    #[test]
-    fn test_adaptive_sleep_critical() {
-        // When memory is critical (below threshold), sleep should be minimum (100ms)
-        let config = Config::default(); // warn threshold = 10%
-        let service = DaemonService::new(config);
+    /// Drop candidates caught in a respawn loop, unless nothing else is left
+    fn skip_respawn_loops<T>(&self, ranked: Vec<(ProcessInfo, T)>) -> Vec<(ProcessInfo, T)> {
+        let now = self.clock.now();
+        let Some(tracker) = self
+            .respawn_tracker
+            .as_ref()
+            .filter(|tracker| tracker.has_loops(now))
+        else {
+            return ranked;
+        };
 
-        // Memory at 5% available (below 10% warn threshold)
-        let meminfo = create_test_meminfo(5.0, 5.0);
-        let duration = service.calculate_adaptive_sleep(&meminfo);
+        let (looping, others): (Vec<_>, Vec<_>) = ranked
+            .into_iter()
+            .partition(|(p, _)| tracker.is_looping(&VictimIdentity::of(p), now));
 
-        assert_eq!(duration, Duration::from_millis(100));
+        for (process, _) in &looping {
+            log::warn!("Skipping {process}: killed repeatedly (respawn loop)");
+        }
+
+        if others.is_empty() {
+            looping
+        } else {
+            others
+        }
     }
 
-    #[test]
-    fn test_adaptive_sleep_safe() {
-        // When memory is safe (well above threshold), sleep should be maximum (1000ms)
-        let config = Config::default(); // warn threshold = 10%
-        let service = DaemonService::new(config);
+    /// Log and notify (once per exhaustion) that the kill budget is used up
+    fn alert_kill_budget_exhausted(&mut self) {
+        let Some(budget) = self.kill_budget.as_mut() else {
+            return;
+        };
+        let retry_in = budget.time_until_available(self.clock.now());
 
-        // Memory at 50% available (40% headroom above 10% threshold, > 20% max)
-        let meminfo = create_test_meminfo(50.0, 50.0);
-        let duration = service.calculate_adaptive_sleep(&meminfo);
+        if self.kill_budget_alerted {
+            log::debug!(
+                "Kill budget exhausted, not killing ({:.0}s until next kill is allowed)",
+                retry_in.as_secs_f64()
+            );
+            return;
+        }
 
-        assert_eq!(duration, Duration::from_millis(1000));
+        let message = format!(
+            "Kill budget of {} kill(s) per {} minute(s) exhausted; not killing for {:.0}s. Check the configured thresholds.",
+            budget.max_kills(),
+            budget.window().as_secs() / 60,
+            retry_in.as_secs_f64()
+        );
+        self.notification_manager
+            .send_alert("OOM Guard: Kill Budget Exhausted", &message);
+        self.kill_budget_alerted = true;
     }
 
-    #[test]
-    fn test_adaptive_sleep_interpolation() {
-        // When memory is in the middle, sleep should be interpolated
-        let config = Config::default(); // warn threshold = 10%
-        let service = DaemonService::new(config);
+    /// Kill as many victims as needed for their expected reclaim to cover
+    /// the deficit to the recovery level, logged as a single kill episode
+    fn kill_until_recovered(
+        &mut self,
+        meminfo: &MemInfo,
+        margin_percent: f64,
+        max_kills: usize,
+    ) -> Result<()> {
+        let deficit_kb = self.reclaim_deficit_kb(meminfo, margin_percent);
+        let batch = select_reclaim_batch(self.rank_victims()?, deficit_kb, max_kills);
+
+        if batch.is_empty() {
+            log::warn!("No suitable victim process found");
+            return Ok(());
+        }
 
-        // Memory at 20% available (10% headroom above 10% threshold)
-        // 10% headroom / 20% max headroom = 50% of the way
-        // 100 + (0.5 * 900) = 550ms
-        let meminfo = create_test_meminfo(20.0, 20.0);
-        let duration = service.calculate_adaptive_sleep(&meminfo);
+        self.kill_episodes += 1;
+        let episode = self.kill_episodes;
+        let expected_kb: u64 = batch.iter().map(|v| v.process.rss_kb).sum();
+        log::warn!(
+            "Kill episode #{episode}: need {} to reach recovery level, selected {} victim(s) with {} expected reclaim",
+            MemInfo::format_size(deficit_kb),
+            batch.len(),
+            MemInfo::format_size(expected_kb)
+        );
 
-        assert_eq!(duration, Duration::from_millis(550));
-    }
+        let total = batch.len();
+        let mut killed = 0;
+        for victim in batch {
+            // Stop at the recovery level rather than the kill threshold
+            let current = self.read_meminfo()?;
+            if self.reclaim_deficit_kb(&current, margin_percent) == 0 {
+                log::info!("Kill episode #{episode}: recovery level reached");
+                break;
+            }
 
-    #[test]
+            log::info!("Kill episode #{episode}: victim {}", victim.process);
+            let identity = VictimIdentity::of(&victim.process);
+            if self.execute_kill(
+                victim.process,
+                KillStrategy::Forceful,
+                &current,
+                &victim.outcome,
+                victim.reason,
+            )? {
+                self.record_kill(identity);
+                killed += 1;
+            }
+        }
+
+        log::warn!("Kill episode #{episode} finished: {killed}/{total} victim(s) killed");
+
+        Ok(())
+    }
+
+    /// Calculate adaptive sleep duration based on memory headroom
+    ///
+    /// Returns Duration between 100ms and 1000ms based on how far we are
+    /// from the warning thresholds. When memory is low (close to threshold),
+    /// we check more frequently. When memory is plentiful, we check less often.
+    ///
+    /// Adaptive sleep algorithm (inspired by earlyoom):
+    /// - headroom <= 0: 100ms (critical, check frequently)
+    /// - headroom >= 20: 1000ms (safe, check less often)
+    /// - Linear interpolation between these values
+    fn calculate_adaptive_sleep(&self, meminfo: &MemInfo) -> Duration {
+        // Constants for adaptive sleep
+        const MIN_SLEEP_MS: u64 = 100; // Minimum sleep when critical
+        const MAX_SLEEP_MS: u64 = 1000; // Maximum sleep when safe
+        const MAX_HEADROOM: f64 = 20.0; // Headroom at which we use max sleep
+
+        // Calculate headroom (how far we are from thresholds)
+        let mem_headroom = meminfo.mem_available_percent() - self.config.mem_threshold_warn;
+        let swap_headroom = meminfo.swap_free_percent() - self.config.swap_threshold_warn;
+
+        // Use the smaller headroom (most critical resource), counting swap
+        // only when it can trigger action
+        let headroom = if self.config.trigger.uses_swap(meminfo.swap_total > 0) {
+            mem_headroom.min(swap_headroom)
+        } else {
+            mem_headroom
+        };
+
+        // Map headroom to sleep duration:
+        // headroom <= 0: MIN_SLEEP_MS (critical, check frequently)
+        // headroom >= MAX_HEADROOM: MAX_SLEEP_MS (safe, check less often)
+        let sleep_ms = if headroom <= 0.0 {
+            MIN_SLEEP_MS
+        } else if headroom >= MAX_HEADROOM {
+            MAX_SLEEP_MS
+        } else {
+            // Linear interpolation: MIN_SLEEP_MS to MAX_SLEEP_MS
+            MIN_SLEEP_MS + ((headroom / MAX_HEADROOM) * (MAX_SLEEP_MS - MIN_SLEEP_MS) as f64) as u64
+        };
+
+        Duration::from_millis(sleep_ms)
+    }
+
+    /// Determine if we need to kill a process and what strategy to use
+    fn determine_kill_strategy(&self, meminfo: &MemInfo) -> Result<Option<KillStrategy>> {
+        let strategy = self.breached_threshold(meminfo).map(|strategy| {
+            // At the warn threshold, throttle first if configured
+            if strategy == KillStrategy::Graceful && self.config.throttle {
+                KillStrategy::Throttle
+            } else if strategy == KillStrategy::Graceful {
+                self.warn_strategy()
+            } else {
+                strategy
+            }
+        });
+
+        match strategy {
+            Some(KillStrategy::Forceful) => log::warn!(
+                "Critical thresholds exceeded: mem={:.1}%, swap={:.1}%",
+                meminfo.mem_available_percent(),
+                meminfo.swap_free_percent()
+            ),
+            Some(_) => log::warn!(
+                "Warning thresholds exceeded: mem={:.1}%, swap={:.1}%",
+                meminfo.mem_available_percent(),
+                meminfo.swap_free_percent()
+            ),
+            None => {}
+        }
+
+        Ok(strategy)
+    }
+
+    /// Action at the warn threshold: suspend instead of terminating if configured
+    const fn warn_strategy(&self) -> KillStrategy {
+        if self.config.freeze {
+            KillStrategy::Freeze
+        } else {
+            KillStrategy::Graceful
+        }
+    }
+
+    /// Check which threshold (if any) is currently breached, without logging:
+    /// Forceful for the kill threshold, Graceful for the warn threshold.
+    /// Without swap, including zram left out with --zram-as-memory, the
+    /// swap thresholds are left out of the trigger
+    pub fn breached_threshold(&self, meminfo: &MemInfo) -> Option<KillStrategy> {
+        let has_swap = meminfo.swap_total > 0;
+
+        // Kill threshold (more aggressive - SIGKILL)
+        let mem_critical = if let Some(kb) = self.config.mem_size_kill {
+            meminfo.is_mem_below_threshold_kb(kb)
+        } else {
+            meminfo.is_mem_below_threshold(self.config.mem_threshold_kill)
+        };
+        let swap_critical = has_swap.then(|| {
+            self.config.swap_size_kill.map_or_else(
+                || meminfo.is_swap_below_threshold(self.config.swap_threshold_kill),
+                |kb| meminfo.is_swap_below_threshold_kb(kb),
+            )
+        });
+
+        // Warn threshold (less aggressive - SIGTERM)
+        let mem_low = if let Some(kb) = self.config.mem_size_warn {
+            meminfo.is_mem_below_threshold_kb(kb)
+        } else {
+            meminfo.is_mem_below_threshold(self.config.mem_threshold_warn)
+        };
+        let swap_low = has_swap.then(|| {
+            self.config.swap_size_warn.map_or_else(
+                || meminfo.is_swap_below_threshold(self.config.swap_threshold_warn),
+                |kb| meminfo.is_swap_below_threshold_kb(kb),
+            )
+        });
+
+        let trigger = &self.config.trigger;
+        if let Trigger::Expr(expr) = trigger {
+            let pressure = self.system.pressure();
+            if !expr.matches(meminfo, pressure.as_ref()) {
+                return None;
+            }
+            return Some(if mem_critical {
+                KillStrategy::Forceful
+            } else {
+                KillStrategy::Graceful
+            });
+        }
+        if trigger.combine(mem_critical, swap_critical) {
+            Some(KillStrategy::Forceful)
+        } else if trigger.combine(mem_low, swap_low) {
+            Some(KillStrategy::Graceful)
+        } else {
+            None
+        }
+    }
+
+    /// Select a victim process to kill
+    fn select_victim(&self) -> Result<Option<RankedVictim>> {
+        let Some(victim) = self.rank_victims()?.into_iter().next() else {
+            return Ok(None);
+        };
+
+        match victim.tier() {
+            VictimTier::Preferred => log::info!("Selected preferred victim: {}", victim.process),
+            VictimTier::Candidate => log::info!("Selected candidate victim: {}", victim.process),
+            VictimTier::Avoided => log::warn!(
+                "No candidates available, selecting from avoided: {}",
+                victim.process
+            ),
+        }
+        Ok(Some(victim))
+    }
+
+    /// Rank all killable processes in selection order: preferred first,
+    /// then regular candidates, then avoided processes as a last resort;
+    /// within a tier by score as adjusted by the rules. With --fair-users,
+    /// the processes of the heaviest user come first within each tier
+    fn rank_victims(&self) -> Result<Vec<RankedVictim>> {
+        let processes = self
+            .process_source
+            .processes()
+            .context("Failed to get process list")?;
+        let (ranked, users) = self.rank_processes(processes);
+        Ok(ranked
+            .into_iter()
+            .map(|(process, outcome)| RankedVictim {
+                reason: self.describe_victim(&process, &outcome, users.as_ref()),
+                process,
+                outcome,
+            })
+            .collect())
+    }
+
+    /// Rank the victims among `processes` with their rule outcomes, along
+    /// with the per-user totals with --fair-users
+    fn rank_processes(
+        &self,
+        processes: Vec<ProcessInfo>,
+    ) -> (Vec<(ProcessInfo, RuleOutcome)>, Option<UserRanking>) {
+        let killable = self.killable_processes(processes);
+        let users = self
+            .config
+            .fair_users
+            .then(|| UserRanking::new(killable.iter().map(|(p, _)| p), &self.config));
+
+        let mut ranked: Vec<(ProcessInfo, RuleOutcome, usize, i64)> = killable
+            .into_iter()
+            .map(|(process, outcome)| {
+                let score = outcome.adjust(self.aged_score(&process));
+                let user_rank = users
+                    .as_ref()
+                    .and_then(|users| users.rank(process.uid))
+                    .unwrap_or(0);
+                (process, outcome, user_rank, score)
+            })
+            .collect();
+        ranked.sort_by_key(|(_, outcome, user_rank, score)| {
+            (
+                VictimTier::of(outcome),
+                *user_rank,
+                std::cmp::Reverse(*score),
+            )
+        });
+
+        let ranked = ranked
+            .into_iter()
+            .map(|(process, outcome, _, _)| (process, outcome))
+            .collect();
+        (self.skip_respawn_loops(ranked), users)
+    }
+
+    /// Processes that can be selected, with their rule outcomes; with
+    /// --kill-tree, the roots of applications standing for their subtrees
+    fn killable_processes(&self, processes: Vec<ProcessInfo>) -> Vec<(ProcessInfo, RuleOutcome)> {
+        let parents = self.parent_names(&processes);
+        let killable: Vec<(ProcessInfo, RuleOutcome)> = processes
+            .into_iter()
+            .filter_map(|process| {
+                let outcome = self.evaluate_rules(&process, &parents);
+                if let Some(reason) = self.ignore_reason(&process, &outcome) {
+                    log::debug!("Ignoring process {}: {reason}", process.pid);
+                    return None;
+                }
+                if !outcome.matched.is_empty() {
+                    log::debug!(
+                        "Process {} matches {}",
+                        process.pid,
+                        outcome.matched.join(", ")
+                    );
+                }
+                Some((process, outcome))
+            })
+            .collect();
+        if self.config.kill_tree {
+            group_applications(killable)
+        } else {
+            killable
+        }
+    }
+
+    /// Memory per user over the killable processes (--fair-users), None
+    /// when fairness is off or the process list cannot be read
+    fn user_ranking(&self) -> Option<UserRanking> {
+        if !self.config.fair_users {
+            return None;
+        }
+        match self.process_source.processes() {
+            Ok(processes) => {
+                let killable = self.killable_processes(processes);
+                Some(UserRanking::new(
+                    killable.iter().map(|(p, _)| p),
+                    &self.config,
+                ))
+            }
+            Err(e) => {
+                log::warn!("Failed to read processes for per-user totals: {e:#}");
+                None
+            }
+        }
+    }
+
+    /// Amount of memory (KiB) that must be freed to get back to the
+    /// recovery level: the warn threshold plus the configured reclaim margin
+    fn reclaim_deficit_kb(&self, meminfo: &MemInfo, margin_percent: f64) -> u64 {
+        let warn_kb = self.config.mem_size_warn.unwrap_or_else(|| {
+            (meminfo.mem_total as f64 * self.config.mem_threshold_warn / 100.0) as u64
+        });
+        let margin_kb = (meminfo.mem_total as f64 * margin_percent / 100.0) as u64;
+
+        (warn_kb + margin_kb).saturating_sub(meminfo.mem_available)
+    }
+
+    /// Process names by pid, only collected when a rule matches on the
+    /// parent name
+    fn parent_names(&self, processes: &[ProcessInfo]) -> ParentNames {
+        if !self.rules.uses_parent() {
+            return ParentNames::new();
+        }
+        processes.iter().map(|p| (p.pid, p.name.clone())).collect()
+    }
+
+    /// Combined effect of the rules matching a process
+    fn evaluate_rules(&self, process: &ProcessInfo, parents: &ParentNames) -> RuleOutcome {
+        self.rules
+            .evaluate(process, parents.get(&process.ppid).map(String::as_str))
+    }
+
+    /// Why a process can never be selected, or None if it can
+    fn ignore_reason(&self, process: &ProcessInfo, outcome: &RuleOutcome) -> Option<String> {
+        // Always ignore our own process
+        if process.pid == std::process::id() as i32 {
+            return Some("oom_guard itself".to_string());
+        }
+
+        // Always ignore PID 1 (init)
+        if process.pid == 1 {
+            return Some("pid 1 (init)".to_string());
+        }
+
+        // Never kill protected processes (oom_score_adj = -1000)
+        if process.oom_score_adj == -1000 {
+            return Some("protected (oom_score_adj=-1000)".to_string());
+        }
+
+        // Never kill zombie processes (already dead)
+        if process.is_zombie {
+            return Some("zombie".to_string());
+        }
+
+        if let Some(rule) = &outcome.ignored_by {
+            return Some(format!("matches {rule}"));
+        }
+
+        if self.config.ignore_root_user && process.uid == 0 {
+            return Some("owned by root (--ignore-root-user)".to_string());
+        }
+
+        if let Some(age) = process.age() {
+            if self.config.min_age.is_some_and(|min| age < min.as_secs()) {
+                return Some(format!("younger than --min-age ({})", format_age(age)));
+            }
+            if self.config.max_age.is_some_and(|max| age > max.as_secs()) {
+                return Some(format!("older than --max-age ({})", format_age(age)));
+            }
+        }
+
+        None
+    }
+
+    /// Whether every process in the victim's cgroup could be selected, so
+    /// that freezing the whole cgroup spares no protected process
+    fn cgroup_all_killable(&self, victim: &ProcessInfo) -> Result<bool> {
+        if victim.cgroup.is_empty() {
+            return Ok(false);
+        }
+
+        let processes = self
+            .process_source
+            .processes()
+            .context("Failed to get process list")?;
+        let parents = self.parent_names(&processes);
+        Ok(processes
+            .iter()
+            .filter(|p| p.cgroup == victim.cgroup)
+            .all(|p| self.is_killable(p, &parents)))
+    }
+
+    /// Whether a process can be selected at all
+    fn is_killable(&self, process: &ProcessInfo, parents: &ParentNames) -> bool {
+        let outcome = self.evaluate_rules(process, parents);
+        self.ignore_reason(process, &outcome).is_none()
+    }
+
+    /// Value a process is ranked by before rules adjust it
+    fn base_score(&self, process: &ProcessInfo) -> i64 {
+        if self.config.sort_by_rss {
+            process.rss_kb as i64
+        } else {
+            i64::from(process.oom_score)
+        }
+    }
+
+    /// Score multiplier for young processes (--prefer-young): 2 when just
+    /// started, 1.5 at the configured age, approaching 1 for old ones; 1
+    /// without the option or when the start time is unknown
+    fn age_factor(&self, process: &ProcessInfo) -> f64 {
+        match (self.config.prefer_young, process.age()) {
+            (Some(scale), Some(age)) => {
+                let scale = scale.as_secs_f64();
+                1.0 + scale / (age as f64 + scale)
+            }
+            _ => 1.0,
+        }
+    }
+
+    /// Base score with the age factor applied, before rules adjust it
+    fn aged_score(&self, process: &ProcessInfo) -> i64 {
+        (self.base_score(process) as f64 * self.age_factor(process)).round() as i64
+    }
+
+    /// Value a process is ranked by within its tier
+    fn ranking_key(&self, process: &ProcessInfo, outcome: &RuleOutcome) -> String {
+        let base = self.base_score(process);
+        let mut key = if self.config.sort_by_rss {
+            format!("RSS {base} KiB")
+        } else {
+            format!("oom_score {base}")
+        };
+        if process.tree_size > 1 {
+            key = format!("{key} over {} processes", process.tree_size);
+        }
+        let factor = self.age_factor(process);
+        if let (true, Some(age)) = (factor > 1.0, process.age()) {
+            key = format!("{key}, x{factor:.2} for age {}", format_age(age));
+        }
+        let adjusted = outcome.adjust(self.aged_score(process));
+        if adjusted == base {
+            key
+        } else {
+            format!("{key}, adjusted to {adjusted}")
+        }
+    }
+
+    /// Why a killable process is (or would be) selected: its tier, the
+    /// rules it matches, its user's place with --fair-users and the value
+    /// it is ranked by
+    fn describe_victim(
+        &self,
+        process: &ProcessInfo,
+        outcome: &RuleOutcome,
+        users: Option<&UserRanking>,
+    ) -> String {
+        let tier = match VictimTier::of(outcome) {
+            VictimTier::Avoided => "avoided",
+            VictimTier::Preferred => "preferred",
+            VictimTier::Candidate => "candidate",
+        };
+        let mut key = self.ranking_key(process, outcome);
+        if let Some(user) = users.and_then(|users| users.describe(process.uid)) {
+            key = format!("{user}; {key}");
+        }
+        if outcome.matched.is_empty() {
+            format!("{tier}; {key}")
+        } else {
+            format!("{tier}, matches {}; {key}", outcome.matched.join(", "))
+        }
+    }
+
+    /// Explain the current selection: every process with its rank (killable
+    /// processes, in selection order) or the reason it is ignored
+    pub fn explain(&self) -> Result<Vec<Explanation>> {
+        let processes = self
+            .process_source
+            .processes()
+            .context("Failed to get process list")?;
+        let parents = self.parent_names(&processes);
+        let (ranked, users) = self.rank_processes(processes.clone());
+
+        let mut explanations: Vec<Explanation> = ranked
+            .into_iter()
+            .enumerate()
+            .map(|(index, (process, outcome))| Explanation {
+                rank: Some(index + 1),
+                verdict: match VictimTier::of(&outcome) {
+                    VictimTier::Preferred => Verdict::Preferred,
+                    VictimTier::Candidate => Verdict::Candidate,
+                    VictimTier::Avoided => Verdict::Avoided,
+                },
+                reason: self.describe_victim(&process, &outcome, users.as_ref()),
+                process,
+            })
+            .collect();
+
+        if self.config.kill_tree {
+            let mut members = self.tree_member_explanations(&processes, &explanations, &parents);
+            explanations.append(&mut members);
+        }
+
+        let mut ignored: Vec<Explanation> = processes
+            .into_iter()
+            .filter_map(|process| {
+                let outcome = self.evaluate_rules(&process, &parents);
+                let reason = self.ignore_reason(&process, &outcome)?;
+                Some(Explanation {
+                    rank: None,
+                    verdict: Verdict::Ignored,
+                    reason,
+                    process,
+                })
+            })
+            .collect();
+        ignored.sort_by_key(|e| std::cmp::Reverse(e.process.rss_kb));
+        explanations.append(&mut ignored);
+
+        Ok(explanations)
+    }
+
+    /// Killable processes that are not ranked themselves because they are
+    /// killed along with the root of their application (--kill-tree)
+    fn tree_member_explanations(
+        &self,
+        processes: &[ProcessInfo],
+        ranked: &[Explanation],
+        parents: &ParentNames,
+    ) -> Vec<Explanation> {
+        let killable: Vec<ProcessInfo> = processes
+            .iter()
+            .filter(|p| self.is_killable(p, parents))
+            .cloned()
+            .collect();
+        let tree = ProcessTree::new(&killable);
+        let mut members: Vec<Explanation> = killable
+            .iter()
+            .filter_map(|process| {
+                let root = tree.app_root(process);
+                let rank = ranked.iter().find(|e| e.process.pid == root)?.rank;
+                (root != process.pid).then(|| Explanation {
+                    rank: None,
+                    verdict: Verdict::Member,
+                    reason: format!(
+                        "in the tree of PID {root}{}",
+                        rank.map_or_else(String::new, |rank| format!(" (rank {rank})"))
+                    ),
+                    process: process.clone(),
+                })
+            })
+            .collect();
+        members.sort_by_key(|e| std::cmp::Reverse(e.process.rss_kb));
+        members
+    }
+
+    /// Kill the selected victim process
+    ///
+    /// Returns true if the process was killed (or would have been, in dry-run mode)
+    fn kill_victim(&mut self, victim: RankedVictim, strategy: KillStrategy) -> Result<bool> {
+        // Double-check: re-verify memory situation before killing
+        let meminfo = self.read_meminfo()?;
+        let still_critical = self.determine_kill_strategy(&meminfo)?;
+
+        if still_critical.is_none() {
+            log::info!(
+                "Memory situation improved, skipping kill of {} ({})",
+                victim.process.pid,
+                sanitize_for_log(&victim.process.name)
+            );
+            return Ok(false);
+        }
+
+        self.execute_kill(
+            victim.process,
+            strategy,
+            &meminfo,
+            &victim.outcome,
+            victim.reason,
+        )
+    }
+
+    /// Kill a victim for `reason`, logging the action and notifying
+    ///
+    /// Returns true if the process was killed (or would have been, in dry-run mode)
+    fn execute_kill(
+        &mut self,
+        victim: ProcessInfo,
+        strategy: KillStrategy,
+        meminfo: &MemInfo,
+        outcome: &RuleOutcome,
+        reason: String,
+    ) -> Result<bool> {
+        log::warn!(
+            "Killing {} - RSS: {} KiB, Age: {}, Strategy: {:?}, Reason: {}",
+            sanitize_for_log(&victim.label()),
+            victim.rss_kb,
+            victim
+                .age()
+                .map_or_else(|| "unknown".to_string(), format_age),
+            strategy,
+            sanitize_for_log(&reason)
+        );
+
+        if self.config.dry_run {
+            log::info!(
+                "DRY RUN: Would kill process {} ({})",
+                victim.pid,
+                sanitize_for_log(&victim.name)
+            );
+            self.log_action(strategy, &victim, meminfo, reason);
+            return Ok(true);
+        }
+
+        let result = self
+            .send_kill(&victim, strategy, outcome)
+            .context("Failed to kill process")?;
+
+        let kill_info = KillInfo::new(&victim, strategy, reason, &result);
+
+        if result.is_success() {
+            self.log_action(strategy, &victim, meminfo, kill_info.reason.clone());
+            log::info!(
+                "Successfully killed {}: {}",
+                sanitize_for_log(&kill_info.label),
+                result.description()
+            );
+
+            if self.config.notify {
+                self.send_notification(&kill_info)?;
+            }
+            for script in &outcome.notify {
+                self.run_rule_notify_script(script, &kill_info);
+            }
+        } else {
+            log::error!(
+                "Failed to kill process {} ({}): {}",
+                victim.pid,
+                sanitize_for_log(&victim.name),
+                result.description()
+            );
+        }
+
+        Ok(result.is_success())
+    }
+
+    /// Deliver the kill to the victim alone, its process group or cgroup,
+    /// or its whole application subtree
+    fn send_kill(
+        &self,
+        victim: &ProcessInfo,
+        strategy: KillStrategy,
+        outcome: &RuleOutcome,
+    ) -> Result<KillResult> {
+        let ladder = outcome.signals.as_deref();
+        if let Some(scope) = outcome.kill_scope {
+            return kill_process_with(victim.pid, strategy, scope, ladder);
+        }
+        if self.config.kill_tree && victim.tree_size > 1 {
+            let members = self.tree_members(victim);
+            let pss_kb: u64 = members
+                .iter()
+                .filter_map(|&pid| ProcessInfo::read_pss_kb(pid))
+                .sum();
+            log::info!(
+                "Tree of process {}: {} processes, RSS {} KiB, PSS {pss_kb} KiB",
+                victim.pid,
+                members.len(),
+                victim.rss_kb
+            );
+            return kill_tree(victim.pid, &members, strategy, ladder);
+        }
+        let scope = if self.config.kill_group {
+            KillScope::Group
+        } else {
+            KillScope::Process
+        };
+        kill_process_with(victim.pid, strategy, scope, ladder)
+    }
+
+    /// PIDs of the killable processes in the application subtree of `root`,
+    /// root first, from a fresh process list (--kill-tree)
+    fn tree_members(&self, root: &ProcessInfo) -> Vec<i32> {
+        let killable: Vec<ProcessInfo> = match self.process_source.processes() {
+            Ok(processes) => {
+                let parents = self.parent_names(&processes);
+                processes
+                    .into_iter()
+                    .filter(|p| self.is_killable(p, &parents))
+                    .collect()
+            }
+            Err(e) => {
+                log::warn!("Failed to read the tree of process {}: {e:#}", root.pid);
+                Vec::new()
+            }
+        };
+        ProcessTree::new(&killable)
+            .applications()
+            .remove(&root.pid)
+            .map_or_else(
+                || vec![root.pid],
+                |members| members.iter().map(|p| p.pid).collect(),
+            )
+    }
+
+    /// Send notification about killed process via scripts and D-Bus
+    fn send_notification(&self, kill_info: &KillInfo) -> Result<()> {
+        self.notification_manager
+            .send_post_kill_notification(kill_info)
+    }
+
+    /// Run a notify target of a matching rule after a kill
+    fn run_rule_notify_script(&self, script: &str, kill_info: &KillInfo) {
+        self.notification_manager
+            .run_event_script(script, "kill", &kill_info.env_vars());
+    }
+
+    /// Report current status
+    fn report_status(&mut self) -> Result<()> {
+        let meminfo = self
+            .system
+            .meminfo()
+            .context("Failed to read memory info")?;
+
+        log::info!("Status Report: {meminfo}");
+        if let Some(zram) = self.system.zram_swap() {
+            log::info!("{zram}");
+        }
+        if let Some(details) = self.system.mem_details() {
+            log::info!("Memory details: {details}");
+        }
+
+        if let Some(last_kill_time) = self.last_kill {
+            log::info!(
+                "Last kill: {:.1}s ago",
+                self.clock.since(last_kill_time).as_secs_f64()
+            );
+        } else {
+            log::info!("No kills yet");
+        }
+
+        if let Some(budget) = self.kill_budget.as_mut() {
+            log::info!(
+                "Kill budget: {}/{} used in the last {} minute(s)",
+                budget.used(self.clock.now()),
+                budget.max_kills(),
+                budget.window().as_secs() / 60
+            );
+        }
+
+        for entry in &self.frozen {
+            log::info!(
+                "Frozen: process {} ({}) via {} for {}s",
+                entry.victim.process.pid,
+                sanitize_for_log(&entry.victim.process.name),
+                entry.target,
+                self.clock.since(entry.frozen_at).as_secs()
+            );
+        }
+
+        for entry in &self.throttled {
+            log::info!(
+                "Throttled: process {} ({}) via {}, memory.high {} bytes for {}s",
+                entry.victim.pid,
+                sanitize_for_log(&entry.victim.name),
+                entry.target,
+                entry.target.applied_high,
+                self.clock.since(entry.throttled_at).as_secs()
+            );
+        }
+
+        if let Some(users) = self.user_ranking() {
+            log::info!("Memory per user (heaviest first):");
+            for usage in users.iter().take(STATUS_USERS) {
+                log::info!("  {}: {}", sanitize_for_log(&usage.name()), usage.summary());
+            }
+        }
+
+        self.report_protection_status();
+
+        if let Some(tracker) = self.respawn_tracker.as_ref() {
+            let counters = tracker.counters(self.clock.now());
+            if !counters.is_empty() {
+                log::info!(
+                    "Kills per victim in the last {} minute(s) (respawn limit {}):",
+                    tracker.window().as_secs() / 60,
+                    tracker.max_kills()
+                );
+            }
+            for (identity, count) in counters {
+                log::info!("  {count}x {}", sanitize_for_log(&identity.to_string()));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<S: SystemSource, P: ProcessSource> Drop for DaemonService<S, P> {
+    fn drop(&mut self) {
+        // Never leave victims frozen or throttled when the daemon goes away
+        self.thaw_all();
+        self.restore_all_throttled();
+        self.restore_oom_score_adj();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::monitor::{unix_now, FixtureSource, MemoryPressure};
+
+    fn create_test_meminfo(mem_available_percent: f64, swap_free_percent: f64) -> MemInfo {
+        // Create meminfo with specific percentages
+        // mem_available / mem_total = mem_available_percent / 100
+        let mem_total = 16_000_000; // 16 GB in KiB
+        let mem_available = (mem_total as f64 * mem_available_percent / 100.0) as u64;
+
+        let swap_total = 8_000_000; // 8 GB in KiB
+        let swap_free = (swap_total as f64 * swap_free_percent / 100.0) as u64;
+
+        MemInfo {
+            mem_total,
+            mem_available,
+            swap_total,
+            swap_free,
+        }
+    }
+
+    #[test]
+    fn test_adaptive_sleep_critical() {
+        // When memory is critical (below threshold), sleep should be minimum (100ms)
+        let config = Config::default(); // warn threshold = 10%
+        let service = DaemonService::new(config);
+
+        // Memory at 5% available (below 10% warn threshold)
+        let meminfo = create_test_meminfo(5.0, 5.0);
+        let duration = service.calculate_adaptive_sleep(&meminfo);
+
+        assert_eq!(duration, Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_adaptive_sleep_safe() {
+        // When memory is safe (well above threshold), sleep should be maximum (1000ms)
+        let config = Config::default(); // warn threshold = 10%
+        let service = DaemonService::new(config);
+
+        // Memory at 50% available (40% headroom above 10% threshold, > 20% max)
+        let meminfo = create_test_meminfo(50.0, 50.0);
+        let duration = service.calculate_adaptive_sleep(&meminfo);
+
+        assert_eq!(duration, Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn test_adaptive_sleep_interpolation() {
+        // When memory is in the middle, sleep should be interpolated
+        let config = Config::default(); // warn threshold = 10%
+        let service = DaemonService::new(config);
+
+        // Memory at 20% available (10% headroom above 10% threshold)
+        // 10% headroom / 20% max headroom = 50% of the way
+        // 100 + (0.5 * 900) = 550ms
+        let meminfo = create_test_meminfo(20.0, 20.0);
+        let duration = service.calculate_adaptive_sleep(&meminfo);
+
+        assert_eq!(duration, Duration::from_millis(550));
+    }
+
+    #[test]
     fn test_adaptive_sleep_uses_minimum_headroom() {
         // Should use the smaller headroom (memory or swap)
         let config = Config::default(); // warn threshold = 10%
         let service = DaemonService::new(config);
 
-        // Memory at 50% but swap at 12% (only 2% headroom)
-        // 2% headroom / 20% max headroom = 10% of the way
-        // 100 + (0.1 * 900) = 190ms
-        let meminfo = create_test_meminfo(50.0, 12.0);
-        let duration = service.calculate_adaptive_sleep(&meminfo);
+        // Memory at 50% but swap at 12% (only 2% headroom)
+        // 2% headroom / 20% max headroom = 10% of the way
+        // 100 + (0.1 * 900) = 190ms
+        let meminfo = create_test_meminfo(50.0, 12.0);
+        let duration = service.calculate_adaptive_sleep(&meminfo);
+
+        assert_eq!(duration, Duration::from_millis(190));
+    }
+
+    #[test]
+    fn test_adaptive_sleep_ignores_swap_the_trigger_ignores() {
+        // Memory at 50%, swap full: swap cannot trigger anything
+        let meminfo = create_test_meminfo(50.0, 0.0);
+        for trigger in ["mem-only", "mem < 5% || psi_full > 30"] {
+            let config = Config {
+                trigger: trigger.parse().unwrap(),
+                ..Config::default()
+            };
+            let service = DaemonService::new(config);
+            assert_eq!(
+                service.calculate_adaptive_sleep(&meminfo),
+                Duration::from_millis(1000),
+                "{trigger}"
+            );
+        }
+
+        let config = Config {
+            trigger: "mem < 5% && swap < 10%".parse().unwrap(),
+            ..Config::default()
+        };
+        let service = DaemonService::new(config);
+        assert_eq!(
+            service.calculate_adaptive_sleep(&meminfo),
+            Duration::from_millis(100)
+        );
+    }
+
+    #[test]
+    fn test_adaptive_sleep_exactly_at_threshold() {
+        // When exactly at threshold (headroom = 0), should be minimum
+        let config = Config::default(); // warn threshold = 10%
+        let service = DaemonService::new(config);
+
+        // Memory exactly at 10% threshold
+        let meminfo = create_test_meminfo(10.0, 10.0);
+        let duration = service.calculate_adaptive_sleep(&meminfo);
+
+        assert_eq!(duration, Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_adaptive_sleep_at_max_headroom() {
+        // When headroom is exactly at max (20%), should be maximum sleep
+        let config = Config::default(); // warn threshold = 10%
+        let service = DaemonService::new(config);
+
+        // Memory at 30% (20% headroom above 10% threshold)
+        let meminfo = create_test_meminfo(30.0, 30.0);
+        let duration = service.calculate_adaptive_sleep(&meminfo);
+
+        assert_eq!(duration, Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn test_cooldown_is_shorter_at_kill_threshold() {
+        let config = Config::default();
+        let service = DaemonService::new(config);
+
+        assert_eq!(
+            service.cooldown_for(Some(KillStrategy::Graceful)),
+            Duration::from_secs(10)
+        );
+        assert_eq!(
+            service.cooldown_for(Some(KillStrategy::Forceful)),
+            Duration::from_secs(5)
+        );
+    }
+
+    #[test]
+    fn test_freeze_replaces_graceful_strategy() {
+        let config = Config {
+            freeze: true,
+            ..Config::default()
+        };
+        let service = DaemonService::new(config);
+
+        // Warn threshold breached: freeze instead of SIGTERM
+        let meminfo = create_test_meminfo(8.0, 8.0);
+        assert_eq!(
+            service.determine_kill_strategy(&meminfo).unwrap(),
+            Some(KillStrategy::Freeze)
+        );
+
+        // Kill threshold breached: still kill
+        let meminfo = create_test_meminfo(2.0, 2.0);
+        assert_eq!(
+            service.determine_kill_strategy(&meminfo).unwrap(),
+            Some(KillStrategy::Forceful)
+        );
+    }
+
+    #[test]
+    fn test_throttle_comes_before_freeze() {
+        let config = Config {
+            throttle: true,
+            freeze: true,
+            ..Config::default()
+        };
+        let service = DaemonService::new(config);
+
+        // Warn threshold breached: throttle first, freeze once nothing is left
+        let meminfo = create_test_meminfo(8.0, 8.0);
+        assert_eq!(
+            service.determine_kill_strategy(&meminfo).unwrap(),
+            Some(KillStrategy::Throttle)
+        );
+        assert_eq!(service.warn_strategy(), KillStrategy::Freeze);
+
+        // Kill threshold breached: still kill
+        let meminfo = create_test_meminfo(2.0, 2.0);
+        assert_eq!(
+            service.determine_kill_strategy(&meminfo).unwrap(),
+            Some(KillStrategy::Forceful)
+        );
+    }
+
+    #[test]
+    fn test_kill_budget_limits_kills() {
+        let config = Config {
+            kill_budget: Some(2),
+            ..Config::default()
+        };
+        let mut service = DaemonService::new(config);
 
-        assert_eq!(duration, Duration::from_millis(190));
+        assert_eq!(service.kills_allowed(), 2);
+        let identity = VictimIdentity::new(None, "/usr/bin/worker", None);
+        service.record_kill(identity.clone());
+        service.record_kill(identity);
+        assert_eq!(service.kills_allowed(), 0);
     }
 
     #[test]
-    fn test_adaptive_sleep_exactly_at_threshold() {
-        // When exactly at threshold (headroom = 0), should be minimum
+    fn test_respawn_loop_victim_is_skipped() {
+        let config = Config {
+            respawn_limit: 1,
+            ..Config::default()
+        };
+        let processes = fixture_processes();
+        let (_dir, _fixture, mut service) =
+            fixture_service(config, &create_test_meminfo(8.0, 8.0), &processes);
+        assert_eq!(service.select_victim().unwrap().unwrap().process.pid, 4002);
+
+        service.record_kill(VictimIdentity::of(&processes[1]));
+        service.record_kill(VictimIdentity::of(&processes[1]));
+        assert_eq!(service.select_victim().unwrap().unwrap().process.pid, 4001);
+    }
+
+    fn create_test_process(pid: i32, rss_kb: u64) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            name: format!("worker-{pid}"),
+            cmdline: format!("/usr/bin/worker {pid}"),
+            rss_kb,
+            oom_score: 100,
+            oom_score_adj: 0,
+            uid: 1000,
+            state: 'S',
+            is_zombie: false,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_reclaim_deficit_to_recovery_level() {
         let config = Config::default(); // warn threshold = 10%
         let service = DaemonService::new(config);
 
-        // Memory exactly at 10% threshold
-        let meminfo = create_test_meminfo(10.0, 10.0);
-        let duration = service.calculate_adaptive_sleep(&meminfo);
+        // 16 GB total, 4% available; recovery level is 10% + 5% = 15%
+        let meminfo = create_test_meminfo(4.0, 0.0);
+        let deficit = service.reclaim_deficit_kb(&meminfo, 5.0);
 
-        assert_eq!(duration, Duration::from_millis(100));
+        assert_eq!(deficit, 2_400_000 - 640_000);
+
+        // Already above the recovery level: nothing to reclaim
+        let meminfo = create_test_meminfo(20.0, 0.0);
+        assert_eq!(service.reclaim_deficit_kb(&meminfo, 5.0), 0);
+    }
+
+    /// A ranked victim in `tier`, as if matched by a prefer or avoid rule
+    fn ranked(process: ProcessInfo, tier: VictimTier) -> RankedVictim {
+        let rule = Some("test".to_string());
+        let outcome = match tier {
+            VictimTier::Preferred => RuleOutcome {
+                preferred_by: rule,
+                ..RuleOutcome::default()
+            },
+            VictimTier::Candidate => RuleOutcome::default(),
+            VictimTier::Avoided => RuleOutcome {
+                avoided_by: rule,
+                ..RuleOutcome::default()
+            },
+        };
+        RankedVictim {
+            process,
+            outcome,
+            reason: String::new(),
+        }
     }
 
     #[test]
-    fn test_adaptive_sleep_at_max_headroom() {
-        // When headroom is exactly at max (20%), should be maximum sleep
-        let config = Config::default(); // warn threshold = 10%
-        let service = DaemonService::new(config);
+    fn test_reclaim_batch_covers_deficit() {
+        // 20 identical workers holding 500 MiB each
+        let ranked: Vec<_> = (100..120)
+            .map(|pid| ranked(create_test_process(pid, 512_000), VictimTier::Candidate))
+            .collect();
 
-        // Memory at 30% (20% headroom above 10% threshold)
-        let meminfo = create_test_meminfo(30.0, 30.0);
-        let duration = service.calculate_adaptive_sleep(&meminfo);
+        let batch = select_reclaim_batch(ranked, 1_500_000, 10);
 
-        assert_eq!(duration, Duration::from_millis(1000));
+        assert_eq!(batch.len(), 3);
+        assert_eq!(
+            batch.iter().map(|v| v.process.pid).collect::<Vec<_>>(),
+            vec![100, 101, 102]
+        );
+    }
+
+    #[test]
+    fn test_reclaim_batch_respects_max_kills() {
+        let ranked: Vec<_> = (100..120)
+            .map(|pid| ranked(create_test_process(pid, 512_000), VictimTier::Candidate))
+            .collect();
+
+        let batch = select_reclaim_batch(ranked, 100_000_000, 5);
+
+        assert_eq!(batch.len(), 5);
+    }
+
+    #[test]
+    fn test_reclaim_batch_kills_at_least_one() {
+        let ranked = vec![ranked(
+            create_test_process(100, 1024),
+            VictimTier::Candidate,
+        )];
+
+        let batch = select_reclaim_batch(ranked, 0, 10);
+
+        assert_eq!(batch.len(), 1);
+    }
+
+    #[test]
+    fn test_reclaim_batch_skips_avoided() {
+        let ranked = vec![
+            ranked(create_test_process(100, 512_000), VictimTier::Preferred),
+            ranked(create_test_process(101, 512_000), VictimTier::Candidate),
+            ranked(create_test_process(102, 4_000_000), VictimTier::Avoided),
+        ];
+
+        let batch = select_reclaim_batch(ranked, 4_000_000, 10);
+
+        assert_eq!(
+            batch.iter().map(|v| v.process.pid).collect::<Vec<_>>(),
+            vec![100, 101]
+        );
+    }
+
+    #[test]
+    fn test_reclaim_batch_falls_back_to_single_avoided() {
+        let ranked = vec![
+            ranked(create_test_process(100, 512_000), VictimTier::Avoided),
+            ranked(create_test_process(101, 512_000), VictimTier::Avoided),
+        ];
+
+        let batch = select_reclaim_batch(ranked, 4_000_000, 10);
+
+        assert_eq!(batch.len(), 1);
+        assert_eq!(batch[0].process.pid, 100);
+    }
+
+    /// Service reading from a fixture tree with the given processes
+    fn fixture_service(
+        config: Config,
+        meminfo: &MemInfo,
+        processes: &[ProcessInfo],
+    ) -> (
+        tempfile::TempDir,
+        FixtureSource,
+        DaemonService<FixtureSource, FixtureSource>,
+    ) {
+        let dir = tempfile::tempdir().unwrap();
+        let fixture = FixtureSource::new(dir.path());
+        fixture.write_meminfo(meminfo).unwrap();
+        for process in processes {
+            fixture.write_process(process).unwrap();
+        }
+
+        let config = Config {
+            dry_run: true,
+            ..config
+        };
+        let service = DaemonService::with_sources(config, fixture.clone(), fixture.clone());
+        (dir, fixture, service)
+    }
+
+    fn fixture_processes() -> Vec<ProcessInfo> {
+        let mut small = create_test_process(4001, 100_000);
+        small.oom_score = 200;
+        let mut big = create_test_process(4002, 4_000_000);
+        big.oom_score = 800;
+        big.name = "browser".to_string();
+        big.cmdline = "/usr/bin/browser --tabs".to_string();
+        vec![small, big]
+    }
+
+    #[test]
+    fn test_proactive_reclaim_stops_once_recovered() {
+        let mut child = std::process::Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .unwrap();
+        let mut process = create_test_process(child.id() as i32, 100_000);
+        process.oom_score = 500;
+
+        // Memory is back above the warn threshold after the first candidate
+        let (_dir, _fixture, mut service) = fixture_service(
+            Config::default(),
+            &create_test_meminfo(50.0, 50.0),
+            &[process],
+        );
+        service.config.dry_run = false;
+        let reclaimed = service.try_proactive_reclaim(&create_test_meminfo(8.0, 8.0));
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+        assert!(reclaimed.unwrap());
+    }
+
+    #[test]
+    fn test_proactive_reclaim_without_candidates_falls_through() {
+        let low = create_test_meminfo(8.0, 8.0);
+        let (_dir, _fixture, mut service) = fixture_service(
+            Config::default(),
+            &low,
+            &[create_test_process(999_999, 100_000)],
+        );
+        assert!(!service.try_proactive_reclaim(&low).unwrap());
+
+        service.config.dry_run = false;
+        assert!(!service.try_proactive_reclaim(&low).unwrap());
+    }
+
+    #[test]
+    fn test_throttle_picks_fixture_cgroup() {
+        let mut processes = fixture_processes();
+        processes[0].cgroup = "/user.slice/small.scope".to_string();
+        processes[1].cgroup = "/user.slice/browser.scope".to_string();
+        let config = Config {
+            throttle: true,
+            ..Config::default()
+        };
+        let (dir, _fixture, mut service) =
+            fixture_service(config, &create_test_meminfo(8.0, 8.0), &processes);
+
+        // Only the small process has a cgroup with memory.high
+        let cgroup = dir.path().join("cgroup/user.slice/small.scope");
+        fs::create_dir_all(&cgroup).unwrap();
+        fs::write(cgroup.join("memory.high"), "max\n").unwrap();
+
+        service.enable_action_log();
+        service.check_once().unwrap();
+        let actions = service.take_actions();
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].strategy, KillStrategy::Throttle);
+        assert_eq!(actions[0].victim.pid, 4001);
+    }
+
+    #[test]
+    fn test_throttle_escalates_after_timeout() {
+        let mut processes = fixture_processes();
+        processes[0].cgroup = "/user.slice/small.scope".to_string();
+        processes[1].cgroup = "/user.slice/browser.scope".to_string();
+        let config = Config {
+            throttle: true,
+            ..Config::default()
+        };
+        let (dir, _fixture, mut service) =
+            fixture_service(config, &create_test_meminfo(8.0, 8.0), &processes);
+        for scope in ["small.scope", "browser.scope"] {
+            let cgroup = dir.path().join("cgroup/user.slice").join(scope);
+            fs::create_dir_all(&cgroup).unwrap();
+            fs::write(cgroup.join("memory.high"), "max").unwrap();
+            fs::write(cgroup.join("memory.current"), "409600\n").unwrap();
+        }
+        service.config.dry_run = false;
+        service.clock = Clock::virtual_clock();
+
+        assert!(service.throttle_next_victim().unwrap());
+        let browser = dir
+            .path()
+            .join("cgroup/user.slice/browser.scope/memory.high");
+        assert_eq!(fs::read_to_string(browser).unwrap(), "368640");
+
+        // Memory has not recovered in time: escalate rather than throttle
+        // the next candidate
+        service.clock.set_elapsed(Duration::from_secs(30));
+        assert!(!service.throttle_next_victim().unwrap());
+        let small = dir.path().join("cgroup/user.slice/small.scope/memory.high");
+        assert_eq!(fs::read_to_string(small).unwrap(), "max");
+
+        service.restore_all_throttled();
+    }
+
+    #[test]
+    fn test_freeze_whole_cgroup_only_when_all_killable() {
+        let mut processes = fixture_processes();
+        for process in &mut processes {
+            process.cgroup = "/user.slice/app.scope".to_string();
+        }
+        let (_dir, _fixture, service) = fixture_service(
+            Config::default(),
+            &create_test_meminfo(50.0, 50.0),
+            &processes,
+        );
+        assert!(service.cgroup_all_killable(&processes[1]).unwrap());
+
+        processes[0].oom_score_adj = -1000;
+        let (_dir, _fixture, service) = fixture_service(
+            Config::default(),
+            &create_test_meminfo(50.0, 50.0),
+            &processes,
+        );
+        assert!(!service.cgroup_all_killable(&processes[1]).unwrap());
+    }
+
+    #[test]
+    fn test_frozen_victim_with_reused_pid_is_dropped() {
+        let processes = fixture_processes();
+        let (_dir, fixture, mut service) = fixture_service(
+            Config::default(),
+            &create_test_meminfo(8.0, 8.0),
+            &processes,
+        );
+        service.enable_action_log();
+        service.clock = Clock::virtual_clock();
+        service.frozen.push(FrozenVictim {
+            victim: ranked(processes[1].clone(), VictimTier::Candidate),
+            target: FreezeTarget::Process(processes[1].pid),
+            frozen_at: service.clock.now(),
+        });
+
+        // The victim was killed by someone else and its PID reused
+        fixture.remove_process(4002).unwrap();
+        let reused = processes[1].clone().with_start_time(unix_now() - 5, 0);
+        fixture.write_process(&reused).unwrap();
+        service.clock.set_elapsed(service.config.freeze_timeout);
+        service
+            .manage_frozen(&create_test_meminfo(8.0, 8.0))
+            .unwrap();
+
+        assert!(service.frozen.is_empty());
+        assert!(service.take_actions().is_empty());
+        assert!(service.last_kill.is_none());
+    }
+
+    #[test]
+    fn test_fixture_no_action_above_thresholds() {
+        let (_dir, _fixture, mut service) = fixture_service(
+            Config::default(),
+            &create_test_meminfo(50.0, 50.0),
+            &fixture_processes(),
+        );
+
+        service.check_once().unwrap();
+        assert!(service.last_kill.is_none());
+    }
+
+    #[test]
+    fn test_fixture_threshold_crossing_kills_highest_score() {
+        let (_dir, fixture, mut service) = fixture_service(
+            Config::default(),
+            &create_test_meminfo(50.0, 50.0),
+            &fixture_processes(),
+        );
+
+        service.check_once().unwrap();
+        assert!(service.last_kill.is_none());
+
+        // Memory drops below the warn threshold
+        fixture
+            .write_meminfo(&create_test_meminfo(8.0, 8.0))
+            .unwrap();
+        assert_eq!(service.select_victim().unwrap().unwrap().process.pid, 4002);
+        service.check_once().unwrap();
+        assert!(service.last_kill.is_some());
+    }
+
+    #[test]
+    fn test_warns_once_when_memory_is_mostly_shmem() {
+        let (dir, fixture, mut service) = fixture_service(
+            Config::default(),
+            &create_test_meminfo(8.0, 8.0),
+            &fixture_processes(),
+        );
+        let meminfo_path = dir.path().join("meminfo");
+        let with_shmem = |shmem_kb: u64| {
+            let content = fs::read_to_string(&meminfo_path).unwrap();
+            fs::write(&meminfo_path, format!("{content}Shmem:    {shmem_kb} kB\n")).unwrap();
+        };
+
+        // 14.72 GB used, 5 GB of it shmem
+        with_shmem(5_000_000);
+        service.check_once().unwrap();
+        assert!(!service.shmem_warned);
+
+        service.last_kill = None;
+        fixture
+            .write_meminfo(&create_test_meminfo(8.0, 8.0))
+            .unwrap();
+        with_shmem(10_000_000);
+        service.check_once().unwrap();
+        assert!(service.shmem_warned);
+        assert_eq!(
+            service.system.mem_details().map(|d| d.shmem),
+            Some(10_000_000)
+        );
+
+        // Memory recovered: warn again next time
+        service.last_kill = None;
+        fixture
+            .write_meminfo(&create_test_meminfo(50.0, 50.0))
+            .unwrap();
+        service.check_once().unwrap();
+        assert!(!service.shmem_warned);
+    }
+
+    #[test]
+    fn test_zram_as_memory_ignores_zram_swap() {
+        let meminfo = create_test_meminfo(8.0, 80.0);
+        let write_zram = |dir: &std::path::Path| {
+            fs::write(
+                dir.join("swaps"),
+                "Filename\tType\tSize\tUsed\tPriority\n/dev/zram0 partition 8000000 1600000 100\n",
+            )
+            .unwrap();
+            fs::create_dir_all(dir.join("block/zram0")).unwrap();
+            fs::write(
+                dir.join("block/zram0/mm_stat"),
+                "1638400000 409600000 419430400 0 419430400 0 0 0 0\n",
+            )
+            .unwrap();
+        };
+
+        // zram looks like plenty of free swap
+        let (dir, _fixture, mut service) =
+            fixture_service(Config::default(), &meminfo, &fixture_processes());
+        write_zram(dir.path());
+        service.check_once().unwrap();
+        assert!(service.last_kill.is_none());
+
+        let config = Config {
+            zram_as_memory: true,
+            ..Config::default()
+        };
+        let (dir, _fixture, mut service) = fixture_service(config, &meminfo, &fixture_processes());
+        write_zram(dir.path());
+        let accounted = service.read_meminfo().unwrap();
+        assert_eq!((accounted.swap_total, accounted.swap_free), (0, 0));
+        assert_eq!(
+            service.breached_threshold(&accounted),
+            Some(KillStrategy::Graceful)
+        );
+        service.check_once().unwrap();
+        assert!(service.last_kill.is_some());
+    }
+
+    #[test]
+    fn test_trigger_without_swap() {
+        let no_swap = MemInfo {
+            swap_total: 0,
+            swap_free: 0,
+            ..create_test_meminfo(4.0, 0.0)
+        };
+        let service_with = |trigger: &str| {
+            let config = Config {
+                trigger: trigger.parse().unwrap(),
+                ..Config::default()
+            };
+            fixture_service(config, &no_swap, &fixture_processes())
+        };
+
+        // Memory alone decides for the keyword triggers
+        let (_dir, _fixture, service) = service_with("all");
+        assert_eq!(
+            service.breached_threshold(&no_swap),
+            Some(KillStrategy::Forceful)
+        );
+        let (_dir, _fixture, service) = service_with("any");
+        assert_eq!(
+            service.breached_threshold(&create_test_meminfo(50.0, 4.0)),
+            Some(KillStrategy::Forceful)
+        );
+        assert_eq!(
+            service.breached_threshold(&MemInfo {
+                mem_available: 8_000_000,
+                ..no_swap
+            }),
+            None
+        );
+
+        // Swap comparisons never match, pressure ones do with PSI
+        let (_dir, fixture, service) = service_with("mem < 10% && (swap < 10% || psi_full > 30)");
+        assert_eq!(service.breached_threshold(&no_swap), None);
+        fixture
+            .write_pressure(&MemoryPressure {
+                full_avg10: 40.0,
+                ..MemoryPressure::default()
+            })
+            .unwrap();
+        assert_eq!(
+            service.breached_threshold(&no_swap),
+            Some(KillStrategy::Forceful)
+        );
+        assert_eq!(
+            service.breached_threshold(&create_test_meminfo(8.0, 50.0)),
+            Some(KillStrategy::Graceful)
+        );
+    }
+
+    #[test]
+    fn test_fixture_prefer_pattern_changes_victim() {
+        let config = Config {
+            prefer: vec![regex::Regex::new("^worker-40+1$").unwrap()],
+            ..Config::default()
+        };
+        let (_dir, _fixture, service) =
+            fixture_service(config, &create_test_meminfo(8.0, 8.0), &fixture_processes());
+
+        assert_eq!(service.select_victim().unwrap().unwrap().process.pid, 4001);
+    }
+
+    #[test]
+    fn test_fixture_cooldown_blocks_second_kill() {
+        let (_dir, _fixture, mut service) = fixture_service(
+            Config::default(),
+            &create_test_meminfo(8.0, 8.0),
+            &fixture_processes(),
+        );
+
+        service.check_once().unwrap();
+        let first_kill = service.last_kill.unwrap();
+
+        // Still below the threshold, but within the cooldown period
+        service.check_once().unwrap();
+        assert_eq!(service.last_kill, Some(first_kill));
+    }
+
+    #[test]
+    fn test_explain_ranks_and_gives_reasons() {
+        let config = Config {
+            ignore: vec![
+                regex::Regex::new("^(sshd|init)$").unwrap(),
+                regex::Regex::new("^cron[d]?$").unwrap(),
+            ],
+            avoid: vec![regex::Regex::new("^browser$|--tabs").unwrap()],
+            ..Config::default()
+        };
+        let mut processes = fixture_processes();
+        let mut cron = create_test_process(4003, 5_000);
+        cron.name = "crond".to_string();
+        let mut protected = create_test_process(4004, 5_000);
+        protected.oom_score_adj = -1000;
+        processes.extend([cron, protected]);
+        let (_dir, _fixture, service) =
+            fixture_service(config, &create_test_meminfo(50.0, 50.0), &processes);
+
+        let explanations = service.explain().unwrap();
+        let find = |pid: i32| explanations.iter().find(|e| e.process.pid == pid).unwrap();
+
+        assert_eq!(find(4001).rank, Some(1));
+        assert_eq!(find(4001).verdict, Verdict::Candidate);
+        assert_eq!(find(4001).reason, "candidate; oom_score 200");
+
+        assert_eq!(find(4002).rank, Some(2));
+        assert_eq!(find(4002).verdict, Verdict::Avoided);
+        assert_eq!(
+            find(4002).reason,
+            "avoided, matches --avoid pattern 1 (^browser$|--tabs); oom_score 800"
+        );
+
+        assert_eq!(find(4003).rank, None);
+        assert_eq!(find(4003).reason, "matches --ignore pattern 2 (^cron[d]?$)");
+        assert_eq!(find(4004).reason, "protected (oom_score_adj=-1000)");
+    }
+
+    /// Process source counting how often the process list is read
+    struct CountingSource {
+        inner: FixtureSource,
+        reads: std::cell::Cell<usize>,
+    }
+
+    impl ProcessSource for CountingSource {
+        fn processes(&self) -> Result<Vec<ProcessInfo>> {
+            self.reads.set(self.reads.get() + 1);
+            self.inner.processes()
+        }
+
+        fn set_oom_score_adj(&self, pid: i32, adj: i32) -> Result<()> {
+            self.inner.set_oom_score_adj(pid, adj)
+        }
+
+        fn is_alive(&self, process: &ProcessInfo) -> bool {
+            self.inner.is_alive(process)
+        }
+
+        fn cgroup_dir(&self, process: &ProcessInfo) -> Option<std::path::PathBuf> {
+            self.inner.cgroup_dir(process)
+        }
+    }
+
+    #[test]
+    fn test_explain_reads_processes_once() {
+        let (_dir, fixture, _service) = fixture_service(
+            Config::default(),
+            &create_test_meminfo(50.0, 50.0),
+            &fixture_processes(),
+        );
+        let config = Config {
+            fair_users: true,
+            ..Config::default()
+        };
+        let source = CountingSource {
+            inner: fixture.clone(),
+            reads: std::cell::Cell::new(0),
+        };
+        let service = DaemonService::with_sources(config, fixture, source);
+
+        assert_eq!(service.explain().unwrap().len(), 2);
+        assert_eq!(service.process_source.reads.get(), 1);
+    }
+
+    #[test]
+    fn test_quotas_and_oom_score_adj_share_one_scan() {
+        use crate::config::UserQuota;
+
+        let (_dir, fixture, _service) = fixture_service(
+            Config::default(),
+            &create_test_meminfo(50.0, 50.0),
+            &fixture_processes(),
+        );
+        let config = Config {
+            user_quotas: vec![UserQuota::parse("uid=1000-1000:100000000").unwrap()],
+            oom_score_adj: vec![(regex::Regex::new("^sshd?$").unwrap(), -900)],
+            dry_run: true,
+            ..Config::default()
+        };
+        let source = CountingSource {
+            inner: fixture.clone(),
+            reads: std::cell::Cell::new(0),
+        };
+        let mut service = DaemonService::with_sources(config, fixture, source);
+
+        service.check_once().unwrap();
+        assert_eq!(service.process_source.reads.get(), 1);
+    }
+
+    #[test]
+    fn test_kill_uses_the_scan_that_ranked_the_victim() {
+        use crate::config::Rule;
+
+        let (_dir, fixture, _service) = fixture_service(
+            Config::default(),
+            &create_test_meminfo(8.0, 8.0),
+            &fixture_processes(),
+        );
+        let config = Config {
+            fair_users: true,
+            rules: vec![Rule::parse("parent=^init$ => score+10", "rule 1").unwrap()],
+            dry_run: true,
+            ..Config::default()
+        };
+        let source = CountingSource {
+            inner: fixture.clone(),
+            reads: std::cell::Cell::new(0),
+        };
+        let mut service = DaemonService::with_sources(config, fixture, source);
+        service.enable_action_log();
+
+        service.check_once().unwrap();
+        let actions = service.take_actions();
+        assert_eq!(actions[0].victim.pid, 4002);
+        assert!(actions[0].reason.contains("candidate"));
+        assert_eq!(service.process_source.reads.get(), 1);
+    }
+
+    #[test]
+    fn test_fair_users_prefers_the_heaviest_user() {
+        // uid 1000 has the highest scoring process, uid 1001 uses more
+        // memory over three notebook kernels
+        let mut processes = fixture_processes();
+        for pid in [5001, 5002, 5003] {
+            let mut kernel = create_test_process(pid, 2_000_000);
+            kernel.uid = 1001;
+            kernel.oom_score = 300 + pid % 10;
+            processes.push(kernel);
+        }
+        let meminfo = create_test_meminfo(50.0, 50.0);
+
+        let (_dir, _fixture, service) = fixture_service(Config::default(), &meminfo, &processes);
+        assert_eq!(service.explain().unwrap()[0].process.pid, 4002);
+
+        let config = Config {
+            fair_users: true,
+            ..Config::default()
+        };
+        let (_dir, _fixture, service) = fixture_service(config, &meminfo, &processes);
+        let explanations = service.explain().unwrap();
+        let ranked: Vec<i32> = explanations.iter().map(|e| e.process.pid).collect();
+        assert_eq!(ranked, [5003, 5002, 5001, 4002, 4001]);
+        assert!(explanations[0]
+            .reason
+            .contains(" ranks 1 of 2 with 5.72 GiB in 3 processes;"));
+
+        // Relative to a larger allowance, uid 1001 is no longer the heaviest
+        let config = Config {
+            fair_users: true,
+            user_allowance: Some(4_000_000),
+            user_allowances: vec![("1001".to_string(), 16_000_000)],
+            ..Config::default()
+        };
+        let (_dir, _fixture, service) = fixture_service(config, &meminfo, &processes);
+        assert_eq!(service.explain().unwrap()[0].process.pid, 4002);
+    }
+
+    #[test]
+    fn test_kill_episode_continues_to_recovery_level() {
+        // Recovery level: warn threshold 10% + margin 30% = 40%
+        let (_dir, fixture, mut service) = fixture_service(
+            Config::default(),
+            &create_test_meminfo(12.0, 12.0),
+            &fixture_processes(),
+        );
+        service.enable_action_log();
+
+        // Memory is already back above the warn threshold after the trigger,
+        // but the episode goes on until the recovery level
+        service
+            .kill_until_recovered(&create_test_meminfo(4.0, 4.0), 30.0, 10)
+            .unwrap();
+        let pids: Vec<i32> = service
+            .take_actions()
+            .iter()
+            .map(|a| a.victim.pid)
+            .collect();
+        assert_eq!(pids, [4002, 4001]);
+
+        fixture
+            .write_meminfo(&create_test_meminfo(45.0, 45.0))
+            .unwrap();
+        service
+            .kill_until_recovered(&create_test_meminfo(4.0, 4.0), 30.0, 10)
+            .unwrap();
+        assert!(service.take_actions().is_empty());
+    }
+
+    #[test]
+    fn test_user_quota_warns_then_kills_within_the_user() {
+        use crate::config::UserQuota;
+
+        // uid 1001 uses 6 GB, over its 4 GB quota, while the system is fine;
+        // its largest process is ignored
+        let mut processes = fixture_processes();
+        for (pid, rss_kb, name) in [(5001, 3_500_000, "sshd"), (5002, 2_500_000, "kernel")] {
+            let mut process = create_test_process(pid, rss_kb);
+            process.uid = 1001;
+            process.oom_score = 100 + pid % 10;
+            process.name = name.to_string();
+            processes.push(process);
+        }
+        let config = Config {
+            user_quotas: vec![UserQuota::parse("uid=1001-1001:4000000").unwrap()],
+            quota_grace: Duration::ZERO,
+            ignore: vec![regex::Regex::new("^sshd?$").unwrap()],
+            ..Config::default()
+        };
+        let (_dir, _fixture, mut service) =
+            fixture_service(config, &create_test_meminfo(50.0, 50.0), &processes);
+        service.enable_action_log();
+
+        // Warned about first
+        service.check_once().unwrap();
+        assert!(service.take_actions().is_empty());
+
+        service.check_once().unwrap();
+        let actions = service.take_actions();
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].victim.pid, 5002);
+        assert_eq!(actions[0].strategy, KillStrategy::Graceful);
+        assert!(actions[0]
+            .reason
+            .contains("(uid 1001) uses 6000000 KiB, over quota uid=1001-1001:4000000; candidate"));
+        // Quota kills do not start the system-wide cooldown
+        assert!(service.last_kill.is_none());
+    }
+
+    #[test]
+    fn test_interactive_penalty_spares_the_active_session() {
+        // The browser runs in the active desktop session
+        let mut processes = fixture_processes();
+        processes[1] = processes[1].clone().with_cgroup(
+            "/user.slice/user-1000.slice/user@1000.service/app.slice/app-browser-1.scope"
+                .to_string(),
+        );
+        let config = Config {
+            foreground_weight: 0.1,
+            active_session_weight: 0.2,
+            ..Config::default()
+        };
+        let (dir, _fixture, service) =
+            fixture_service(config, &create_test_meminfo(50.0, 50.0), &processes);
+        fs::create_dir(dir.path().join("sessions")).unwrap();
+        fs::write(
+            dir.path().join("sessions/2"),
+            "UID=1000\nACTIVE=1\nTYPE=wayland\nSCOPE=session-2.scope\n",
+        )
+        .unwrap();
+
+        let explanations = service.explain().unwrap();
+        assert_eq!(explanations[0].process.pid, 4001);
+        assert_eq!(explanations[1].process.pid, 4002);
+        assert_eq!(
+            explanations[1].reason,
+            "candidate, matches --interactive-penalty active-session=0.2; oom_score 800, adjusted to 160"
+        );
+    }
+
+    #[test]
+    fn test_prefer_young_and_age_filters() {
+        let now = unix_now();
+        let mut processes = fixture_processes();
+        processes[0].oom_score = 500;
+        processes[0] = processes[0].clone().with_start_time(now - 600, now);
+        processes[1] = processes[1].clone().with_start_time(now - 3 * 86400, now);
+        let mut fresh = create_test_process(4003, 2_000_000).with_start_time(now - 5, now);
+        fresh.oom_score = 900;
+        processes.push(fresh);
+        let config = Config {
+            prefer_young: Some(Duration::from_secs(3600)),
+            min_age: Some(Duration::from_secs(30)),
+            ..Config::default()
+        };
+        let (_dir, _fixture, service) =
+            fixture_service(config, &create_test_meminfo(50.0, 50.0), &processes);
+
+        let explanations = service.explain().unwrap();
+        let find = |pid: i32| explanations.iter().find(|e| e.process.pid == pid).unwrap();
+        // 500 x1.86 for a ten minute old process outranks 800 x1.01
+        assert_eq!(find(4001).rank, Some(1));
+        assert!(find(4001)
+            .reason
+            .contains(", x1.86 for age 10m, adjusted to "));
+        assert_eq!(find(4002).rank, Some(2));
+        assert_eq!(find(4003).verdict, Verdict::Ignored);
+        assert!(find(4003).reason.starts_with("younger than --min-age ("));
+
+        let config = Config {
+            max_age: Some(Duration::from_secs(86400)),
+            ..Config::default()
+        };
+        let (_dir, _fixture, service) =
+            fixture_service(config, &create_test_meminfo(50.0, 50.0), &processes);
+        let explanations = service.explain().unwrap();
+        let browser = explanations.iter().find(|e| e.process.pid == 4002).unwrap();
+        assert_eq!(browser.reason, "older than --max-age (3d)");
+    }
+
+    #[test]
+    fn test_oom_score_adj_is_kept_and_restored() {
+        let mut processes = fixture_processes();
+        processes[0].name = "sshd".to_string();
+        let config = Config {
+            oom_score_adj: vec![(regex::Regex::new("^sshd?$").unwrap(), -900)],
+            restore_oom_score_adj: true,
+            ..Config::default()
+        };
+        let (_dir, fixture, mut service) =
+            fixture_service(config, &create_test_meminfo(50.0, 50.0), &processes);
+        let adj_of = |pid: i32| {
+            fixture
+                .processes()
+                .unwrap()
+                .into_iter()
+                .find(|p| p.pid == pid)
+                .unwrap()
+                .oom_score_adj
+        };
+
+        // Dry run only reports the change
+        service.check_once().unwrap();
+        assert_eq!(adj_of(4001), 0);
+        assert_eq!(service.adjusted.len(), 0);
+
+        let config = Config {
+            dry_run: false,
+            ..service.config.clone()
+        };
+        let mut service = DaemonService::with_sources(config, fixture.clone(), fixture.clone());
+        service.check_once().unwrap();
+        assert_eq!(adj_of(4001), -900);
+        assert_eq!(adj_of(4002), 0);
+
+        // A new process is adjusted on the next scan
+        let mut new_sshd = create_test_process(4003, 10_000);
+        new_sshd.name = "sshd".to_string();
+        new_sshd.oom_score_adj = 100;
+        fixture.write_process(&new_sshd).unwrap();
+        service.check_once().unwrap();
+        assert_eq!(adj_of(4003), -900);
+        assert_eq!(service.adjusted.len(), 2);
+
+        service.restore_oom_score_adj();
+        assert_eq!(adj_of(4001), 0);
+        assert_eq!(adj_of(4003), 100);
+    }
+
+    #[test]
+    fn test_kernel_oom_kills_are_reported_as_missed() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("hook.out");
+        let script = dir.path().join("hook.sh");
+        fs::write(
+            &script,
+            format!(
+                "#!/bin/sh\necho \"$OOM_GUARD_EVENT $OOM_GUARD_COUNT $OOM_GUARD_CGROUP\" > {}\n",
+                output.display()
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let cgroup = "/system.slice/db.service";
+        let config = Config {
+            watch_cgroups: vec![cgroup.to_string()],
+            kernel_oom_script: Some(script.display().to_string()),
+            ..Config::default()
+        };
+        let meminfo = create_test_meminfo(50.0, 50.0);
+        let fixture = FixtureSource::new(dir.path().join("proc"));
+        fixture.write_meminfo(&meminfo).unwrap();
+        fixture.write_oom_kills(3).unwrap();
+        fixture.write_cgroup_oom_kills(cgroup, 0).unwrap();
+        let mut service = DaemonService::with_sources(config, fixture.clone(), fixture.clone());
+
+        service.check_once().unwrap();
+        assert_eq!(service.oom_watcher.missed(), 0);
+        assert!(!output.exists());
+
+        fixture.write_oom_kills(4).unwrap();
+        fixture.write_cgroup_oom_kills(cgroup, 1).unwrap();
+        service.check_once().unwrap();
+        assert_eq!(service.oom_watcher.missed(), 1);
+        assert_eq!(
+            fs::read_to_string(&output).unwrap(),
+            "kernel-oom-kill 1 /system.slice/db.service\n"
+        );
+    }
+
+    #[test]
+    fn test_explain_kill_tree_ranks_applications() {
+        let config = Config {
+            kill_tree: true,
+            ..Config::default()
+        };
+        let mut processes = fixture_processes();
+        for pid in [4011, 4012] {
+            let mut renderer = create_test_process(pid, 1_000_000);
+            renderer.ppid = 4002;
+            renderer.oom_score = 300;
+            processes.push(renderer);
+        }
+        let (_dir, _fixture, service) =
+            fixture_service(config, &create_test_meminfo(50.0, 50.0), &processes);
+
+        let explanations = service.explain().unwrap();
+        let find = |pid: i32| explanations.iter().find(|e| e.process.pid == pid).unwrap();
+
+        assert_eq!(find(4002).rank, Some(1));
+        assert_eq!(find(4002).process.rss_kb, 6_000_000);
+        assert_eq!(
+            find(4002).reason,
+            "candidate; oom_score 1400 over 3 processes"
+        );
+        assert_eq!(find(4001).rank, Some(2));
+        assert_eq!(find(4011).verdict, Verdict::Member);
+        assert_eq!(find(4011).reason, "in the tree of PID 4002 (rank 1)");
+    }
+
+    #[test]
+    fn test_kill_tree_avoids_applications_with_avoided_members() {
+        let config = Config {
+            kill_tree: true,
+            avoid: vec![regex::Regex::new("^(renderer|gpu)$").unwrap()],
+            ..Config::default()
+        };
+        let mut processes = fixture_processes();
+        let mut renderer = create_test_process(4011, 1_000_000);
+        renderer.name = "renderer".to_string();
+        renderer.ppid = 4002;
+        processes.push(renderer);
+        let (_dir, _fixture, mut service) =
+            fixture_service(config, &create_test_meminfo(8.0, 8.0), &processes);
+
+        let explanations = service.explain().unwrap();
+        let find = |pid: i32| explanations.iter().find(|e| e.process.pid == pid).unwrap();
+        assert_eq!(find(4001).rank, Some(1));
+        assert_eq!(find(4002).rank, Some(2));
+        assert_eq!(find(4002).verdict, Verdict::Avoided);
+        assert_eq!(
+            find(4002).reason,
+            "avoided, matches --avoid pattern 1 (^(renderer|gpu)$) in its tree (PID 4011); oom_score 900 over 2 processes"
+        );
+
+        service.enable_action_log();
+        service.check_once().unwrap();
+        assert_eq!(service.take_actions()[0].victim.pid, 4001);
+    }
+
+    #[test]
+    fn test_actions_carry_selection_reason() {
+        let config = Config {
+            prefer: vec![regex::Regex::new("^browser$|--tabs").unwrap()],
+            ..Config::default()
+        };
+        let (_dir, _fixture, mut service) =
+            fixture_service(config, &create_test_meminfo(8.0, 8.0), &fixture_processes());
+        service.enable_action_log();
+
+        service.check_once().unwrap();
+        let actions = service.take_actions();
+        assert_eq!(actions.len(), 1);
+        assert_eq!(
+            actions[0].reason,
+            "preferred, matches --prefer pattern 1 (^browser$|--tabs); oom_score 800"
+        );
+    }
+
+    #[test]
+    fn test_rules_reorder_and_ignore_candidates() {
+        use crate::config::Rule;
+
+        let mut processes = fixture_processes();
+        let mut child = create_test_process(4003, 50_000);
+        child.ppid = 4002;
+        child.oom_score = 100;
+        child.name = "renderer".to_string();
+        processes.push(child);
+
+        let config = Config {
+            rules: vec![
+                Rule::parse("parent=^browser$ => score*3 score+250", "rule 1").unwrap(),
+                Rule::parse("name=^worker-[0-9]+$ uid=1000 => ignore", "rule 2").unwrap(),
+            ],
+            ..Config::default()
+        };
+        let (_dir, _fixture, service) =
+            fixture_service(config, &create_test_meminfo(8.0, 8.0), &processes);
+
+        // The renderer's score 100 becomes 550 and ranks between 800 and
+        // the ignored worker
+        let explanations = service.explain().unwrap();
+        let find = |pid: i32| explanations.iter().find(|e| e.process.pid == pid).unwrap();
+        assert_eq!(find(4002).rank, Some(1));
+        assert_eq!(find(4003).rank, Some(2));
+        assert_eq!(
+            find(4003).reason,
+            "candidate, matches rule 1; oom_score 100, adjusted to 550"
+        );
+        assert_eq!(find(4001).rank, None);
+        assert_eq!(find(4001).reason, "matches rule 2");
+
+        // A larger multiplier overtakes the browser itself
+        let config = Config {
+            rules: vec![Rule::parse("parent=^browser$ => score*10", "rule 1").unwrap()],
+            ..Config::default()
+        };
+        let (_dir, _fixture, service) =
+            fixture_service(config, &create_test_meminfo(8.0, 8.0), &processes);
+        assert_eq!(service.select_victim().unwrap().unwrap().process.pid, 4003);
     }
 }
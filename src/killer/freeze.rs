@@ -0,0 +1,168 @@
+// Suspending processes instead of killing them
+
+use super::reclaim::{read_memory_current, reclaim_cgroup};
+use crate::monitor::cgroup;
+use anyhow::{Context, Result};
+use nix::sys::signal::{self, killpg, Signal};
+use nix::unistd::{getpgid, Pid};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How a victim was frozen, needed to thaw it again
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FreezeTarget {
+    /// Frozen through cgroup.freeze of its systemd unit cgroup
+    Cgroup(PathBuf),
+    /// Stopped with SIGSTOP sent to its process group
+    ProcessGroup(i32),
+    /// Stopped with SIGSTOP sent to the process only
+    Process(i32),
+}
+
+impl FreezeTarget {
+    /// Resume the frozen victim
+    pub fn thaw(&self) -> Result<()> {
+        match self {
+            Self::Cgroup(dir) => fs::write(dir.join("cgroup.freeze"), "0")
+                .with_context(|| format!("Failed to thaw cgroup {}", dir.display())),
+            Self::ProcessGroup(pgid) => killpg(Pid::from_raw(*pgid), Signal::SIGCONT)
+                .with_context(|| format!("Failed to send SIGCONT to process group {pgid}")),
+            Self::Process(pid) => signal::kill(Pid::from_raw(*pid), Signal::SIGCONT)
+                .with_context(|| format!("Failed to send SIGCONT to process {pid}")),
+        }
+    }
+
+    /// Push the frozen victim's memory out to swap
+    ///
+    /// Only possible for cgroup-frozen victims (requires memory.reclaim,
+    /// Linux 5.19+). Returns the number of bytes reclaimed.
+    pub fn swap_out(&self) -> Option<u64> {
+        let Self::Cgroup(dir) = self else {
+            return None;
+        };
+
+        let current = read_memory_current(dir)?;
+        reclaim_cgroup(dir, current)
+    }
+}
+
+impl std::fmt::Display for FreezeTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Cgroup(dir) => write!(f, "cgroup {}", dir.display()),
+            Self::ProcessGroup(pgid) => write!(f, "process group {pgid}"),
+            Self::Process(pid) => write!(f, "process {pid}"),
+        }
+    }
+}
+
+/// Whether a cgroup can be frozen on its own: a systemd unit cgroup
+/// with cgroup.freeze
+pub fn is_freezable(dir: &Path) -> bool {
+    dir.file_name()
+        .and_then(|leaf| leaf.to_str())
+        .is_some_and(cgroup::is_unit_name)
+        && dir.join("cgroup.freeze").exists()
+}
+
+/// Freeze a process: through cgroup.freeze of `cgroup` when given,
+/// otherwise by sending SIGSTOP to the process (or its process group)
+pub fn freeze_process(pid: i32, kill_group: bool, cgroup: Option<&Path>) -> Result<FreezeTarget> {
+    if let Some(dir) = cgroup {
+        match fs::write(dir.join("cgroup.freeze"), "1") {
+            Ok(()) => {
+                log::info!("Froze cgroup {} (pid {pid})", dir.display());
+                return Ok(FreezeTarget::Cgroup(dir.to_path_buf()));
+            }
+            Err(e) => log::warn!(
+                "Failed to freeze cgroup {}: {e}. Falling back to SIGSTOP.",
+                dir.display()
+            ),
+        }
+    }
+
+    let nix_pid = Pid::from_raw(pid);
+    if kill_group {
+        if let Ok(pgid) = getpgid(Some(nix_pid)) {
+            killpg(pgid, Signal::SIGSTOP)
+                .with_context(|| format!("Failed to send SIGSTOP to process group {pgid}"))?;
+            log::info!("Stopped process group {pgid} (leader pid {pid})");
+            return Ok(FreezeTarget::ProcessGroup(pgid.as_raw()));
+        }
+    }
+
+    signal::kill(nix_pid, Signal::SIGSTOP)
+        .with_context(|| format!("Failed to send SIGSTOP to process {pid}"))?;
+    log::info!("Stopped process {pid}");
+    Ok(FreezeTarget::Process(pid))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_freeze_target_display() {
+        assert_eq!(
+            FreezeTarget::Cgroup(PathBuf::from("/sys/fs/cgroup/app.scope")).to_string(),
+            "cgroup /sys/fs/cgroup/app.scope"
+        );
+        assert_eq!(
+            FreezeTarget::ProcessGroup(42).to_string(),
+            "process group 42"
+        );
+        assert_eq!(FreezeTarget::Process(7).to_string(), "process 7");
+    }
+
+    #[test]
+    fn test_is_freezable() {
+        let tmp = tempfile::tempdir().unwrap();
+        let unit = tmp.path().join("app.scope");
+        let plain = tmp.path().join("workers");
+        for dir in [&unit, &plain] {
+            fs::create_dir(dir).unwrap();
+            fs::write(dir.join("cgroup.freeze"), "0\n").unwrap();
+        }
+
+        assert!(is_freezable(&unit));
+        assert!(!is_freezable(&plain));
+        assert!(!is_freezable(&tmp.path().join("gone.service")));
+    }
+
+    #[test]
+    fn test_swap_out_requires_cgroup() {
+        assert_eq!(FreezeTarget::Process(7).swap_out(), None);
+    }
+
+    fn process_state(pid: i32) -> char {
+        procfs::process::Process::new(pid)
+            .and_then(|p| p.stat())
+            .map_or('?', |stat| stat.state)
+    }
+
+    #[test]
+    fn test_freeze_and_thaw_with_sigstop() {
+        let mut child = std::process::Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .unwrap();
+        let pid = child.id() as i32;
+
+        let target = freeze_process(pid, false, None).unwrap();
+        assert_eq!(target, FreezeTarget::Process(pid));
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert_eq!(process_state(pid), 'T');
+
+        target.thaw().unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert_ne!(process_state(pid), 'T');
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+
+    #[test]
+    fn test_freeze_nonexistent_process() {
+        assert!(freeze_process(999_999, false, None).is_err());
+    }
+}
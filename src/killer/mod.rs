@@ -1,10 +1,19 @@
 // Process killer module
 
+pub mod freeze;
+pub mod reclaim;
 mod selector;
 pub mod signals;
+pub mod throttle;
 
+use crate::monitor::ProcessInfo;
+
+pub use freeze::{freeze_process, FreezeTarget};
 pub use selector::ProcessSelector;
-pub use signals::{kill_process, KillResult, KillStrategy};
+pub use signals::{
+    kill_process, kill_process_with, kill_tree, KillResult, KillScope, KillStrategy,
+};
+pub use throttle::{throttle_process, ThrottleTarget};
 
 /// Information about a killed process
 #[derive(Debug, Clone)]
@@ -12,35 +21,81 @@ pub struct KillInfo {
     pub pid: i32,
     pub name: String,
     pub cmdline: String,
+    /// systemd service or scope the process ran in, empty if none
+    pub unit: String,
+    /// Container ID, runtime, pod UID and QoS class, empty outside
+    /// containers and pods
+    pub container: String,
+    pub runtime: String,
+    pub pod: String,
+    pub qos: String,
+    /// What was killed, see [`ProcessInfo::label`]
+    pub label: String,
     pub uid: u32,
     pub rss_kb: u64,
     pub oom_score: i32,
+    /// Seconds since the process started and its start time (Unix
+    /// seconds), None when unknown
+    pub age_secs: Option<u64>,
+    pub start_time: Option<u64>,
     pub strategy: KillStrategy,
+    /// Why the process was selected
+    pub reason: String,
     pub result: String,
 }
 
 impl KillInfo {
-    /// Create a new KillInfo
-    #[allow(clippy::too_many_arguments)]
+    /// Create a new KillInfo for a victim
     pub fn new(
-        pid: i32,
-        name: String,
-        cmdline: String,
-        uid: u32,
-        rss_kb: u64,
-        oom_score: i32,
+        victim: &ProcessInfo,
         strategy: KillStrategy,
+        reason: String,
         result: &KillResult,
     ) -> Self {
         Self {
-            pid,
-            name,
-            cmdline,
-            uid,
-            rss_kb,
-            oom_score,
+            pid: victim.pid,
+            name: victim.name.clone(),
+            cmdline: victim.cmdline.clone(),
+            unit: victim.unit.clone(),
+            container: victim.container.clone(),
+            runtime: victim.runtime.clone(),
+            pod: victim.pod.clone(),
+            qos: victim.qos.clone(),
+            label: victim.label(),
+            uid: victim.uid,
+            rss_kb: victim.rss_kb,
+            oom_score: victim.oom_score,
+            age_secs: victim.age(),
+            start_time: victim.age().map(|_| victim.start_time),
             strategy,
+            reason,
             result: result.description().to_string(),
         }
     }
+
+    /// The OOM_GUARD_* variables hook scripts receive for this kill
+    pub fn env_vars(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("OOM_GUARD_PID", self.pid.to_string()),
+            ("OOM_GUARD_NAME", self.name.clone()),
+            ("OOM_GUARD_CMDLINE", self.cmdline.clone()),
+            ("OOM_GUARD_UNIT", self.unit.clone()),
+            ("OOM_GUARD_CONTAINER", self.container.clone()),
+            ("OOM_GUARD_RUNTIME", self.runtime.clone()),
+            ("OOM_GUARD_POD", self.pod.clone()),
+            ("OOM_GUARD_QOS", self.qos.clone()),
+            ("OOM_GUARD_UID", self.uid.to_string()),
+            ("OOM_GUARD_RSS", self.rss_kb.to_string()),
+            ("OOM_GUARD_SCORE", self.oom_score.to_string()),
+            (
+                "OOM_GUARD_AGE",
+                self.age_secs.map(|age| age.to_string()).unwrap_or_default(),
+            ),
+            (
+                "OOM_GUARD_START_TIME",
+                self.start_time.map(|t| t.to_string()).unwrap_or_default(),
+            ),
+            ("OOM_GUARD_REASON", self.reason.clone()),
+        ]
+    }
 }
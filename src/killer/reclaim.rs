@@ -0,0 +1,152 @@
+// Non-destructive memory reclaim: memory.reclaim and MADV_PAGEOUT
+
+use super::signals::syscall_numbers::SYS_PROCESS_MADVISE;
+use super::signals::{close_fd, try_pidfd_open};
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::Path;
+
+/// madvise advice to reclaim pages (Linux 5.4+)
+const MADV_PAGEOUT: i32 = 21;
+
+/// Maximum number of iovecs per process_madvise call (UIO_MAXIOV)
+const IOV_MAX: usize = 1024;
+
+/// Read memory.current (bytes) of a cgroup
+pub fn read_memory_current(dir: &Path) -> Option<u64> {
+    fs::read_to_string(dir.join("memory.current"))
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+}
+
+/// Ask the kernel to reclaim up to `bytes` from a cgroup (Linux 5.19+)
+///
+/// Returns the number of bytes by which the cgroup's usage dropped.
+pub fn reclaim_cgroup(dir: &Path, bytes: u64) -> Option<u64> {
+    let before = read_memory_current(dir)?;
+    if let Err(e) = fs::write(dir.join("memory.reclaim"), bytes.min(before).to_string()) {
+        // EAGAIN just means the kernel could not reclaim the full amount
+        log::debug!("memory.reclaim on {} stopped early: {e}", dir.display());
+    }
+    let after = read_memory_current(dir)?;
+
+    Some(before.saturating_sub(after))
+}
+
+/// Whether a cgroup supports memory.reclaim (Linux 5.19+)
+pub fn is_reclaimable(dir: &Path) -> bool {
+    dir.join("memory.reclaim").exists()
+}
+
+/// Parse the private mappings of /proc/[pid]/maps into (start, length) ranges
+fn private_ranges(maps: &str) -> Vec<(usize, usize)> {
+    maps.lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let range = fields.next()?;
+            let perms = fields.next()?;
+            let path = fields.nth(3).unwrap_or("");
+
+            // Skip shared mappings and kernel-provided special regions
+            if !perms.ends_with('p') || matches!(path, "[vsyscall]" | "[vdso]" | "[vvar]") {
+                return None;
+            }
+
+            let (start, end) = range.split_once('-')?;
+            let start = usize::from_str_radix(start, 16).ok()?;
+            let end = usize::from_str_radix(end, 16).ok()?;
+            (end > start).then_some((start, end - start))
+        })
+        .collect()
+}
+
+/// Page out the private memory of a process with process_madvise(MADV_PAGEOUT)
+///
+/// Requires Linux 5.10+ and, for other users' processes, CAP_SYS_NICE.
+#[cfg(target_os = "linux")]
+pub fn pageout_process(pid: i32) -> Result<()> {
+    if SYS_PROCESS_MADVISE < 0 {
+        bail!("process_madvise is not supported on this architecture");
+    }
+
+    let maps = fs::read_to_string(format!("/proc/{pid}/maps"))
+        .with_context(|| format!("Failed to read memory map of process {pid}"))?;
+    let ranges = private_ranges(&maps);
+
+    let Some(pidfd) = try_pidfd_open(pid) else {
+        bail!("pidfd_open({pid}) failed");
+    };
+
+    let mut result = Ok(());
+    for chunk in ranges.chunks(IOV_MAX) {
+        let iovecs: Vec<libc::iovec> = chunk
+            .iter()
+            .map(|&(start, len)| libc::iovec {
+                iov_base: start as *mut libc::c_void,
+                iov_len: len,
+            })
+            .collect();
+
+        // SAFETY: process_madvise only reads the iovec array, which is valid
+        // for iovecs.len() entries for the duration of the call. The ranges
+        // describe memory of the target process, not of this process.
+        #[allow(unsafe_code)]
+        let ret = unsafe {
+            libc::syscall(
+                SYS_PROCESS_MADVISE,
+                pidfd,
+                iovecs.as_ptr(),
+                iovecs.len(),
+                MADV_PAGEOUT,
+                0,
+            )
+        };
+
+        if ret < 0 {
+            result = Err(std::io::Error::last_os_error())
+                .with_context(|| format!("process_madvise(MADV_PAGEOUT) on process {pid} failed"));
+            break;
+        }
+    }
+
+    close_fd(pidfd);
+    result
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn pageout_process(_pid: i32) -> Result<()> {
+    bail!("process_madvise is only available on Linux")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_private_ranges() {
+        let maps = "\
+55d4c2a00000-55d4c2a21000 r--p 00000000 08:01 1234 /usr/bin/app
+7f0000000000-7f0000100000 rw-p 00000000 00:00 0
+7f0000100000-7f0000200000 rw-s 00000000 00:05 42 /dev/shm/buf
+7ffd1c9f0000-7ffd1c9f4000 r--p 00000000 00:00 0 [vvar]
+7ffd1c9f4000-7ffd1c9f6000 r-xp 00000000 00:00 0 [vdso]
+ffffffffff600000-ffffffffff601000 --xp 00000000 00:00 0 [vsyscall]
+";
+        let ranges = private_ranges(maps);
+
+        assert_eq!(
+            ranges,
+            vec![(0x55d4_c2a0_0000, 0x21000), (0x7f00_0000_0000, 0x10_0000)]
+        );
+    }
+
+    #[test]
+    fn test_private_ranges_ignores_garbage() {
+        assert!(private_ranges("not a maps line\n\n").is_empty());
+    }
+
+    #[test]
+    fn test_pageout_nonexistent_process() {
+        assert!(pageout_process(999_999).is_err());
+    }
+}
@@ -143,7 +143,7 @@ impl ProcessSelector {
             .collect();
 
         // Sort by score (descending - highest score first)
-        scored.sort_by(|a, b| b.1.cmp(&a.1));
+        scored.sort_by_key(|(_, score)| std::cmp::Reverse(*score));
 
         // Log top candidates
         if log::log_enabled!(log::Level::Debug) {
@@ -241,6 +241,7 @@ mod tests {
             uid: 1000,
             state: 'S',
             is_zombie: false,
+            ..Default::default()
         }
     }
 
@@ -262,6 +263,7 @@ mod tests {
             uid: 1000,
             state: 'S',
             is_zombie: false,
+            ..Default::default()
         }
     }
 
@@ -276,6 +278,7 @@ mod tests {
             uid: 1000,
             state: 'Z',
             is_zombie: true,
+            ..Default::default()
         }
     }
 
@@ -287,7 +290,7 @@ mod tests {
         let kernel_thread = create_test_process(2, "kthreadd", "[kthreadd]", 0, 0);
         assert!(selector.is_kernel_thread(&kernel_thread));
 
-        let user_process = create_test_process(1234, "firefox", "/usr/bin/firefox", 1000000, 100);
+        let user_process = create_test_process(1234, "firefox", "/usr/bin/firefox", 1_000_000, 100);
         assert!(!selector.is_kernel_thread(&user_process));
     }
 
@@ -307,10 +310,10 @@ mod tests {
 
         let selector = ProcessSelector::new(config);
 
-        let firefox = create_test_process(1234, "firefox", "/usr/bin/firefox", 1000000, 100);
+        let firefox = create_test_process(1234, "firefox", "/usr/bin/firefox", 1_000_000, 100);
         assert!(!selector.is_killable(&firefox));
 
-        let chrome = create_test_process(1235, "chrome", "/usr/bin/chrome", 1000000, 100);
+        let chrome = create_test_process(1235, "chrome", "/usr/bin/chrome", 1_000_000, 100);
         assert!(selector.is_killable(&chrome));
     }
 
@@ -321,10 +324,10 @@ mod tests {
 
         let selector = ProcessSelector::new(config);
 
-        let chrome = create_test_process(1234, "chrome", "/usr/bin/chrome", 100000, 10);
-        let firefox = create_test_process(1235, "firefox", "/usr/bin/firefox", 200000, 20);
+        let chrome = create_test_process(1234, "chrome", "/usr/bin/chrome", 100_000, 10);
+        let firefox = create_test_process(1235, "firefox", "/usr/bin/firefox", 200_000, 20);
 
-        let candidates = vec![chrome.clone(), firefox.clone()];
+        let candidates = vec![chrome, firefox];
         let victim = selector.select_best_victim(candidates);
 
         assert!(victim.is_some());
@@ -340,11 +343,16 @@ mod tests {
 
         let selector = ProcessSelector::new(config);
 
-        let important =
-            create_test_process(1234, "important-app", "/usr/bin/important-app", 500000, 100);
-        let regular = create_test_process(1235, "regular-app", "/usr/bin/regular-app", 100000, 50);
+        let important = create_test_process(
+            1234,
+            "important-app",
+            "/usr/bin/important-app",
+            500_000,
+            100,
+        );
+        let regular = create_test_process(1235, "regular-app", "/usr/bin/regular-app", 100_000, 50);
 
-        let candidates = vec![important.clone(), regular.clone()];
+        let candidates = vec![important, regular];
         let victim = selector.select_best_victim(candidates);
 
         assert!(victim.is_some());
@@ -355,15 +363,17 @@ mod tests {
 
     #[test]
     fn test_sort_by_rss() {
-        let mut config = Config::default();
-        config.sort_by_rss = true;
+        let config = Config {
+            sort_by_rss: true,
+            ..Config::default()
+        };
 
         let selector = ProcessSelector::new(config);
 
         let small = create_test_process(1234, "small", "/usr/bin/small", 10000, 100);
-        let large = create_test_process(1235, "large", "/usr/bin/large", 1000000, 10);
+        let large = create_test_process(1235, "large", "/usr/bin/large", 1_000_000, 10);
 
-        let candidates = vec![small.clone(), large.clone()];
+        let candidates = vec![small, large];
         let victim = selector.select_best_victim(candidates);
 
         assert!(victim.is_some());
@@ -373,18 +383,20 @@ mod tests {
 
     #[test]
     fn test_root_user_filter() {
-        let mut config = Config::default();
-        config.ignore_root_user = true;
+        let config = Config {
+            ignore_root_user: true,
+            ..Config::default()
+        };
 
         let selector = ProcessSelector::new(config);
 
         let mut root_process =
-            create_test_process(1234, "root-daemon", "/usr/sbin/daemon", 100000, 50);
+            create_test_process(1234, "root-daemon", "/usr/sbin/daemon", 100_000, 50);
         root_process.uid = 0;
 
         assert!(!selector.is_killable(&root_process));
 
-        let user_process = create_test_process(1235, "user-app", "/usr/bin/app", 100000, 50);
+        let user_process = create_test_process(1235, "user-app", "/usr/bin/app", 100_000, 50);
         assert!(selector.is_killable(&user_process));
     }
 
@@ -400,11 +412,11 @@ mod tests {
 
         // Process with oom_score_adj=0 should be killable
         let normal =
-            create_test_process_with_adj(1235, "firefox", "/usr/bin/firefox", 500000, 100, 0);
+            create_test_process_with_adj(1235, "firefox", "/usr/bin/firefox", 500_000, 100, 0);
         assert!(selector.is_killable(&normal));
 
         // Process with oom_score_adj=-500 should still be killable (only -1000 is protected)
-        let adjusted = create_test_process_with_adj(1236, "app", "/usr/bin/app", 100000, 50, -500);
+        let adjusted = create_test_process_with_adj(1236, "app", "/usr/bin/app", 100_000, 50, -500);
         assert!(selector.is_killable(&adjusted));
     }
 
@@ -418,7 +430,7 @@ mod tests {
         assert!(!selector.is_killable(&zombie));
 
         // Normal process should be killable
-        let normal = create_test_process(1235, "firefox", "/usr/bin/firefox", 500000, 100);
+        let normal = create_test_process(1235, "firefox", "/usr/bin/firefox", 500_000, 100);
         assert!(selector.is_killable(&normal));
     }
 }
@@ -1,33 +1,42 @@
 // Signal management for process termination
 
-use anyhow::Result;
+use crate::monitor::cgroup;
+use anyhow::{anyhow, Result};
 use nix::sys::signal::{self, killpg, Signal};
 use nix::unistd::{getpgid, Pid};
+use std::fs;
+use std::path::PathBuf;
 use std::thread;
 use std::time::Duration;
 
-// Syscall numbers for pidfd_open and process_mrelease
-// These vary by architecture
+// Syscall numbers for pidfd_send_signal, pidfd_open, process_madvise and
+// process_mrelease. These vary by architecture
 #[cfg(target_arch = "x86_64")]
-mod syscall_numbers {
+pub(super) mod syscall_numbers {
+    pub const SYS_PIDFD_SEND_SIGNAL: i64 = 424;
     pub const SYS_PIDFD_OPEN: i64 = 434;
+    pub const SYS_PROCESS_MADVISE: i64 = 440;
     pub const SYS_PROCESS_MRELEASE: i64 = 448;
 }
 
 #[cfg(target_arch = "aarch64")]
-mod syscall_numbers {
+pub(super) mod syscall_numbers {
+    pub const SYS_PIDFD_SEND_SIGNAL: i64 = 424;
     pub const SYS_PIDFD_OPEN: i64 = 438;
+    pub const SYS_PROCESS_MADVISE: i64 = 440;
     pub const SYS_PROCESS_MRELEASE: i64 = 452;
 }
 
 #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
-mod syscall_numbers {
+pub(super) mod syscall_numbers {
     // Fallback - these syscalls won't work but we fail gracefully
+    pub const SYS_PIDFD_SEND_SIGNAL: i64 = -1;
     pub const SYS_PIDFD_OPEN: i64 = -1;
+    pub const SYS_PROCESS_MADVISE: i64 = -1;
     pub const SYS_PROCESS_MRELEASE: i64 = -1;
 }
 
-use syscall_numbers::{SYS_PIDFD_OPEN, SYS_PROCESS_MRELEASE};
+use syscall_numbers::{SYS_PIDFD_OPEN, SYS_PIDFD_SEND_SIGNAL, SYS_PROCESS_MRELEASE};
 
 /// PIDFD_NONBLOCK flag for pidfd_open (0x800 = O_NONBLOCK)
 const PIDFD_NONBLOCK: u32 = 0x800;
@@ -35,7 +44,7 @@ const PIDFD_NONBLOCK: u32 = 0x800;
 /// Try to open a pidfd for the process (Linux 5.3+)
 /// Returns None if the syscall is not available or fails
 #[cfg(target_os = "linux")]
-fn try_pidfd_open(pid: i32) -> Option<i32> {
+pub(super) fn try_pidfd_open(pid: i32) -> Option<i32> {
     if SYS_PIDFD_OPEN < 0 {
         return None;
     }
@@ -59,7 +68,7 @@ fn try_pidfd_open(pid: i32) -> Option<i32> {
 }
 
 #[cfg(not(target_os = "linux"))]
-fn try_pidfd_open(_pid: i32) -> Option<i32> {
+pub(super) fn try_pidfd_open(_pid: i32) -> Option<i32> {
     None
 }
 
@@ -96,9 +105,42 @@ fn try_process_mrelease(_pidfd: i32) {
     // No-op on non-Linux systems
 }
 
+/// Send a signal (None to only check for existence) through a pidfd
+/// (Linux 5.1+)
+#[cfg(target_os = "linux")]
+fn pidfd_send_signal(pidfd: i32, signal: Option<Signal>) -> nix::Result<()> {
+    if SYS_PIDFD_SEND_SIGNAL < 0 {
+        return Err(nix::errno::Errno::ENOSYS);
+    }
+
+    let signal = signal.map_or(0, |s| s as libc::c_int);
+    // SAFETY: pidfd_send_signal takes a pidfd, a signal number, an optional
+    // siginfo (NULL here) and flags (0). No memory of ours is written.
+    #[allow(unsafe_code)]
+    let result = unsafe {
+        libc::syscall(
+            SYS_PIDFD_SEND_SIGNAL,
+            pidfd,
+            signal,
+            std::ptr::null::<libc::siginfo_t>(),
+            0,
+        )
+    };
+    if result < 0 {
+        Err(nix::errno::Errno::last())
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn pidfd_send_signal(_pidfd: i32, _signal: Option<Signal>) -> nix::Result<()> {
+    Err(nix::errno::Errno::ENOSYS)
+}
+
 /// Close a file descriptor safely
 #[cfg(target_os = "linux")]
-fn close_fd(fd: i32) {
+pub(super) fn close_fd(fd: i32) {
     // SAFETY: close is a standard POSIX function that closes a file descriptor.
     // We only call this with valid file descriptors obtained from pidfd_open.
     #[allow(unsafe_code)]
@@ -108,7 +150,7 @@ fn close_fd(fd: i32) {
 }
 
 #[cfg(not(target_os = "linux"))]
-fn close_fd(_fd: i32) {
+pub(super) fn close_fd(_fd: i32) {
     // No-op on non-Linux systems
 }
 
@@ -119,8 +161,80 @@ pub enum KillStrategy {
     Graceful,
     /// Send SIGKILL immediately (forceful termination)
     Forceful,
+    /// Suspend the process (cgroup.freeze or SIGSTOP) instead of killing it
+    Freeze,
+    /// Lower memory.high of the process's cgroup so the kernel throttles it
+    Throttle,
+}
+
+impl KillStrategy {
+    /// Whether this strategy ends the process (as opposed to freezing or
+    /// throttling it, which can be undone)
+    pub const fn terminates(self) -> bool {
+        matches!(self, Self::Graceful | Self::Forceful)
+    }
+}
+
+impl std::fmt::Display for KillStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Graceful => "SIGTERM",
+            Self::Forceful => "SIGKILL",
+            Self::Freeze => "freeze",
+            Self::Throttle => "throttle",
+        })
+    }
+}
+
+/// What kill signals are delivered to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KillScope {
+    /// The victim process only
+    Process,
+    /// The victim's process group
+    Group,
+    /// Every process in the victim's systemd unit cgroup
+    Cgroup,
+}
+
+impl std::str::FromStr for KillScope {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "process" => Ok(Self::Process),
+            "group" => Ok(Self::Group),
+            "cgroup" => Ok(Self::Cgroup),
+            _ => anyhow::bail!("Invalid kill scope '{s}' (expected process, group or cgroup)"),
+        }
+    }
+}
+
+impl std::fmt::Display for KillScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Process => "process",
+            Self::Group => "group",
+            Self::Cgroup => "cgroup",
+        })
+    }
+}
+
+/// Parse a signal name such as "SIGTERM", "TERM" or "int"
+pub fn parse_signal(name: &str) -> Result<Signal> {
+    let upper = name.to_ascii_uppercase();
+    let full = if upper.starts_with("SIG") {
+        upper
+    } else {
+        format!("SIG{upper}")
+    };
+    full.parse()
+        .map_err(|_| anyhow::anyhow!("Unknown signal '{name}'"))
 }
 
+/// Default signals of a graceful kill
+const GRACEFUL_LADDER: [Signal; 2] = [Signal::SIGTERM, Signal::SIGKILL];
+
 /// Result of a kill operation
 #[derive(Debug)]
 pub enum KillResult {
@@ -173,7 +287,7 @@ fn send_signal(pid: i32, signal: Signal) -> Result<KillResult> {
 }
 
 /// Check if a process is still alive
-fn is_process_alive(pid: i32) -> bool {
+pub fn is_process_alive(pid: i32) -> bool {
     let nix_pid = Pid::from_raw(pid);
     // Send signal 0 to check if process exists without actually sending a signal
     signal::kill(nix_pid, None).is_ok()
@@ -188,12 +302,31 @@ fn is_process_alive(pid: i32) -> bool {
 ///
 /// # Returns
 /// Result containing the KillResult enum describing the outcome
+pub fn kill_process(pid: i32, strategy: KillStrategy, kill_group: bool) -> Result<KillResult> {
+    let scope = if kill_group {
+        KillScope::Group
+    } else {
+        KillScope::Process
+    };
+    kill_process_with(pid, strategy, scope, None)
+}
+
+/// Kill a process with a custom scope and signal ladder
+///
+/// A graceful kill sends the signals of `ladder` (default SIGTERM, SIGKILL)
+/// in order, moving to the next one when the process is still alive after
+/// a second; a forceful kill sends only the last signal of the ladder.
 ///
 /// This function uses modern Linux kernel features when available:
 /// - `pidfd_open()` (Linux 5.3+) for race-free process tracking
 /// - `process_mrelease()` (Linux 5.14+) for faster memory reclamation
-pub fn kill_process(pid: i32, strategy: KillStrategy, kill_group: bool) -> Result<KillResult> {
-    log::debug!("Attempting to kill process {pid} (strategy: {strategy:?}, group: {kill_group})");
+pub fn kill_process_with(
+    pid: i32,
+    strategy: KillStrategy,
+    scope: KillScope,
+    ladder: Option<&[Signal]>,
+) -> Result<KillResult> {
+    log::debug!("Attempting to kill process {pid} (strategy: {strategy:?}, scope: {scope})");
 
     // Try to get pidfd for safer process tracking (Linux 5.3+)
     // This prevents race conditions where the PID might be reused
@@ -212,15 +345,25 @@ pub fn kill_process(pid: i32, strategy: KillStrategy, kill_group: bool) -> Resul
         return Ok(KillResult::AlreadyDead);
     }
 
+    let ladder = ladder.filter(|l| !l.is_empty()).unwrap_or(&GRACEFUL_LADDER);
     let result = match strategy {
-        KillStrategy::Graceful => kill_graceful(pid, kill_group),
-        KillStrategy::Forceful => kill_forceful(pid, kill_group),
+        KillStrategy::Graceful => send_ladder(pid, ladder, scope),
+        KillStrategy::Forceful => send_ladder(pid, &ladder[ladder.len() - 1..], scope),
+        // A frozen victim has to be thawed later, which needs the
+        // FreezeTarget that only freeze_process returns
+        KillStrategy::Freeze => Err(anyhow!(
+            "Cannot freeze process {pid} here, use freeze_process"
+        )),
+        // Likewise, restoring memory.high needs the ThrottleTarget
+        KillStrategy::Throttle => Err(anyhow!(
+            "Cannot throttle process {pid} here, use throttle_process"
+        )),
     };
 
     // After kill attempt, try to release memory faster using process_mrelease (Linux 5.14+)
     // This syscall helps the kernel reclaim memory pages more quickly
     if let Some(fd) = pidfd {
-        if result.as_ref().is_ok_and(KillResult::is_success) {
+        if strategy.terminates() && result.as_ref().is_ok_and(KillResult::is_success) {
             try_process_mrelease(fd);
         }
         close_fd(fd);
@@ -258,66 +401,250 @@ fn send_signal_to_target(pid: i32, signal: Signal, kill_group: bool) -> Result<K
     }
 }
 
-/// Kill a process gracefully using SIGTERM
-fn kill_graceful(pid: i32, kill_group: bool) -> Result<KillResult> {
-    log::info!("Sending SIGTERM to process {pid} (group: {kill_group})");
+/// Pick the cgroup to kill for a process: its own systemd unit cgroup, as
+/// long as that is not the cgroup the daemon itself runs in
+fn killable_cgroup(pid: i32) -> Option<PathBuf> {
+    let path = cgroup::read_cgroup_path(pid)?;
+    let leaf = path.rsplit('/').next()?;
+    if !cgroup::is_unit_name(leaf) {
+        return None;
+    }
+    if cgroup::read_cgroup_path(std::process::id() as i32).as_deref() == Some(path.as_str()) {
+        return None;
+    }
+
+    let dir = cgroup::cgroup_dir(&path);
+    dir.join("cgroup.procs").exists().then_some(dir)
+}
 
-    let result = send_signal_to_target(pid, Signal::SIGTERM, kill_group)?;
+/// Send a signal to every process of the victim's unit cgroup, falling back
+/// to the process alone when it is not in a unit of its own
+fn send_signal_to_cgroup(pid: i32, signal: Signal) -> Result<KillResult> {
+    let Some(dir) = killable_cgroup(pid) else {
+        log::warn!("Process {pid} is not in a unit cgroup of its own, signalling the process only");
+        return send_signal(pid, signal);
+    };
 
-    if !result.is_success() {
-        log::warn!(
-            "Failed to send SIGTERM to process {}: {}",
-            pid,
-            result.description()
-        );
-        return Ok(result);
+    // cgroup.kill (Linux 5.14+) kills the whole cgroup atomically
+    if signal == Signal::SIGKILL && fs::write(dir.join("cgroup.kill"), "1").is_ok() {
+        log::debug!("Killed cgroup {}", dir.display());
+        return Ok(KillResult::Success);
     }
 
-    // Wait briefly to see if process terminates gracefully
-    for i in 0..10 {
-        thread::sleep(Duration::from_millis(100));
-        if !is_process_alive(pid) {
-            log::info!("Process {} terminated gracefully after {}ms", pid, i * 100);
-            return Ok(KillResult::Success);
+    let procs = fs::read_to_string(dir.join("cgroup.procs"))?;
+    log::debug!("Sending {signal} to cgroup {}", dir.display());
+    let mut result = KillResult::NotFound;
+    for member in procs
+        .lines()
+        .filter_map(|line| line.trim().parse::<i32>().ok())
+    {
+        let sent = send_signal(member, signal)?;
+        if member == pid || !result.is_success() {
+            result = sent;
         }
     }
+    Ok(result)
+}
 
-    // Process didn't die after SIGTERM, escalate to SIGKILL
-    log::warn!("Process {pid} did not respond to SIGTERM, escalating to SIGKILL");
-    kill_forceful(pid, kill_group)
+/// Send a signal to the process, its group or its cgroup
+fn send_signal_to_scope(pid: i32, signal: Signal, scope: KillScope) -> Result<KillResult> {
+    match scope {
+        KillScope::Process => send_signal(pid, signal),
+        KillScope::Group => send_signal_to_target(pid, signal, true),
+        KillScope::Cgroup => send_signal_to_cgroup(pid, signal),
+    }
+}
+
+/// Wait up to `attempts` x `step` for `gone` to hold
+fn wait_for_exit(gone: impl Fn() -> bool, attempts: u32, step: Duration) -> Option<Duration> {
+    (1..=attempts).find_map(|i| {
+        thread::sleep(step);
+        gone().then_some(step * i)
+    })
 }
 
-/// Kill a process forcefully using SIGKILL
-fn kill_forceful(pid: i32, kill_group: bool) -> Result<KillResult> {
-    log::info!("Sending SIGKILL to process {pid} (group: {kill_group})");
+/// Send each signal of the ladder in turn until the process exits
+fn send_ladder(pid: i32, ladder: &[Signal], scope: KillScope) -> Result<KillResult> {
+    run_ladder(
+        &format!("process {pid}"),
+        ladder,
+        |signal| {
+            log::info!("Sending {signal} to process {pid} (scope: {scope})");
+            send_signal_to_scope(pid, signal, scope)
+        },
+        || !is_process_alive(pid),
+    )
+}
+
+/// Send each signal of the ladder with `send` until `gone` holds
+fn run_ladder(
+    target: &str,
+    ladder: &[Signal],
+    send: impl Fn(Signal) -> Result<KillResult>,
+    gone: impl Fn() -> bool,
+) -> Result<KillResult> {
+    for (i, &signal) in ladder.iter().enumerate() {
+        let result = send(signal)?;
+        if !result.is_success() {
+            log::warn!(
+                "Failed to send {signal} to {target}: {}",
+                result.description()
+            );
+            return Ok(result);
+        }
+
+        // SIGKILL cannot be caught; anything else gets a second to take effect
+        let waited = if signal == Signal::SIGKILL {
+            wait_for_exit(&gone, 5, Duration::from_millis(50))
+        } else {
+            wait_for_exit(&gone, 10, Duration::from_millis(100))
+        };
+        if let Some(after) = waited {
+            log::info!(
+                "{target} terminated after {signal} ({}ms)",
+                after.as_millis()
+            );
+            return Ok(KillResult::Success);
+        }
+
+        if let Some(next) = ladder.get(i + 1) {
+            log::warn!("{target} did not respond to {signal}, escalating to {next}");
+        }
+    }
 
-    let result = send_signal_to_target(pid, Signal::SIGKILL, kill_group)?;
+    let last = ladder.last().copied().unwrap_or(Signal::SIGKILL);
+    if last == Signal::SIGKILL {
+        log::error!("{target} still alive after SIGKILL - this should not happen!");
+    }
+    Ok(KillResult::Error(format!("process survived {last}")))
+}
 
-    if !result.is_success() {
-        log::warn!(
-            "Failed to send SIGKILL to process {}: {}",
+/// A member of a tree being killed, pinned so that a reused pid is never
+/// signalled: through a pidfd, or else by its start time
+struct TreeMember {
+    pid: i32,
+    pidfd: Option<i32>,
+    start_time: Option<u64>,
+}
+
+impl TreeMember {
+    fn open(pid: i32) -> Self {
+        let pidfd = try_pidfd_open(pid);
+        let start_time = if pidfd.is_none() {
+            read_start_time(pid)
+        } else {
+            None
+        };
+        Self {
             pid,
-            result.description()
-        );
-        return Ok(result);
+            pidfd,
+            start_time,
+        }
     }
 
-    // Wait briefly to verify process termination
-    for i in 0..5 {
-        thread::sleep(Duration::from_millis(50));
-        if !is_process_alive(pid) {
-            log::info!("Process {} forcefully terminated after {}ms", pid, i * 50);
-            return Ok(KillResult::Success);
+    /// Whether this very process is still alive
+    fn is_alive(&self) -> bool {
+        match self.pidfd {
+            Some(fd) => pidfd_send_signal(fd, None).is_ok(),
+            None => self.start_time.is_some_and(|start| {
+                read_start_time(self.pid) == Some(start) && is_process_alive(self.pid)
+            }),
         }
     }
 
-    // Process should always die after SIGKILL, but check just in case
-    if is_process_alive(pid) {
-        log::error!("Process {pid} still alive after SIGKILL - this should not happen!");
-        Ok(KillResult::Error("process survived SIGKILL".to_string()))
-    } else {
-        Ok(KillResult::Success)
+    fn signal(&self, signal: Signal) -> Result<KillResult> {
+        let Some(fd) = self.pidfd else {
+            return send_signal(self.pid, signal);
+        };
+        match pidfd_send_signal(fd, Some(signal)) {
+            Ok(()) => Ok(KillResult::Success),
+            Err(nix::errno::Errno::ESRCH) => Ok(KillResult::NotFound),
+            Err(nix::errno::Errno::EPERM) => Ok(KillResult::PermissionDenied),
+            Err(e) => Ok(KillResult::Error(format!("signal error: {e}"))),
+        }
+    }
+}
+
+impl Drop for TreeMember {
+    fn drop(&mut self) {
+        if let Some(fd) = self.pidfd {
+            close_fd(fd);
+        }
+    }
+}
+
+/// Start time of a process in clock ticks after boot
+pub fn read_start_time(pid: i32) -> Option<u64> {
+    procfs::process::Process::new(pid)
+        .and_then(|p| p.stat())
+        .ok()
+        .map(|stat| stat.starttime)
+}
+
+/// Kill an application subtree: `root` and the other processes in `members`
+///
+/// Every signal of the ladder goes to all members still alive, and the
+/// kill succeeds once all of them have exited. Members that exit or are
+/// reaped on their own along the way are skipped. Each member is pinned
+/// by a pidfd (or its start time) when the kill starts, so a pid reused
+/// while the ladder runs is left alone.
+pub fn kill_tree(
+    root: i32,
+    members: &[i32],
+    strategy: KillStrategy,
+    ladder: Option<&[Signal]>,
+) -> Result<KillResult> {
+    if !strategy.terminates() {
+        return kill_process_with(root, strategy, KillScope::Process, ladder);
+    }
+    log::debug!(
+        "Attempting to kill tree of process {root} ({} processes, strategy: {strategy:?})",
+        members.len()
+    );
+
+    let pidfd = try_pidfd_open(root);
+    if !is_process_alive(root) {
+        if let Some(fd) = pidfd {
+            close_fd(fd);
+        }
+        return Ok(KillResult::AlreadyDead);
     }
+
+    let ladder = ladder.filter(|l| !l.is_empty()).unwrap_or(&GRACEFUL_LADDER);
+    let ladder = match strategy {
+        KillStrategy::Forceful => &ladder[ladder.len() - 1..],
+        _ => ladder,
+    };
+    let tracked: Vec<TreeMember> = members.iter().map(|&pid| TreeMember::open(pid)).collect();
+    let alive = || tracked.iter().filter(|member| member.is_alive());
+    let result = run_ladder(
+        &format!("tree of process {root}"),
+        ladder,
+        |signal| {
+            log::info!(
+                "Sending {signal} to tree of process {root} ({} processes)",
+                alive().count()
+            );
+            let mut result = KillResult::NotFound;
+            for member in alive() {
+                let sent = member.signal(signal)?;
+                if !result.is_success() {
+                    result = sent;
+                }
+            }
+            Ok(result)
+        },
+        || alive().next().is_none(),
+    );
+
+    if let Some(fd) = pidfd {
+        if result.as_ref().is_ok_and(KillResult::is_success) {
+            try_process_mrelease(fd);
+        }
+        close_fd(fd);
+    }
+
+    result
 }
 
 #[cfg(test)]
@@ -350,10 +677,25 @@ mod tests {
         assert_eq!(KillResult::NotFound.description(), "not found");
     }
 
+    #[test]
+    fn test_parse_kill_scope() {
+        assert_eq!("cgroup".parse::<KillScope>().unwrap(), KillScope::Cgroup);
+        assert_eq!("group".parse::<KillScope>().unwrap(), KillScope::Group);
+        assert!("session".parse::<KillScope>().is_err());
+    }
+
+    #[test]
+    fn test_parse_signal() {
+        assert_eq!(parse_signal("SIGTERM").unwrap(), Signal::SIGTERM);
+        assert_eq!(parse_signal("int").unwrap(), Signal::SIGINT);
+        assert_eq!(parse_signal("USR1").unwrap(), Signal::SIGUSR1);
+        assert!(parse_signal("SIGFOO").is_err());
+    }
+
     #[test]
     fn test_kill_nonexistent_process() {
         // Process ID 999999 should not exist
-        let result = kill_process(999999, KillStrategy::Forceful, false);
+        let result = kill_process(999_999, KillStrategy::Forceful, false);
         assert!(result.is_ok());
         let kill_result = result.unwrap();
         assert!(matches!(
@@ -361,4 +703,47 @@ mod tests {
             KillResult::NotFound | KillResult::AlreadyDead
         ));
     }
+
+    #[test]
+    fn test_tree_member_stops_tracking_an_exited_process() {
+        let mut child = std::process::Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .unwrap();
+        let member = TreeMember::open(child.id() as i32);
+        assert!(member.is_alive());
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+        // Reaped: even if the pid came back, this member is gone
+        assert!(!member.is_alive());
+        assert!(matches!(
+            member.signal(Signal::SIGTERM).unwrap(),
+            KillResult::NotFound
+        ));
+    }
+
+    #[test]
+    fn test_kill_tree_kills_every_member() {
+        let mut children: Vec<_> = (0..2)
+            .map(|_| {
+                std::process::Command::new("sleep")
+                    .arg("30")
+                    .spawn()
+                    .unwrap()
+            })
+            .collect();
+        let pids: Vec<i32> = children.iter().map(|c| c.id() as i32).collect();
+
+        // Reap the children as they die so they do not linger as zombies
+        let reaper = std::thread::spawn(move || {
+            for child in &mut children {
+                child.wait().unwrap();
+            }
+        });
+        let result = kill_tree(pids[0], &pids, KillStrategy::Forceful, None).unwrap();
+        reaper.join().unwrap();
+
+        assert!(result.is_success());
+    }
 }
@@ -0,0 +1,175 @@
+// Soft mitigation: throttle a cgroup into reclaim through memory.high
+
+use super::reclaim::read_memory_current;
+use crate::monitor::cgroup;
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Granularity of memory.high values
+const PAGE_SIZE: u64 = 4096;
+
+/// Default memory.high, as a percentage of the cgroup's current usage
+pub const DEFAULT_THROTTLE_PERCENT: f64 = 90.0;
+
+/// Lowest memory.high, as a percentage of the cgroup's current usage;
+/// anything lower makes the cgroup thrash instead of slowing it down
+pub const MIN_THROTTLE_PERCENT: f64 = 50.0;
+
+/// A cgroup whose memory.high was lowered, with the value to restore
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThrottleTarget {
+    /// Cgroup directory in the unified hierarchy
+    pub dir: PathBuf,
+    /// Original memory.high contents ("max" or a byte count)
+    pub original_high: String,
+    /// memory.high value that was applied, in bytes
+    pub applied_high: u64,
+}
+
+impl ThrottleTarget {
+    /// Restore the original memory.high of the cgroup
+    pub fn restore(&self) -> Result<()> {
+        fs::write(self.dir.join("memory.high"), &self.original_high).with_context(|| {
+            format!(
+                "Failed to restore memory.high of cgroup {}",
+                self.dir.display()
+            )
+        })
+    }
+
+    /// Whether the cgroup still exists
+    pub fn exists(&self) -> bool {
+        self.dir.join("memory.high").exists()
+    }
+}
+
+impl std::fmt::Display for ThrottleTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "cgroup {}", self.dir.display())
+    }
+}
+
+/// memory.high limit for a cgroup using `current` bytes: `percent` of the
+/// current usage (at least MIN_THROTTLE_PERCENT), rounded down to a whole
+/// page
+pub fn throttle_limit(current: u64, percent: f64) -> u64 {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let limit = (current as f64 * percent.max(MIN_THROTTLE_PERCENT) / 100.0) as u64;
+    limit / PAGE_SIZE * PAGE_SIZE
+}
+
+/// Whether a cgroup has a memory.high of its own to lower
+pub fn is_throttleable(dir: &Path) -> bool {
+    dir.join("memory.high").exists()
+}
+
+/// Cgroup of a process that can be throttled, excluding the root cgroup
+/// and the cgroup the daemon itself runs in
+fn throttleable_cgroup(pid: i32) -> Option<PathBuf> {
+    let path = cgroup::read_cgroup_path(pid)?;
+    if path == "/" {
+        return None;
+    }
+    if cgroup::read_cgroup_path(std::process::id() as i32).as_deref() == Some(path.as_str()) {
+        return None;
+    }
+
+    let dir = cgroup::cgroup_dir(&path);
+    is_throttleable(&dir).then_some(dir)
+}
+
+/// Lower memory.high of a cgroup to `percent` of its current usage
+pub fn throttle_cgroup(dir: &Path, percent: f64) -> Result<ThrottleTarget> {
+    let high_path = dir.join("memory.high");
+    let original_high = fs::read_to_string(&high_path)
+        .with_context(|| format!("Failed to read {}", high_path.display()))?
+        .trim()
+        .to_string();
+    let Some(current) = read_memory_current(dir) else {
+        bail!("Failed to read memory.current of cgroup {}", dir.display());
+    };
+    if current == 0 {
+        bail!("Cgroup {} uses no memory to throttle", dir.display());
+    }
+
+    let applied_high = throttle_limit(current, percent);
+    fs::write(&high_path, applied_high.to_string())
+        .with_context(|| format!("Failed to write {}", high_path.display()))?;
+
+    Ok(ThrottleTarget {
+        dir: dir.to_path_buf(),
+        original_high,
+        applied_high,
+    })
+}
+
+/// Throttle the cgroup of a process to `percent` of its current usage
+pub fn throttle_process(pid: i32, percent: f64) -> Result<ThrottleTarget> {
+    let Some(dir) = throttleable_cgroup(pid) else {
+        bail!("Process {pid} has no cgroup of its own that supports memory.high");
+    };
+
+    let target = throttle_cgroup(&dir, percent)?;
+    log::info!(
+        "Set memory.high of {target} to {} bytes (was {}) for pid {pid}",
+        target.applied_high,
+        target.original_high
+    );
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_throttle_limit_rounds_to_pages() {
+        assert_eq!(throttle_limit(1_000_000, 90.0), 897_024);
+        assert_eq!(throttle_limit(100 * PAGE_SIZE, 50.0), 50 * PAGE_SIZE);
+    }
+
+    #[test]
+    fn test_throttle_limit_at_least_half_the_usage() {
+        assert_eq!(throttle_limit(100 * PAGE_SIZE, 10.0), 50 * PAGE_SIZE);
+        assert_eq!(throttle_limit(1_000_000, 1.0), 499_712);
+        assert_eq!(throttle_limit(0, 90.0), 0);
+    }
+
+    #[test]
+    fn test_throttle_and_restore_cgroup_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::write(dir.join("memory.high"), "max\n").unwrap();
+        fs::write(dir.join("memory.current"), "409600\n").unwrap();
+
+        let target = throttle_cgroup(dir, 50.0).unwrap();
+        assert_eq!(target.original_high, "max");
+        assert_eq!(target.applied_high, 204_800);
+        assert_eq!(
+            fs::read_to_string(dir.join("memory.high")).unwrap(),
+            "204800"
+        );
+
+        target.restore().unwrap();
+        assert_eq!(fs::read_to_string(dir.join("memory.high")).unwrap(), "max");
+    }
+
+    #[test]
+    fn test_throttle_empty_cgroup_fails() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::write(tmp.path().join("memory.high"), "max\n").unwrap();
+        fs::write(tmp.path().join("memory.current"), "0\n").unwrap();
+
+        assert!(throttle_cgroup(tmp.path(), 90.0).is_err());
+        assert_eq!(
+            fs::read_to_string(tmp.path().join("memory.high")).unwrap(),
+            "max\n"
+        );
+    }
+
+    #[test]
+    fn test_throttle_nonexistent_process() {
+        assert!(throttle_process(999_999, 90.0).is_err());
+    }
+}
@@ -5,6 +5,8 @@ pub mod daemon;
 pub mod killer;
 pub mod monitor;
 pub mod notify;
+pub mod simulator;
+pub mod top;
 
 // Re-export commonly used types
 pub use config::Config;
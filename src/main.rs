@@ -1,8 +1,8 @@
 // OOM Guard - Main entry point
 
 use nix::sys::mman::{mlockall, MlockAllFlags};
-use oom_guard::config::{Args, Config};
-use oom_guard::daemon;
+use oom_guard::config::{Args, Command, Config};
+use oom_guard::{daemon, simulator, top};
 use std::process;
 
 /// Setup logging based on configuration
@@ -50,17 +50,20 @@ fn setup_logging(debug: bool, use_syslog: bool) {
 
 fn main() {
     // Parse command-line arguments
-    let args = Args::parse_args();
+    let mut args = Args::parse_args();
+    let command = args.command.take();
 
     // Initialize logging based on debug flag and syslog option
     setup_logging(args.debug, args.syslog);
 
     // Lock all current and future memory pages to prevent swapping
     // This ensures the daemon stays responsive even under memory pressure
-    match mlockall(MlockAllFlags::MCL_CURRENT | MlockAllFlags::MCL_FUTURE) {
-        Ok(()) => log::info!("Memory locked successfully - daemon will not be swapped"),
-        Err(e) => {
-            log::warn!("Failed to lock memory: {e}. Daemon may be slow under memory pressure.");
+    if command.is_none() {
+        match mlockall(MlockAllFlags::MCL_CURRENT | MlockAllFlags::MCL_FUTURE) {
+            Ok(()) => log::info!("Memory locked successfully - daemon will not be swapped"),
+            Err(e) => {
+                log::warn!("Failed to lock memory: {e}. Daemon may be slow under memory pressure.");
+            }
         }
     }
 
@@ -68,14 +71,21 @@ fn main() {
     let config = match Config::from_args(args) {
         Ok(cfg) => cfg,
         Err(e) => {
-            eprintln!("Configuration error: {e}");
+            eprintln!("Configuration error: {e:#}");
             eprintln!("Use --help for usage information");
             process::exit(1);
         }
     };
 
-    // Run the daemon
-    if let Err(e) = daemon::run(config) {
+    // Run the requested command, or the daemon
+    let result = match command {
+        Some(Command::Replay { recording }) => daemon::run_replay(config, &recording),
+        Some(Command::Explain) => daemon::run_explain(config),
+        Some(Command::Top) => top::run_top(config),
+        Some(Command::Simulate { scenario }) => simulator::run_simulation(config, &scenario),
+        None => daemon::run(config),
+    };
+    if let Err(e) = result {
         eprintln!("Fatal error: {e}");
         process::exit(1);
     }
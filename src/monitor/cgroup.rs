@@ -0,0 +1,315 @@
+// Control group (cgroup v2) helpers
+
+use std::fs;
+use std::path::PathBuf;
+
+/// Mount point of the unified cgroup hierarchy
+pub const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// Read the cgroup path of a process from /proc/[pid]/cgroup
+pub fn read_cgroup_path(pid: i32) -> Option<String> {
+    fs::read_to_string(format!("/proc/{pid}/cgroup"))
+        .ok()
+        .and_then(|content| cgroup_path_from_file(&content).map(str::to_string))
+}
+
+/// Extract the cgroup path from the contents of /proc/[pid]/cgroup
+pub fn cgroup_path_from_file(content: &str) -> Option<&str> {
+    // Prefer the unified (cgroup v2) hierarchy line "0::/path"
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .or_else(|| {
+            content
+                .lines()
+                .next()
+                .and_then(|line| line.splitn(3, ':').nth(2))
+        })
+}
+
+/// Find the innermost systemd unit (service or scope) in a cgroup path
+pub fn unit_from_path(path: &str) -> Option<&str> {
+    path.rsplit('/').find(|component| is_unit_name(component))
+}
+
+/// Check if a cgroup path component names a systemd service or scope
+pub fn is_unit_name(component: &str) -> bool {
+    component
+        .rsplit_once('.')
+        .is_some_and(|(_, suffix)| matches!(suffix, "service" | "scope"))
+}
+
+/// Systemd slices in a cgroup path, innermost first
+pub fn slices_from_path(path: &str) -> impl Iterator<Item = &str> {
+    path.rsplit('/')
+        .filter(|component| is_slice_name(component))
+}
+
+/// Find the innermost systemd slice in a cgroup path
+pub fn slice_from_path(path: &str) -> Option<&str> {
+    slices_from_path(path).next()
+}
+
+/// Check if a cgroup path component names a systemd slice
+pub fn is_slice_name(component: &str) -> bool {
+    component
+        .rsplit_once('.')
+        .is_some_and(|(_, suffix)| suffix == "slice")
+}
+
+/// Scope name prefixes of container runtimes, with the runtime they name
+const RUNTIME_PREFIXES: [(&str, &str); 4] = [
+    ("docker-", "docker"),
+    ("cri-containerd-", "containerd"),
+    ("crio-", "cri-o"),
+    ("libpod-", "podman"),
+];
+
+/// Find the container ID in a cgroup path
+///
+/// Handles the cgroupfs layout (`/docker/<id>`, `/kubepods/.../<id>`) and
+/// the systemd one (`docker-<id>.scope`, `cri-containerd-<id>.scope`,
+/// `crio-<id>.scope`, `libpod-<id>.scope`).
+pub fn container_from_path(path: &str) -> Option<&str> {
+    find_container(path).map(|(id, _)| id)
+}
+
+/// Find the runtime of the container in a cgroup path: docker,
+/// containerd, cri-o or podman
+///
+/// The cgroupfs layout of Kubernetes (`/kubepods/.../<id>`) does not name
+/// the runtime, so this is `None` there even though the container is found.
+pub fn runtime_from_path(path: &str) -> Option<&'static str> {
+    find_container(path).and_then(|(_, runtime)| runtime)
+}
+
+/// Container ID and, when the path names it, runtime
+fn find_container(path: &str) -> Option<(&str, Option<&'static str>)> {
+    let mut components = path.rsplit('/').peekable();
+    while let Some(component) = components.next() {
+        let name = component.strip_suffix(".scope").unwrap_or(component);
+        let (id, runtime) = RUNTIME_PREFIXES
+            .iter()
+            .find_map(|(prefix, runtime)| Some((name.strip_prefix(prefix)?, Some(*runtime))))
+            .unwrap_or_else(|| {
+                // cgroupfs layout: the runtime is the parent directory
+                let runtime = match components.peek() {
+                    Some(&"docker") => Some("docker"),
+                    Some(&"libpod_parent") => Some("podman"),
+                    _ => None,
+                };
+                (name, runtime)
+            });
+        if is_container_id(id) {
+            return Some((id, runtime));
+        }
+    }
+    None
+}
+
+/// Kubernetes pod a cgroup belongs to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PodInfo {
+    /// Pod UID, e.g. "8d5e2a3c-5b7f-4c1e-9a0d-2f6b1c3e4d5a"
+    pub uid: String,
+    /// QoS class: Guaranteed, Burstable or BestEffort
+    pub qos: &'static str,
+}
+
+/// Find the Kubernetes pod and its QoS class in a cgroup path
+///
+/// Handles the systemd layout
+/// (`/kubepods.slice/kubepods-burstable.slice/kubepods-burstable-pod<uid>.slice/...`,
+/// with '_' for '-' in the UID) and the cgroupfs one
+/// (`/kubepods/burstable/pod<uid>/...`). Guaranteed pods sit directly under
+/// kubepods.
+pub fn pod_from_path(path: &str) -> Option<PodInfo> {
+    let components: Vec<&str> = path
+        .split('/')
+        .map(|c| c.strip_suffix(".slice").unwrap_or(c))
+        .collect();
+    if !components.iter().any(|c| c.starts_with("kubepods")) {
+        return None;
+    }
+    let uid = components.iter().find_map(|c| {
+        let uid = c
+            .rsplit_once("-pod")
+            .filter(|(parent, _)| parent.starts_with("kubepods"))
+            .map(|(_, uid)| uid)
+            .or_else(|| c.strip_prefix("pod"))?;
+        let valid = !uid.is_empty()
+            && uid
+                .chars()
+                .all(|ch| ch.is_ascii_hexdigit() || ch == '-' || ch == '_');
+        valid.then(|| uid.replace('_', "-"))
+    })?;
+    let qos = if components
+        .iter()
+        .any(|c| matches!(*c, "besteffort" | "kubepods-besteffort"))
+    {
+        "BestEffort"
+    } else if components
+        .iter()
+        .any(|c| matches!(*c, "burstable" | "kubepods-burstable"))
+    {
+        "Burstable"
+    } else {
+        "Guaranteed"
+    };
+    Some(PodInfo { uid, qos })
+}
+
+/// Container IDs are 64 hexadecimal digits
+fn is_container_id(id: &str) -> bool {
+    id.len() == 64 && id.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Directory of a cgroup in the unified hierarchy
+pub fn cgroup_dir(path: &str) -> PathBuf {
+    PathBuf::from(CGROUP_ROOT).join(path.trim_start_matches('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cgroup_path_v2() {
+        let content = "0::/system.slice/nginx.service\n";
+        assert_eq!(
+            cgroup_path_from_file(content),
+            Some("/system.slice/nginx.service")
+        );
+    }
+
+    #[test]
+    fn test_unit_from_path() {
+        assert_eq!(
+            unit_from_path("/system.slice/nginx.service"),
+            Some("nginx.service")
+        );
+        assert_eq!(
+            unit_from_path(
+                "/user.slice/user-1000.slice/user@1000.service/app.slice/app-firefox-1234.scope"
+            ),
+            Some("app-firefox-1234.scope")
+        );
+        assert_eq!(unit_from_path("/"), None);
+        assert_eq!(unit_from_path("/user.slice"), None);
+    }
+
+    #[test]
+    fn test_slice_from_path() {
+        let path = "/user.slice/user-1000.slice/user@1000.service/app.slice/app-firefox-1234.scope";
+        assert_eq!(slice_from_path(path), Some("app.slice"));
+        assert_eq!(
+            slices_from_path(path).collect::<Vec<_>>(),
+            ["app.slice", "user-1000.slice", "user.slice"]
+        );
+        assert_eq!(
+            slice_from_path("/system.slice/nginx.service"),
+            Some("system.slice")
+        );
+        assert_eq!(slice_from_path("/init.scope"), None);
+        assert_eq!(slice_from_path(""), None);
+    }
+
+    #[test]
+    fn test_container_from_path() {
+        let id = "4f3c2a1b".repeat(8);
+        for path in [
+            format!("/docker/{id}"),
+            format!("/system.slice/docker-{id}.scope"),
+            format!("/kubepods.slice/kubepods-burstable.slice/kubepods-burstable-pod1234.slice/cri-containerd-{id}.scope"),
+            format!("/kubepods/besteffort/pod5678/{id}"),
+            format!("/machine.slice/libpod-{id}.scope/container"),
+        ] {
+            assert_eq!(container_from_path(&path), Some(id.as_str()), "{path}");
+        }
+        assert_eq!(
+            container_from_path(&format!("/machine.slice/libpod-conmon-{id}.scope")),
+            None
+        );
+        assert_eq!(container_from_path("/system.slice/nginx.service"), None);
+    }
+
+    #[test]
+    fn test_runtime_from_path() {
+        let id = "4f3c2a1b".repeat(8);
+        for (path, runtime) in [
+            (format!("/docker/{id}"), Some("docker")),
+            (format!("/system.slice/docker-{id}.scope"), Some("docker")),
+            (
+                format!("/kubepods.slice/kubepods-pod12.slice/cri-containerd-{id}.scope"),
+                Some("containerd"),
+            ),
+            (
+                format!("/kubepods.slice/kubepods-pod12.slice/crio-{id}.scope"),
+                Some("cri-o"),
+            ),
+            (
+                format!("/machine.slice/libpod-{id}.scope/container"),
+                Some("podman"),
+            ),
+            (format!("/libpod_parent/libpod-{id}"), Some("podman")),
+            (format!("/kubepods/besteffort/pod5678/{id}"), None),
+            ("/system.slice/nginx.service".to_string(), None),
+        ] {
+            assert_eq!(runtime_from_path(&path), runtime, "{path}");
+        }
+    }
+
+    #[test]
+    fn test_pod_from_path() {
+        let id = "4f3c2a1b".repeat(8);
+        let pod = |uid: &str, qos| {
+            Some(PodInfo {
+                uid: uid.to_string(),
+                qos,
+            })
+        };
+        assert_eq!(
+            pod_from_path(&format!(
+                "/kubepods.slice/kubepods-besteffort.slice/kubepods-besteffort-pod8d5e2a3c_5b7f_4c1e.slice/cri-containerd-{id}.scope"
+            )),
+            pod("8d5e2a3c-5b7f-4c1e", "BestEffort")
+        );
+        assert_eq!(
+            pod_from_path(&format!(
+                "/kubepods.slice/kubepods-burstable.slice/kubepods-burstable-pod1234.slice/crio-{id}.scope"
+            )),
+            pod("1234", "Burstable")
+        );
+        assert_eq!(
+            pod_from_path(&format!(
+                "/kubepods.slice/kubepods-pod5b7f_4c1e.slice/crio-{id}.scope"
+            )),
+            pod("5b7f-4c1e", "Guaranteed")
+        );
+        assert_eq!(
+            pod_from_path(&format!("/kubepods/besteffort/pod5b7f-4c1e/{id}")),
+            pod("5b7f-4c1e", "BestEffort")
+        );
+        assert_eq!(
+            pod_from_path(&format!("/kubepods/pod5b7f-4c1e/{id}")),
+            pod("5b7f-4c1e", "Guaranteed")
+        );
+        assert_eq!(
+            pod_from_path(&format!("/system.slice/docker-{id}.scope")),
+            None
+        );
+        assert_eq!(
+            pod_from_path("/kubepods.slice/kubepods-burstable.slice"),
+            None
+        );
+        assert_eq!(pod_from_path("/user.slice/podcast.service"), None);
+    }
+
+    #[test]
+    fn test_cgroup_dir() {
+        assert_eq!(
+            cgroup_dir("/system.slice/nginx.service"),
+            PathBuf::from("/sys/fs/cgroup/system.slice/nginx.service")
+        );
+    }
+}
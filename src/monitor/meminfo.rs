@@ -1,8 +1,9 @@
 // Memory information parsing from /proc/meminfo
 
 use anyhow::{Context, Result};
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::{BufRead, BufReader};
+use std::path::Path;
 
 /// Memory information structure
 #[derive(Debug, Clone, Copy, Default)]
@@ -23,9 +24,11 @@ impl MemInfo {
         Self::read_from_path("/proc/meminfo")
     }
 
-    /// Read memory information from a specific path (for testing)
-    fn read_from_path(path: &str) -> Result<Self> {
-        let file = File::open(path).with_context(|| format!("Failed to open {path}"))?;
+    /// Read memory information from a file in /proc/meminfo format
+    pub fn read_from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file =
+            File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
         let reader = BufReader::new(file);
 
         let mut info = Self::default();
@@ -54,7 +57,7 @@ impl MemInfo {
 
         // Validate that we got all required fields
         if info.mem_total == 0 {
-            anyhow::bail!("Failed to read MemTotal from {path}");
+            anyhow::bail!("Failed to read MemTotal from {}", path.display());
         }
 
         Ok(info)
@@ -106,18 +109,18 @@ impl MemInfo {
         self.swap_free < threshold_kb
     }
 
-    /// Format memory size in human-readable format
+    /// Format a memory size given in KiB in human-readable format
     pub fn format_size(kb: u64) -> String {
-        const KB: u64 = 1024;
-        const MB: u64 = KB * 1024;
+        const MB: u64 = 1024;
         const GB: u64 = MB * 1024;
+        const TB: u64 = GB * 1024;
 
-        if kb >= GB {
+        if kb >= TB {
+            format!("{:.2} TiB", kb as f64 / TB as f64)
+        } else if kb >= GB {
             format!("{:.2} GiB", kb as f64 / GB as f64)
         } else if kb >= MB {
             format!("{:.2} MiB", kb as f64 / MB as f64)
-        } else if kb >= KB {
-            format!("{:.2} KiB", kb as f64 / KB as f64)
         } else {
             format!("{kb} KiB")
         }
@@ -139,6 +142,126 @@ impl std::fmt::Display for MemInfo {
     }
 }
 
+/// Share of used memory in shmem above which killing is not expected to
+/// help, see `MemDetails::shmem_share`
+pub const SHMEM_WARN_SHARE: f64 = 0.5;
+
+/// The rest of /proc/meminfo: memory that is used but not by process
+/// RSS, or that killing processes cannot free. All sizes in KiB
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemDetails {
+    pub cached: u64,
+    pub buffers: u64,
+    /// Shared memory: tmpfs files (/dev/shm, /tmp, /run) and SysV/POSIX
+    /// shared memory segments, freed only when deleted
+    pub shmem: u64,
+    /// Reclaimable kernel slab (dentries, inodes)
+    pub s_reclaimable: u64,
+    /// Memory that cannot be paged out (ramfs, mlock, SHM_LOCK)
+    pub unevictable: u64,
+    pub mlocked: u64,
+    pub dirty: u64,
+    pub writeback: u64,
+    /// Huge pages in the persistent pool, and how many are free
+    pub hugepages_total: u64,
+    pub hugepages_free: u64,
+    pub hugepage_size: u64,
+    /// Memory used by the zswap pool, and the data it holds
+    pub zswap: u64,
+    pub zswapped: u64,
+}
+
+impl MemDetails {
+    /// Read the details of /proc/meminfo
+    pub fn read() -> Option<Self> {
+        Self::read_from_path("/proc/meminfo")
+    }
+
+    /// Read the details of a file in /proc/meminfo format
+    pub fn read_from_path(path: impl AsRef<Path>) -> Option<Self> {
+        fs::read_to_string(path).ok().map(|s| Self::parse(&s))
+    }
+
+    /// Parse /proc/meminfo; fields the kernel does not report stay 0
+    pub fn parse(content: &str) -> Self {
+        let mut details = Self::default();
+        for line in content.lines() {
+            let mut parts = line.split_whitespace();
+            let (Some(key), Some(Ok(value))) = (parts.next(), parts.next().map(str::parse)) else {
+                continue;
+            };
+            let field = match key.trim_end_matches(':') {
+                "Cached" => &mut details.cached,
+                "Buffers" => &mut details.buffers,
+                "Shmem" => &mut details.shmem,
+                "SReclaimable" => &mut details.s_reclaimable,
+                "Unevictable" => &mut details.unevictable,
+                "Mlocked" => &mut details.mlocked,
+                "Dirty" => &mut details.dirty,
+                "Writeback" => &mut details.writeback,
+                "HugePages_Total" => &mut details.hugepages_total,
+                "HugePages_Free" => &mut details.hugepages_free,
+                "Hugepagesize" => &mut details.hugepage_size,
+                "Zswap" => &mut details.zswap,
+                "Zswapped" => &mut details.zswapped,
+                _ => continue,
+            };
+            *field = value;
+        }
+        details
+    }
+
+    /// Memory reserved for the huge page pool, used or not
+    pub const fn hugepages_kb(&self) -> u64 {
+        self.hugepages_total * self.hugepage_size
+    }
+
+    /// Share of the used memory (total minus available) that is shmem
+    pub fn shmem_share(&self, meminfo: &MemInfo) -> f64 {
+        let used = meminfo.mem_total.saturating_sub(meminfo.mem_available);
+        if used == 0 {
+            return 0.0;
+        }
+        self.shmem as f64 / used as f64
+    }
+}
+
+impl std::fmt::Display for MemDetails {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let size = MemInfo::format_size;
+        write!(
+            f,
+            "Cached: {}, Buffers: {}, Shmem: {}, SReclaimable: {}, Unevictable: {}, Mlocked: {}, Dirty: {}, Writeback: {}",
+            size(self.cached),
+            size(self.buffers),
+            size(self.shmem),
+            size(self.s_reclaimable),
+            size(self.unevictable),
+            size(self.mlocked),
+            size(self.dirty),
+            size(self.writeback),
+        )?;
+        if self.hugepages_total > 0 {
+            write!(
+                f,
+                ", HugePages: {}/{} free ({})",
+                self.hugepages_free,
+                self.hugepages_total,
+                size(self.hugepages_kb())
+            )?;
+        }
+        if self.zswap > 0 || self.zswapped > 0 {
+            write!(
+                f,
+                ", Zswap: {} holding {}",
+                size(self.zswap),
+                size(self.zswapped)
+            )?;
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -178,12 +301,53 @@ mod tests {
         assert!(!info.is_swap_below_threshold_kb(500_000));
     }
 
+    #[test]
+    fn test_mem_details() {
+        let content = "MemTotal:       16000000 kB\n\
+                       MemAvailable:    2000000 kB\n\
+                       Buffers:           10000 kB\n\
+                       Cached:          9000000 kB\n\
+                       Shmem:           8000000 kB\n\
+                       SReclaimable:     300000 kB\n\
+                       Unevictable:       40000 kB\n\
+                       Mlocked:           40000 kB\n\
+                       Dirty:               100 kB\n\
+                       Writeback:             0 kB\n\
+                       Zswap:             50000 kB\n\
+                       Zswapped:         200000 kB\n\
+                       HugePages_Total:       4\n\
+                       HugePages_Free:        3\n\
+                       Hugepagesize:       2048 kB\n";
+        let details = MemDetails::parse(content);
+        assert_eq!(details.shmem, 8_000_000);
+        assert_eq!(details.s_reclaimable, 300_000);
+        assert_eq!(details.zswapped, 200_000);
+        assert_eq!((details.hugepages_total, details.hugepages_free), (4, 3));
+        assert_eq!(details.hugepages_kb(), 8192);
+
+        let info = MemInfo {
+            mem_total: 16_000_000,
+            mem_available: 2_000_000,
+            ..MemInfo::default()
+        };
+        assert!((details.shmem_share(&info) - 8.0 / 14.0).abs() < 1e-9);
+        assert_eq!(details.shmem_share(&MemInfo::default()), 0.0);
+        assert!(details
+            .to_string()
+            .contains(", HugePages: 3/4 free (8.00 MiB)"));
+
+        // Older kernels lack some fields
+        let details = MemDetails::parse("MemTotal: 1000 kB\nShmem: 10 kB\n");
+        assert_eq!((details.shmem, details.zswap), (10, 0));
+        assert!(!details.to_string().contains("Zswap"));
+    }
+
     #[test]
     fn test_format_size() {
         assert_eq!(MemInfo::format_size(512), "512 KiB");
-        assert_eq!(MemInfo::format_size(1024), "1.00 KiB");
-        assert_eq!(MemInfo::format_size(1536), "1.50 KiB");
-        assert_eq!(MemInfo::format_size(1024 * 1024), "1.00 MiB");
-        assert_eq!(MemInfo::format_size(1024 * 1024 * 1024), "1.00 GiB");
+        assert_eq!(MemInfo::format_size(1024), "1.00 MiB");
+        assert_eq!(MemInfo::format_size(1536), "1.50 MiB");
+        assert_eq!(MemInfo::format_size(1024 * 1024), "1.00 GiB");
+        assert_eq!(MemInfo::format_size(1024 * 1024 * 1024), "1.00 TiB");
     }
 }
@@ -1,7 +1,22 @@
 // Memory monitoring module
 
+pub mod cgroup;
 mod meminfo;
+pub mod oomkill;
 mod process;
+mod psi;
+pub mod recording;
+mod sessions;
+mod source;
+mod tree;
+mod users;
+mod zram;
 
-pub use meminfo::MemInfo;
-pub use process::ProcessInfo;
+pub use meminfo::{MemDetails, MemInfo, SHMEM_WARN_SHARE};
+pub use process::{format_age, unix_now, ProcessInfo};
+pub use psi::MemoryPressure;
+pub use sessions::ActiveSessions;
+pub use source::{FixtureSource, ProcessSource, ProcfsSource, SystemSource};
+pub use tree::{aggregate, ProcessTree};
+pub use users::{group_members, UserNames};
+pub use zram::{ZramDevice, ZramSwap};
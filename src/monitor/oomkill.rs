@@ -0,0 +1,206 @@
+// Kernel OOM kills: counters and kernel log records
+//
+// The kernel counts its OOM kills system-wide in /proc/vmstat and per
+// cgroup in memory.events, both as "oom_kill N". Which task was killed
+// only appears in the kernel log, read from /dev/kmsg where permitted.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+/// Path of the system-wide event counters
+pub const VMSTAT_PATH: &str = "/proc/vmstat";
+
+/// Path of the kernel log device
+pub const KMSG_PATH: &str = "/dev/kmsg";
+
+/// Parse the "oom_kill N" line of /proc/vmstat or memory.events
+pub fn parse_oom_kill_counter(content: &str) -> Option<u64> {
+    content.lines().find_map(|line| {
+        line.strip_prefix("oom_kill ")
+            .and_then(|count| count.trim().parse().ok())
+    })
+}
+
+/// Read the "oom_kill" counter of a file in /proc/vmstat or memory.events
+/// format
+pub fn read_oom_kill_counter(path: impl AsRef<Path>) -> Option<u64> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| parse_oom_kill_counter(&content))
+}
+
+/// A task killed by the kernel OOM killer, from the kernel log
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KernelOomKill {
+    pub pid: i32,
+    pub name: String,
+    pub uid: Option<u32>,
+    pub anon_rss_kb: Option<u64>,
+    /// Memory cgroup of the task, from the "oom-kill:" summary line
+    pub cgroup: Option<String>,
+}
+
+impl KernelOomKill {
+    /// Parse "Out of memory: Killed process 4321 (stress) total-vm:8392436kB,
+    /// anon-rss:7864320kB, file-rss:4kB, shmem-rss:0kB, UID:1000 ...", also
+    /// logged as "Memory cgroup out of memory: Killed process ..."
+    fn parse_killed(message: &str) -> Option<Self> {
+        let (_, rest) = message.split_once("Killed process ")?;
+        let (pid, rest) = rest.split_once(" (")?;
+        let (name, rest) = rest.rsplit_once(") ")?;
+        let field = |key: &str| {
+            rest.split([' ', ','])
+                .find_map(|token| token.strip_prefix(key))
+        };
+        Some(Self {
+            pid: pid.trim().parse().ok()?,
+            name: name.to_string(),
+            uid: field("UID:").and_then(|uid| uid.parse().ok()),
+            anon_rss_kb: field("anon-rss:").and_then(|rss| rss.trim_end_matches("kB").parse().ok()),
+            cgroup: None,
+        })
+    }
+}
+
+impl std::fmt::Display for KernelOomKill {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "PID {} ({})", self.pid, self.name)?;
+        if let Some(uid) = self.uid {
+            write!(f, ", uid {uid}")?;
+        }
+        if let Some(rss) = self.anon_rss_kb {
+            write!(f, ", anon-rss {rss} KiB")?;
+        }
+        if let Some(cgroup) = &self.cgroup {
+            write!(f, ", cgroup {cgroup}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Kernel OOM kills in kernel log messages, with the cgroup from the
+/// "oom-kill:...,task_memcg=/...,task=NAME,pid=N,uid=N" line of each kill
+pub fn parse_kernel_log<'a>(messages: impl IntoIterator<Item = &'a str>) -> Vec<KernelOomKill> {
+    let mut cgroups: HashMap<i32, String> = HashMap::new();
+    let mut kills = Vec::new();
+    for message in messages {
+        if let Some(summary) = message.strip_prefix("oom-kill:") {
+            let fields: HashMap<&str, &str> = summary
+                .split(',')
+                .filter_map(|field| field.split_once('='))
+                .collect();
+            if let (Some(pid), Some(cgroup)) = (
+                fields.get("pid").and_then(|pid| pid.parse().ok()),
+                fields.get("task_memcg"),
+            ) {
+                cgroups.insert(pid, (*cgroup).to_string());
+            }
+        } else if let Some(kill) = KernelOomKill::parse_killed(message) {
+            kills.push(kill);
+        }
+    }
+    for kill in &mut kills {
+        kill.cgroup = cgroups.remove(&kill.pid);
+    }
+    kills
+}
+
+/// Reader of new kernel log records
+pub struct KmsgReader {
+    file: File,
+}
+
+impl KmsgReader {
+    /// Open /dev/kmsg, skipping the records already logged. Needs
+    /// CAP_SYSLOG unless kernel.dmesg_restrict is 0
+    pub fn open() -> Result<Self> {
+        let mut file = fs::OpenOptions::new()
+            .read(true)
+            .custom_flags(nix::libc::O_NONBLOCK)
+            .open(KMSG_PATH)
+            .with_context(|| format!("Failed to open {KMSG_PATH}"))?;
+        file.seek(SeekFrom::End(0))
+            .with_context(|| format!("Failed to seek {KMSG_PATH}"))?;
+        Ok(Self { file })
+    }
+
+    /// Kernel OOM kills logged since the last call
+    pub fn read_oom_kills(&mut self) -> Vec<KernelOomKill> {
+        let mut messages = Vec::new();
+        let mut buf = vec![0; 8192];
+        loop {
+            // Each read returns one record: "PRIORITY,SEQ,USEC,FLAGS;MESSAGE"
+            // followed by continuation lines starting with a space
+            match self.file.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => {
+                    let record = String::from_utf8_lossy(&buf[..n]);
+                    if let Some((_, message)) =
+                        record.lines().next().and_then(|l| l.split_once(';'))
+                    {
+                        messages.push(message.to_string());
+                    }
+                }
+                // Records were overwritten before being read
+                Err(e) if e.raw_os_error() == Some(nix::libc::EPIPE) => {}
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    log::debug!("Failed to read {KMSG_PATH}: {e}");
+                    break;
+                }
+            }
+        }
+        parse_kernel_log(messages.iter().map(String::as_str))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_oom_kill_counter() {
+        let vmstat = "nr_free_pages 123\noom_kill 7\nnuma_hit 5\n";
+        assert_eq!(parse_oom_kill_counter(vmstat), Some(7));
+        let events = "low 0\nhigh 12\nmax 3\noom 2\noom_kill 2\noom_group_kill 0\n";
+        assert_eq!(parse_oom_kill_counter(events), Some(2));
+        assert_eq!(parse_oom_kill_counter("nr_free_pages 123\n"), None);
+    }
+
+    #[test]
+    fn test_parse_kernel_log() {
+        let kills = parse_kernel_log([
+            "stress invoked oom-killer: gfp_mask=0x140dca(GFP_HIGHUSER_MOVABLE|__GFP_COMP|__GFP_ZERO), order=0, oom_score_adj=0",
+            "oom-kill:constraint=CONSTRAINT_NONE,nodemask=(null),cpuset=/,mems_allowed=0,global_oom,task_memcg=/user.slice/user-1000.slice/session-2.scope,task=stress,pid=4321,uid=1000",
+            "Out of memory: Killed process 4321 (stress) total-vm:8392436kB, anon-rss:7864320kB, file-rss:4kB, shmem-rss:0kB, UID:1000 pgtables:15420kB oom_score_adj:0",
+            "Memory cgroup out of memory: Killed process 977 (my (worker)) total-vm:1000kB, anon-rss:512kB, file-rss:0kB, shmem-rss:0kB, UID:0 pgtables:40kB oom_score_adj:0",
+        ]);
+        assert_eq!(
+            kills,
+            [
+                KernelOomKill {
+                    pid: 4321,
+                    name: "stress".to_string(),
+                    uid: Some(1000),
+                    anon_rss_kb: Some(7_864_320),
+                    cgroup: Some("/user.slice/user-1000.slice/session-2.scope".to_string()),
+                },
+                KernelOomKill {
+                    pid: 977,
+                    name: "my (worker)".to_string(),
+                    uid: Some(0),
+                    anon_rss_kb: Some(512),
+                    cgroup: None,
+                },
+            ]
+        );
+        assert_eq!(
+            kills[0].to_string(),
+            "PID 4321 (stress), uid 1000, anon-rss 7864320 KiB, cgroup /user.slice/user-1000.slice/session-2.scope"
+        );
+    }
+}
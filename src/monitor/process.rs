@@ -1,29 +1,94 @@
 // Process information and selection
 
-use anyhow::Result;
+use super::cgroup::{
+    cgroup_path_from_file, container_from_path, pod_from_path, runtime_from_path, slice_from_path,
+    unit_from_path,
+};
+use super::UserNames;
+use anyhow::{Context, Result};
 use procfs::process::Process;
 use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Mount point of procfs
+pub const PROC_ROOT: &str = "/proc";
 
 /// Information about a process
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct ProcessInfo {
     pub pid: i32,
+    pub ppid: i32,
+    /// Process group and session IDs, 0 when unknown
+    pub pgrp: i32,
+    pub session: i32,
+    /// Device number of the controlling terminal, 0 without one
+    pub tty_nr: i32,
+    /// Foreground process group of the controlling terminal, -1 or 0 when
+    /// unknown
+    pub tpgid: i32,
+    /// Whether the process belongs to an active logind session
+    pub active_session: bool,
     pub name: String,
     pub cmdline: String,
+    /// Resolved /proc/[pid]/exe, empty when unreadable (kernel threads,
+    /// other users' processes without privileges)
+    pub exe: String,
+    /// cgroup path from /proc/[pid]/cgroup, empty when unknown
+    pub cgroup: String,
+    /// Innermost systemd service or scope of the cgroup, empty if none
+    pub unit: String,
+    /// Innermost systemd slice of the cgroup, empty if none
+    pub slice: String,
+    /// Container ID from the cgroup path, empty outside containers
+    pub container: String,
+    /// Container runtime (docker, containerd, cri-o, podman), empty when
+    /// the cgroup path does not name it
+    pub runtime: String,
+    /// Kubernetes pod UID, empty outside pods
+    pub pod: String,
+    /// QoS class of the pod (Guaranteed, Burstable, BestEffort), empty
+    /// outside pods
+    pub qos: String,
     pub rss_kb: u64,
     pub oom_score: i32,
     pub oom_score_adj: i32,
     pub uid: u32,
+    /// Name of `uid`, or the number when it has no passwd entry
+    pub user: String,
     pub state: char,
     pub is_zombie: bool,
+    /// Start time in seconds since the Unix epoch, 0 when unknown
+    pub start_time: u64,
+    /// Start time in clock ticks after boot, as in /proc/[pid]/stat; with
+    /// the PID it identifies the process, unlike the whole seconds above
+    pub start_ticks: u64,
+    /// Seconds since the process started, as of reading it
+    pub age_secs: u64,
+    /// Number of processes whose memory `rss_kb` and `oom_score` add up
+    /// when this describes a whole application subtree (--kill-tree),
+    /// 0 for a single process
+    pub tree_size: usize,
 }
 
 impl ProcessInfo {
     /// Read information about a specific process
     pub fn read(pid: i32) -> Result<Self> {
-        let process = Process::new(pid)?;
+        Self::read_from_dir(&Path::new(PROC_ROOT).join(pid.to_string()))
+    }
+
+    /// Read information about a process from a /proc/[pid]-style directory
+    pub fn read_from_dir(dir: &Path) -> Result<Self> {
+        let boot_time = dir.parent().and_then(read_boot_time);
+        Self::read_with_users(dir, &UserNames::read(), boot_time)
+    }
+
+    /// Read a process, resolving its user name from `users` and its start
+    /// time from the system's `boot_time`
+    fn read_with_users(dir: &Path, users: &UserNames, boot_time: Option<u64>) -> Result<Self> {
+        let process = Process::new_with_root(dir.to_path_buf())?;
+        let pid = process.pid;
         let stat = process.stat()?;
-        let status = process.status()?;
 
         // Get RSS in KiB (stat.rss is in pages, typically 4KB)
         let page_size = procfs::page_size();
@@ -33,10 +98,10 @@ impl ProcessInfo {
         let oom_score = process.oom_score().unwrap_or(0);
 
         // Get OOM score adj from /proc/[pid]/oom_score_adj
-        let oom_score_adj = Self::read_oom_score_adj(pid);
+        let oom_score_adj = Self::read_oom_score_adj(dir);
 
-        // Get UID
-        let uid = status.ruid;
+        // Get real UID
+        let uid = Self::read_uid(dir)?;
 
         // Get process state from stat (first char of state field)
         let state = stat.state;
@@ -51,40 +116,160 @@ impl ProcessInfo {
             cmdline
         };
 
+        let start_time =
+            boot_time.map_or(0, |boot| boot + stat.starttime / procfs::ticks_per_second());
+
         Ok(Self {
             pid,
+            ppid: stat.ppid,
+            pgrp: stat.pgrp,
+            session: stat.session,
+            tty_nr: stat.tty_nr,
+            tpgid: stat.tpgid,
             name: stat.comm,
             cmdline,
+            exe: Self::read_exe(dir),
             rss_kb,
             oom_score: oom_score as i32,
             oom_score_adj,
             uid,
+            user: users.name(uid),
             state,
             is_zombie,
-        })
+            start_ticks: stat.starttime,
+            ..Self::default()
+        }
+        .with_start_time(start_time, unix_now())
+        .with_cgroup(Self::read_cgroup(dir)))
+    }
+
+    /// Set the start time (Unix seconds, 0 when unknown) and the age it
+    /// gives at `now`
+    #[must_use]
+    pub const fn with_start_time(mut self, start_time: u64, now: u64) -> Self {
+        self.start_time = start_time;
+        self.age_secs = if start_time == 0 {
+            0
+        } else {
+            now.saturating_sub(start_time)
+        };
+        self
+    }
+
+    /// Age of the process, None when its start time is unknown
+    pub const fn age(&self) -> Option<u64> {
+        if self.start_time == 0 {
+            None
+        } else {
+            Some(self.age_secs)
+        }
+    }
+
+    /// Set the cgroup path along with the unit, slice, container and pod
+    /// derived from it
+    #[must_use]
+    pub fn with_cgroup(mut self, cgroup: String) -> Self {
+        self.unit = unit_from_path(&cgroup).unwrap_or_default().to_string();
+        self.slice = slice_from_path(&cgroup).unwrap_or_default().to_string();
+        self.container = container_from_path(&cgroup).unwrap_or_default().to_string();
+        self.runtime = runtime_from_path(&cgroup).unwrap_or_default().to_string();
+        (self.pod, self.qos) = pod_from_path(&cgroup)
+            .map(|pod| (pod.uid, pod.qos.to_string()))
+            .unwrap_or_default();
+        self.cgroup = cgroup;
+        self
+    }
+
+    /// What the process is called in kill reports: its container and pod,
+    /// else its systemd unit, with PID and name, e.g.
+    /// "container 4f3c2a1b4f3c in BestEffort pod 8d5e2a3c-... (PID 48213, java)",
+    /// "app-firefox-1234.scope (PID 1234, firefox)" or "process 1234 (firefox)"
+    pub fn label(&self) -> String {
+        let owner = if !self.container.is_empty() {
+            let id = self.container.get(..12).unwrap_or(&self.container);
+            if self.pod.is_empty() {
+                format!("container {id}")
+            } else {
+                format!("container {id} in {} pod {}", self.qos, self.pod)
+            }
+        } else if !self.unit.is_empty() {
+            self.unit.clone()
+        } else {
+            return format!("process {} ({})", self.pid, self.name);
+        };
+        format!("{owner} (PID {}, {})", self.pid, self.name)
     }
 
     /// Read oom_score_adj from /proc/[pid]/oom_score_adj
-    fn read_oom_score_adj(pid: i32) -> i32 {
-        let path = format!("/proc/{pid}/oom_score_adj");
-        fs::read_to_string(&path)
+    fn read_oom_score_adj(dir: &Path) -> i32 {
+        fs::read_to_string(dir.join("oom_score_adj"))
             .ok()
             .and_then(|s| s.trim().parse::<i32>().ok())
             .unwrap_or(0)
     }
 
+    /// Whether the process is in the foreground process group of its
+    /// terminal, i.e. the job a user is typing into
+    pub const fn is_foreground(&self) -> bool {
+        self.tty_nr != 0 && self.tpgid > 0 && self.tpgid == self.pgrp
+    }
+
+    /// Proportional set size in KiB from /proc/[pid]/smaps_rollup, which
+    /// splits shared pages between the processes mapping them
+    pub fn read_pss_kb(pid: i32) -> Option<u64> {
+        let content = fs::read_to_string(format!("{PROC_ROOT}/{pid}/smaps_rollup")).ok()?;
+        content
+            .lines()
+            .find_map(|line| line.strip_prefix("Pss:"))
+            .and_then(|value| value.trim().trim_end_matches("kB").trim().parse().ok())
+    }
+
+    /// Resolve the /proc/[pid]/exe link
+    fn read_exe(dir: &Path) -> String {
+        fs::read_link(dir.join("exe"))
+            .map(|path| path.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    /// Read the cgroup path from /proc/[pid]/cgroup
+    fn read_cgroup(dir: &Path) -> String {
+        fs::read_to_string(dir.join("cgroup"))
+            .ok()
+            .and_then(|content| cgroup_path_from_file(&content).map(str::to_string))
+            .unwrap_or_default()
+    }
+
+    /// Read the real UID from the "Uid:" line of /proc/[pid]/status
+    fn read_uid(dir: &Path) -> Result<u32> {
+        let status = fs::read_to_string(dir.join("status"))
+            .with_context(|| format!("Failed to read {}/status", dir.display()))?;
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix("Uid:"))
+            .and_then(|uids| uids.split_whitespace().next())
+            .and_then(|uid| uid.parse().ok())
+            .with_context(|| format!("No Uid in {}/status", dir.display()))
+    }
+
     /// Get all processes on the system
     pub fn all_processes() -> Result<Vec<Self>> {
+        Self::all_from_dir(Path::new(PROC_ROOT))
+    }
+
+    /// Get all processes from a /proc-style directory tree
+    pub fn all_from_dir(root: &Path) -> Result<Vec<Self>> {
+        let users = UserNames::read();
+        let boot_time = read_boot_time(root);
         let mut processes = Vec::new();
 
-        for entry in fs::read_dir("/proc")? {
+        for entry in fs::read_dir(root)? {
             let entry = entry?;
             let file_name = entry.file_name();
             let name = file_name.to_string_lossy();
 
             // Check if directory name is a number (PID)
-            if let Ok(pid) = name.parse::<i32>() {
-                if let Ok(info) = Self::read(pid) {
+            if name.parse::<i32>().is_ok() {
+                if let Ok(info) = Self::read_with_users(&entry.path(), &users, boot_time) {
                     processes.push(info);
                 }
             }
@@ -94,12 +279,94 @@ impl ProcessInfo {
     }
 }
 
+/// Boot time in seconds since the Unix epoch, from the "btime" line of the
+/// stat file of a /proc-style directory
+fn read_boot_time(root: &Path) -> Option<u64> {
+    fs::read_to_string(root.join("stat"))
+        .ok()?
+        .lines()
+        .find_map(|line| line.strip_prefix("btime "))
+        .and_then(|btime| btime.trim().parse().ok())
+}
+
+/// Current time in seconds since the Unix epoch
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// Compact age for logs: "45s", "12m", "5h" or "3d"
+pub fn format_age(secs: u64) -> String {
+    match secs {
+        0..120 => format!("{secs}s"),
+        120..7200 => format!("{}m", secs / 60),
+        7200..172_800 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86400),
+    }
+}
+
 impl std::fmt::Display for ProcessInfo {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
             "PID {} ({}): {} KiB, OOM score {}, adj {}, state {}",
             self.pid, self.name, self.rss_kb, self.oom_score, self.oom_score_adj, self.state
-        )
+        )?;
+        if self.tree_size > 1 {
+            write!(f, ", tree of {} processes", self.tree_size)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_age() {
+        let process = ProcessInfo::default().with_start_time(1_000, 1_090);
+        assert_eq!(process.age(), Some(90));
+        assert_eq!(ProcessInfo::default().with_start_time(0, 1_090).age(), None);
+
+        assert_eq!(format_age(45), "45s");
+        assert_eq!(format_age(600), "10m");
+        assert_eq!(format_age(5 * 3600), "5h");
+        assert_eq!(format_age(3 * 86400), "3d");
+    }
+
+    #[test]
+    fn test_label() {
+        let process = ProcessInfo {
+            pid: 48213,
+            name: "java".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(process.label(), "process 48213 (java)");
+
+        let scoped = process.clone().with_cgroup(
+            "/user.slice/user-1000.slice/user@1000.service/app.slice/app-java-9.scope".to_string(),
+        );
+        assert_eq!(scoped.label(), "app-java-9.scope (PID 48213, java)");
+
+        let id = "4f3c2a1b".repeat(8);
+        let docker = process
+            .clone()
+            .with_cgroup(format!("/system.slice/docker-{id}.scope"));
+        assert_eq!(docker.runtime, "docker");
+        assert_eq!(docker.label(), "container 4f3c2a1b4f3c (PID 48213, java)");
+
+        let pod = process.with_cgroup(format!(
+            "/kubepods.slice/kubepods-besteffort.slice/kubepods-besteffort-pod8d5e_2a3c.slice/cri-containerd-{id}.scope"
+        ));
+        assert_eq!(
+            (pod.pod.as_str(), pod.qos.as_str()),
+            ("8d5e-2a3c", "BestEffort")
+        );
+        assert_eq!(
+            pod.label(),
+            "container 4f3c2a1b4f3c in BestEffort pod 8d5e-2a3c (PID 48213, java)"
+        );
     }
 }
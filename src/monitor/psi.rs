@@ -0,0 +1,101 @@
+// Memory pressure stall information (PSI) from /proc/pressure/memory
+
+use std::fs;
+use std::path::Path;
+
+/// Path of the system-wide memory PSI file (Linux 4.20+)
+pub const PSI_MEMORY_PATH: &str = "/proc/pressure/memory";
+
+/// Share of time (percent) tasks were stalled on memory
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MemoryPressure {
+    /// At least one task stalled, 10s average
+    pub some_avg10: f64,
+    /// At least one task stalled, 60s average
+    pub some_avg60: f64,
+    /// All non-idle tasks stalled, 10s average
+    pub full_avg10: f64,
+    /// All non-idle tasks stalled, 60s average
+    pub full_avg60: f64,
+}
+
+impl MemoryPressure {
+    /// Read system-wide memory pressure, if PSI is available
+    pub fn read() -> Option<Self> {
+        Self::read_from_path(PSI_MEMORY_PATH)
+    }
+
+    /// Read memory pressure from a file in /proc/pressure/memory format
+    pub fn read_from_path(path: impl AsRef<Path>) -> Option<Self> {
+        fs::read_to_string(path).ok().and_then(|s| Self::parse(&s))
+    }
+
+    /// Parse the "some" and "full" lines of a PSI file
+    pub fn parse(content: &str) -> Option<Self> {
+        let mut pressure = Self::default();
+        let mut found = false;
+
+        for line in content.lines() {
+            let mut fields = line.split_whitespace();
+            let kind = fields.next()?;
+            let mut avg10 = 0.0;
+            let mut avg60 = 0.0;
+            for field in fields {
+                match field.split_once('=') {
+                    Some(("avg10", value)) => avg10 = value.parse().ok()?,
+                    Some(("avg60", value)) => avg60 = value.parse().ok()?,
+                    _ => {}
+                }
+            }
+
+            match kind {
+                "some" => {
+                    pressure.some_avg10 = avg10;
+                    pressure.some_avg60 = avg60;
+                    found = true;
+                }
+                "full" => {
+                    pressure.full_avg10 = avg10;
+                    pressure.full_avg60 = avg60;
+                }
+                _ => {}
+            }
+        }
+
+        found.then_some(pressure)
+    }
+}
+
+impl std::fmt::Display for MemoryPressure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "PSI some {:.2}%/{:.2}%, full {:.2}%/{:.2}% (avg10/avg60)",
+            self.some_avg10, self.some_avg60, self.full_avg10, self.full_avg60
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_psi() {
+        let content = "\
+some avg10=12.50 avg60=3.20 avg300=0.80 total=123456
+full avg10=4.00 avg60=1.10 avg300=0.20 total=65432
+";
+        let pressure = MemoryPressure::parse(content).unwrap();
+        assert_eq!(pressure.some_avg10, 12.5);
+        assert_eq!(pressure.some_avg60, 3.2);
+        assert_eq!(pressure.full_avg10, 4.0);
+        assert_eq!(pressure.full_avg60, 1.1);
+    }
+
+    #[test]
+    fn test_parse_psi_invalid() {
+        assert_eq!(MemoryPressure::parse(""), None);
+        assert_eq!(MemoryPressure::parse("some avg10=abc\n"), None);
+    }
+}
@@ -0,0 +1,377 @@
+// Recordings of memory incidents: memory, PSI and the process table per sample
+//
+// The format is line based. Each sample starts with a "T" line holding the
+// time since the start of the recording, the wall clock time and the memory
+// counters, followed by one tab-separated "P" line per process:
+//
+//   T <ms> <time> <mem_total> <mem_available> <swap_total> <swap_free> [<some10> <some60> <full10> <full60>]
+//   P <pid> <uid> <rss_kb> <oom_score> <oom_score_adj> <state> <name> <cmdline> <ppid> <exe> <cgroup> <user> <pgrp> <session> <tty_nr> <tpgid> <active_session> <start_time>
+//
+// The PSI averages are left out when the kernel has no PSI. Times are in
+// seconds since the Unix epoch, a start time of 0 meaning unknown; the age
+// of a process is the time of its sample minus its start time.
+
+use super::{unix_now, MemInfo, MemoryPressure, ProcessInfo};
+use anyhow::{bail, Context, Result};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// First line of every recording
+pub const RECORDING_HEADER: &str = "# oom_guard recording v1";
+
+/// State of the system at one point of a recording
+#[derive(Debug, Clone)]
+pub struct Sample {
+    /// Time since the start of the recording
+    pub elapsed: Duration,
+    /// Wall clock time, in seconds since the Unix epoch
+    pub time: u64,
+    pub meminfo: MemInfo,
+    pub pressure: Option<MemoryPressure>,
+    pub processes: Vec<ProcessInfo>,
+}
+
+/// Appends samples to a recording file
+pub struct Recorder {
+    writer: BufWriter<File>,
+    start: Instant,
+}
+
+impl Recorder {
+    /// Create (or truncate) a recording file
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create recording {}", path.display()))?;
+        let mut writer = BufWriter::new(file);
+        writeln!(writer, "{RECORDING_HEADER}")?;
+
+        Ok(Self {
+            writer,
+            start: Instant::now(),
+        })
+    }
+
+    /// Record the current state of the system
+    pub fn record(
+        &mut self,
+        meminfo: &MemInfo,
+        pressure: Option<MemoryPressure>,
+        processes: Vec<ProcessInfo>,
+    ) -> Result<()> {
+        self.write_sample(&Sample {
+            elapsed: self.start.elapsed(),
+            time: unix_now(),
+            meminfo: *meminfo,
+            pressure,
+            processes,
+        })
+    }
+
+    /// Append a sample and flush it, so the recording survives a crash
+    pub fn write_sample(&mut self, sample: &Sample) -> Result<()> {
+        write!(
+            self.writer,
+            "T {} {} {} {} {} {}",
+            sample.elapsed.as_millis(),
+            sample.time,
+            sample.meminfo.mem_total,
+            sample.meminfo.mem_available,
+            sample.meminfo.swap_total,
+            sample.meminfo.swap_free
+        )?;
+        if let Some(psi) = sample.pressure {
+            write!(
+                self.writer,
+                " {:.2} {:.2} {:.2} {:.2}",
+                psi.some_avg10, psi.some_avg60, psi.full_avg10, psi.full_avg60
+            )?;
+        }
+        writeln!(self.writer)?;
+
+        // Kernel threads have no RSS and can never be chosen; leave them out
+        for p in sample.processes.iter().filter(|p| p.rss_kb > 0) {
+            writeln!(
+                self.writer,
+                "P\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                p.pid,
+                p.uid,
+                p.rss_kb,
+                p.oom_score,
+                p.oom_score_adj,
+                p.state,
+                escape(&p.name),
+                escape(&p.cmdline),
+                p.ppid,
+                escape(&p.exe),
+                escape(&p.cgroup),
+                escape(&p.user),
+                p.pgrp,
+                p.session,
+                p.tty_nr,
+                p.tpgid,
+                u8::from(p.active_session),
+                p.start_time
+            )?;
+        }
+
+        self.writer.flush().context("Failed to write recording")
+    }
+}
+
+/// Read all samples of a recording file
+pub fn read_recording(path: impl AsRef<Path>) -> Result<Vec<Sample>> {
+    let path = path.as_ref();
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read recording {}", path.display()))?;
+    parse_recording(&content).with_context(|| format!("Invalid recording {}", path.display()))
+}
+
+/// Parse the contents of a recording
+pub fn parse_recording(content: &str) -> Result<Vec<Sample>> {
+    let mut lines = content.lines().enumerate();
+    match lines.next() {
+        Some((_, RECORDING_HEADER)) => {}
+        _ => bail!("missing header \"{RECORDING_HEADER}\""),
+    }
+
+    let mut samples: Vec<Sample> = Vec::new();
+    for (index, line) in lines {
+        let line_no = index + 1;
+        if let Some(rest) = line.strip_prefix("T ") {
+            samples.push(parse_sample_line(rest).with_context(|| format!("line {line_no}"))?);
+        } else if let Some(rest) = line.strip_prefix("P\t") {
+            let Some(sample) = samples.last_mut() else {
+                bail!("line {line_no}: process before the first sample");
+            };
+            let process =
+                parse_process_line(rest, sample.time).with_context(|| format!("line {line_no}"))?;
+            sample.processes.push(process);
+        } else if !line.is_empty() && !line.starts_with('#') {
+            bail!("line {line_no}: unexpected record");
+        }
+    }
+
+    Ok(samples)
+}
+
+fn parse_sample_line(rest: &str) -> Result<Sample> {
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+    if fields.len() != 6 && fields.len() != 10 {
+        bail!("expected 6 or 10 sample fields, got {}", fields.len());
+    }
+
+    let ms: u64 = fields[0].parse().context("invalid time")?;
+    let time: u64 = fields[1].parse().context("invalid wall clock time")?;
+    let counters: Vec<u64> = fields[2..6]
+        .iter()
+        .map(|f| f.parse().context("invalid memory counter"))
+        .collect::<Result<_>>()?;
+    let pressure = if fields.len() == 10 {
+        let psi: Vec<f64> = fields[6..10]
+            .iter()
+            .map(|f| f.parse().context("invalid PSI value"))
+            .collect::<Result<_>>()?;
+        Some(MemoryPressure {
+            some_avg10: psi[0],
+            some_avg60: psi[1],
+            full_avg10: psi[2],
+            full_avg60: psi[3],
+        })
+    } else {
+        None
+    };
+
+    Ok(Sample {
+        elapsed: Duration::from_millis(ms),
+        time,
+        meminfo: MemInfo {
+            mem_total: counters[0],
+            mem_available: counters[1],
+            swap_total: counters[2],
+            swap_free: counters[3],
+        },
+        pressure,
+        processes: Vec::new(),
+    })
+}
+
+/// Parse a process line of a sample taken at `time`
+fn parse_process_line(rest: &str, time: u64) -> Result<ProcessInfo> {
+    let fields: Vec<&str> = rest.split('\t').collect();
+    if fields.len() != 18 {
+        bail!("expected 18 process fields, got {}", fields.len());
+    }
+
+    let state = fields[5].chars().next().context("missing state")?;
+    let start_time: u64 = fields[17].parse().context("invalid start_time")?;
+    let process = ProcessInfo {
+        pid: fields[0].parse().context("invalid pid")?,
+        uid: fields[1].parse().context("invalid uid")?,
+        rss_kb: fields[2].parse().context("invalid rss")?,
+        oom_score: fields[3].parse().context("invalid oom_score")?,
+        oom_score_adj: fields[4].parse().context("invalid oom_score_adj")?,
+        state,
+        is_zombie: state == 'Z',
+        name: unescape(fields[6]),
+        cmdline: unescape(fields[7]),
+        ppid: fields[8].parse().context("invalid ppid")?,
+        exe: unescape(fields[9]),
+        user: unescape(fields[11]),
+        pgrp: fields[12].parse().context("invalid pgrp")?,
+        session: fields[13].parse().context("invalid session")?,
+        tty_nr: fields[14].parse().context("invalid tty_nr")?,
+        tpgid: fields[15].parse().context("invalid tpgid")?,
+        active_session: fields[16] == "1",
+        ..ProcessInfo::default()
+    };
+    Ok(process
+        .with_start_time(start_time, time)
+        .with_cgroup(unescape(fields[10])))
+}
+
+/// Escape backslashes, tabs and newlines so a field fits on one line
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+}
+
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(pid: i32, name: &str, rss_kb: u64) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            name: name.to_string(),
+            ppid: 1,
+            pgrp: pid,
+            session: 5,
+            tty_nr: 34_816,
+            tpgid: pid,
+            active_session: true,
+            cmdline: format!("/usr/bin/{name}\t--x"),
+            exe: format!("/usr/bin/{name}"),
+            rss_kb,
+            oom_score: 300,
+            oom_score_adj: 0,
+            uid: 1000,
+            user: "alice".to_string(),
+            state: 'R',
+            is_zombie: false,
+            ..ProcessInfo::default()
+        }
+        .with_start_time(unix_now() - 45, 0)
+        .with_cgroup("/user.slice/user-1000.slice/session-2.scope".to_string())
+    }
+
+    #[test]
+    fn test_recording_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("incident.rec");
+        let mut recorder = Recorder::create(&path).unwrap();
+
+        let meminfo = MemInfo {
+            mem_total: 16_000_000,
+            mem_available: 1_000_000,
+            swap_total: 8_000_000,
+            swap_free: 500_000,
+        };
+        let pressure = MemoryPressure {
+            some_avg10: 42.5,
+            some_avg60: 10.0,
+            full_avg10: 20.25,
+            full_avg60: 5.0,
+        };
+        recorder
+            .write_sample(&Sample {
+                elapsed: Duration::from_millis(1500),
+                time: unix_now(),
+                meminfo,
+                pressure: Some(pressure),
+                processes: vec![process(10, "hog", 900_000), process(2, "kthreadd", 0)],
+            })
+            .unwrap();
+        recorder
+            .record(&meminfo, None, vec![process(11, "odd\\name", 1024)])
+            .unwrap();
+
+        let samples = read_recording(&path).unwrap();
+        assert_eq!(samples.len(), 2);
+
+        assert_eq!(samples[0].elapsed, Duration::from_millis(1500));
+        assert_eq!(samples[0].meminfo.mem_available, 1_000_000);
+        assert_eq!(samples[0].meminfo.swap_free, 500_000);
+        assert_eq!(samples[0].pressure, Some(pressure));
+        // Kernel threads are not recorded
+        assert_eq!(samples[0].processes.len(), 1);
+        assert_eq!(samples[0].processes[0].cmdline, "/usr/bin/hog\t--x");
+        assert_eq!(samples[0].processes[0].rss_kb, 900_000);
+        assert_eq!(samples[0].processes[0].exe, "/usr/bin/hog");
+        assert_eq!(
+            samples[0].processes[0].cgroup,
+            "/user.slice/user-1000.slice/session-2.scope"
+        );
+        assert_eq!(samples[0].processes[0].unit, "session-2.scope");
+        assert_eq!(samples[0].processes[0].user, "alice");
+        assert_eq!(samples[0].processes[0].pgrp, 10);
+        assert_eq!(samples[0].processes[0].session, 5);
+        assert!(samples[0].processes[0].is_foreground());
+        assert!(samples[0].processes[0].active_session);
+        assert!((45..50).contains(&samples[0].processes[0].age().unwrap()));
+
+        assert_eq!(samples[1].pressure, None);
+        assert_eq!(samples[1].processes[0].name, "odd\\name");
+    }
+
+    #[test]
+    fn test_recording_rejects_missing_header() {
+        assert!(parse_recording("T 0 1000 1 1 0 0\n").is_err());
+    }
+
+    #[test]
+    fn test_recording_process_age_from_sample_time() {
+        let line = "P\t7\t0\t10\t0\t0\tS\tapp\tapp\t1\t\t\troot\t7\t7\t0\t0\t0\t1000";
+        let content = format!("{RECORDING_HEADER}\nT 0 1090 100 50 0 0\n{line}\n");
+        let samples = parse_recording(&content).unwrap();
+        let process = &samples[0].processes[0];
+        assert_eq!((process.pid, process.ppid), (7, 1));
+        assert_eq!(process.start_time, 1000);
+        assert_eq!(process.age(), Some(90));
+    }
+
+    #[test]
+    fn test_recording_rejects_other_process_lines() {
+        // Lines with fewer fields, e.g. without parent, exe and cgroup
+        let content =
+            format!("{RECORDING_HEADER}\nT 0 1090 100 50 0 0\nP\t7\t0\t10\t0\t0\tS\tapp\tapp\n");
+        assert!(parse_recording(&content).is_err());
+    }
+
+    #[test]
+    fn test_recording_rejects_orphan_process() {
+        let content = format!("{RECORDING_HEADER}\nP\t1\t0\t10\t0\t0\tS\tinit\tinit\n");
+        assert!(parse_recording(&content).is_err());
+    }
+}
@@ -0,0 +1,131 @@
+// Active logind sessions from /run/systemd/sessions
+//
+// logind keeps one file per session with KEY=VALUE lines such as
+// UID=1000, ACTIVE=1, SCOPE=session-2.scope and TYPE=wayland. Processes
+// started from a session run in its scope; apps launched from a graphical
+// desktop usually run under the user's systemd instance instead.
+
+use super::ProcessInfo;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+/// Location of the logind session database
+pub const SESSIONS_DIR: &str = "/run/systemd/sessions";
+
+/// Session types of graphical desktops
+const GRAPHICAL_TYPES: [&str; 3] = ["x11", "wayland", "mir"];
+
+/// Scopes of the active sessions, and users with an active desktop
+#[derive(Debug, Clone, Default)]
+pub struct ActiveSessions {
+    scopes: HashSet<String>,
+    desktop_uids: HashSet<u32>,
+}
+
+impl ActiveSessions {
+    /// Read the session database, empty without logind
+    pub fn read() -> Self {
+        Self::read_from(Path::new(SESSIONS_DIR))
+    }
+
+    /// Read session files from `dir`, empty if it cannot be read
+    pub fn read_from(dir: &Path) -> Self {
+        let mut sessions = Self::default();
+        let Ok(entries) = fs::read_dir(dir) else {
+            return sessions;
+        };
+        for entry in entries.flatten() {
+            // logind writes "<id>.ref" pipes next to the session files
+            if entry.file_name().to_string_lossy().contains('.') {
+                continue;
+            }
+            if let Ok(content) = fs::read_to_string(entry.path()) {
+                sessions.add(&content);
+            }
+        }
+        sessions
+    }
+
+    /// Add a session from the content of its file, if it is active
+    pub fn add(&mut self, content: &str) {
+        let value = |key: &str| {
+            content
+                .lines()
+                .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+        };
+        if value("ACTIVE") != Some("1") {
+            return;
+        }
+        if let Some(scope) = value("SCOPE") {
+            self.scopes.insert(scope.to_string());
+        }
+        let graphical = value("TYPE").is_some_and(|t| GRAPHICAL_TYPES.contains(&t));
+        if let Some(uid) = value("UID").and_then(|uid| uid.parse().ok()) {
+            if graphical {
+                self.desktop_uids.insert(uid);
+            }
+        }
+    }
+
+    /// Whether a process runs in an active session's scope, or under the
+    /// systemd user instance of a user with an active desktop
+    pub fn contains(&self, process: &ProcessInfo) -> bool {
+        self.scopes.contains(&process.unit)
+            || (self.desktop_uids.contains(&process.uid)
+                && process
+                    .cgroup
+                    .contains(&format!("/user@{}.service/", process.uid)))
+    }
+
+    /// Set `active_session` on each process
+    pub fn mark(&self, processes: &mut [ProcessInfo]) {
+        for process in processes {
+            process.active_session = self.contains(process);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(uid: u32, cgroup: &str) -> ProcessInfo {
+        ProcessInfo {
+            uid,
+            ..Default::default()
+        }
+        .with_cgroup(cgroup.to_string())
+    }
+
+    #[test]
+    fn test_active_sessions() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("2"),
+            "UID=1000\nUSER=alice\nACTIVE=1\nSTATE=active\nTYPE=wayland\nSCOPE=session-2.scope\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("5"),
+            "UID=1001\nACTIVE=0\nSTATE=online\nTYPE=tty\nSCOPE=session-5.scope\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("2.ref"), "").unwrap();
+        let sessions = ActiveSessions::read_from(dir.path());
+
+        let in_session = process(1000, "/user.slice/user-1000.slice/session-2.scope");
+        let desktop_app = process(
+            1000,
+            "/user.slice/user-1000.slice/user@1000.service/app.slice/app-firefox-1.scope",
+        );
+        let inactive = process(1001, "/user.slice/user-1001.slice/session-5.scope");
+        let service = process(0, "/system.slice/cron.service");
+        assert!(sessions.contains(&in_session));
+        assert!(sessions.contains(&desktop_app));
+        assert!(!sessions.contains(&inactive));
+        assert!(!sessions.contains(&service));
+
+        assert!(!ActiveSessions::read_from(&dir.path().join("missing")).contains(&in_session));
+    }
+}
@@ -0,0 +1,469 @@
+// Sources of memory and process information: the live system or a fixture
+
+use super::cgroup::{cgroup_dir, read_cgroup_path};
+use super::oomkill::{read_oom_kill_counter, VMSTAT_PATH};
+use super::{ActiveSessions, MemDetails, MemInfo, MemoryPressure, ProcessInfo, ZramSwap};
+use crate::killer::signals::read_start_time;
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Source of system-wide memory information
+pub trait SystemSource {
+    /// Current memory and swap usage
+    fn meminfo(&self) -> Result<MemInfo>;
+
+    /// Breakdown of the memory not used by processes, if available
+    fn mem_details(&self) -> Option<MemDetails>;
+
+    /// zram devices used as swap, None without any
+    fn zram_swap(&self) -> Option<ZramSwap>;
+
+    /// Memory pressure stall information, if available
+    fn pressure(&self) -> Option<MemoryPressure>;
+
+    /// Processes killed by the kernel OOM killer since boot, if counted
+    fn oom_kills(&self) -> Option<u64>;
+
+    /// Processes killed by the kernel OOM killer in a cgroup, if counted
+    fn cgroup_oom_kills(&self, cgroup: &str) -> Option<u64>;
+}
+
+/// Source of the process table
+pub trait ProcessSource {
+    /// All processes currently running
+    fn processes(&self) -> Result<Vec<ProcessInfo>>;
+
+    /// Set the oom_score_adj of a process
+    fn set_oom_score_adj(&self, pid: i32, adj: i32) -> Result<()>;
+
+    /// Whether a process is still running, rather than gone with its PID
+    /// free or given to another process (told apart by start time)
+    fn is_alive(&self, process: &ProcessInfo) -> bool;
+
+    /// Directory of the cgroup of a process, None for the root cgroup and
+    /// the cgroup the daemon itself runs in
+    fn cgroup_dir(&self, process: &ProcessInfo) -> Option<PathBuf>;
+}
+
+/// The live system, read through /proc
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProcfsSource;
+
+impl SystemSource for ProcfsSource {
+    fn meminfo(&self) -> Result<MemInfo> {
+        MemInfo::read()
+    }
+
+    fn mem_details(&self) -> Option<MemDetails> {
+        MemDetails::read()
+    }
+
+    fn zram_swap(&self) -> Option<ZramSwap> {
+        ZramSwap::read()
+    }
+
+    fn pressure(&self) -> Option<MemoryPressure> {
+        MemoryPressure::read()
+    }
+
+    fn oom_kills(&self) -> Option<u64> {
+        read_oom_kill_counter(VMSTAT_PATH)
+    }
+
+    fn cgroup_oom_kills(&self, cgroup: &str) -> Option<u64> {
+        read_oom_kill_counter(cgroup_dir(cgroup).join("memory.events"))
+    }
+}
+
+impl ProcessSource for ProcfsSource {
+    fn processes(&self) -> Result<Vec<ProcessInfo>> {
+        let mut processes = ProcessInfo::all_processes()?;
+        ActiveSessions::read().mark(&mut processes);
+        Ok(processes)
+    }
+
+    fn set_oom_score_adj(&self, pid: i32, adj: i32) -> Result<()> {
+        let path = format!("/proc/{pid}/oom_score_adj");
+        fs::write(&path, adj.to_string()).with_context(|| format!("Failed to write {path}"))
+    }
+
+    fn is_alive(&self, process: &ProcessInfo) -> bool {
+        read_start_time(process.pid) == Some(process.start_ticks)
+    }
+
+    fn cgroup_dir(&self, process: &ProcessInfo) -> Option<PathBuf> {
+        let path = process.cgroup.as_str();
+        if path.is_empty() || path == "/" {
+            return None;
+        }
+        if read_cgroup_path(std::process::id() as i32).as_deref() == Some(path) {
+            return None;
+        }
+        Some(cgroup_dir(path))
+    }
+}
+
+/// A directory tree laid out like /proc
+///
+/// `meminfo` and `pressure/memory` at the top and one `[pid]/` directory
+/// per process with `stat`, `status`, `cmdline`, `oom_score`,
+/// `oom_score_adj` and optionally `exe` and `cgroup`. logind sessions
+/// go in `sessions/`, laid out like /run/systemd/sessions, cgroup files
+/// in `cgroup/`, laid out like /sys/fs/cgroup, and swap areas in `swaps`
+/// with zram devices in `block/`, laid out like /sys/block
+#[derive(Debug, Clone)]
+pub struct FixtureSource {
+    root: PathBuf,
+}
+
+impl FixtureSource {
+    /// Read fixtures from `root`
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Write `meminfo` in /proc/meminfo format
+    pub fn write_meminfo(&self, meminfo: &MemInfo) -> Result<()> {
+        fs::create_dir_all(&self.root)
+            .with_context(|| format!("Failed to create {}", self.root.display()))?;
+        let content = format!(
+            "MemTotal:       {} kB\nMemAvailable:   {} kB\nSwapTotal:      {} kB\nSwapFree:       {} kB\n",
+            meminfo.mem_total, meminfo.mem_available, meminfo.swap_total, meminfo.swap_free
+        );
+        fs::write(self.root.join("meminfo"), content).context("Failed to write meminfo fixture")
+    }
+
+    /// Write `pressure/memory` in /proc/pressure/memory format
+    pub fn write_pressure(&self, pressure: &MemoryPressure) -> Result<()> {
+        let dir = self.root.join("pressure");
+        fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        let content = format!(
+            "some avg10={:.2} avg60={:.2} avg300=0.00 total=0\nfull avg10={:.2} avg60={:.2} avg300=0.00 total=0\n",
+            pressure.some_avg10, pressure.some_avg60, pressure.full_avg10, pressure.full_avg60
+        );
+        fs::write(dir.join("memory"), content).context("Failed to write pressure fixture")
+    }
+
+    /// Write `vmstat` with the kernel OOM kill counter
+    pub fn write_oom_kills(&self, count: u64) -> Result<()> {
+        fs::write(self.root.join("vmstat"), format!("oom_kill {count}\n"))
+            .context("Failed to write vmstat fixture")
+    }
+
+    /// Write the `memory.events` of a cgroup with its OOM kill counter
+    pub fn write_cgroup_oom_kills(&self, cgroup: &str, count: u64) -> Result<()> {
+        let dir = self
+            .root
+            .join("cgroup")
+            .join(cgroup.trim_start_matches('/'));
+        fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        fs::write(
+            dir.join("memory.events"),
+            format!("oom {count}\noom_kill {count}\n"),
+        )
+        .context("Failed to write memory.events fixture")
+    }
+
+    /// Write the `[pid]/` directory of a process
+    pub fn write_process(&self, process: &ProcessInfo) -> Result<()> {
+        let dir = self.root.join(process.pid.to_string());
+        fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+
+        fs::write(dir.join("stat"), stat_line(process))?;
+        if process.start_time != 0 {
+            fs::write(
+                self.root.join("stat"),
+                format!("btime {FIXTURE_BOOT_TIME}\n"),
+            )?;
+        }
+        fs::write(
+            dir.join("status"),
+            format!(
+                "Name:\t{}\nState:\t{}\nPid:\t{}\nUid:\t{uid}\t{uid}\t{uid}\t{uid}\n",
+                process.name,
+                process.state,
+                process.pid,
+                uid = process.uid
+            ),
+        )?;
+        let mut cmdline = process
+            .cmdline
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join("\0");
+        cmdline.push('\0');
+        fs::write(dir.join("cmdline"), cmdline)?;
+        fs::write(dir.join("oom_score"), format!("{}\n", process.oom_score))?;
+        fs::write(
+            dir.join("oom_score_adj"),
+            format!("{}\n", process.oom_score_adj),
+        )?;
+        if !process.exe.is_empty() {
+            let link = dir.join("exe");
+            let _ = fs::remove_file(&link);
+            std::os::unix::fs::symlink(&process.exe, &link)?;
+        }
+        if !process.cgroup.is_empty() {
+            fs::write(dir.join("cgroup"), format!("0::{}\n", process.cgroup))?;
+        }
+
+        Ok(())
+    }
+
+    /// Remove the `[pid]/` directory of a process, e.g. after it was killed
+    pub fn remove_process(&self, pid: i32) -> Result<()> {
+        let dir = self.root.join(pid.to_string());
+        if dir.exists() {
+            fs::remove_dir_all(&dir)
+                .with_context(|| format!("Failed to remove {}", dir.display()))?;
+        }
+        Ok(())
+    }
+}
+
+impl SystemSource for FixtureSource {
+    fn meminfo(&self) -> Result<MemInfo> {
+        MemInfo::read_from_path(self.root.join("meminfo"))
+    }
+
+    fn mem_details(&self) -> Option<MemDetails> {
+        MemDetails::read_from_path(self.root.join("meminfo"))
+    }
+
+    fn zram_swap(&self) -> Option<ZramSwap> {
+        ZramSwap::read_from(self.root.join("swaps"), self.root.join("block"))
+    }
+
+    fn pressure(&self) -> Option<MemoryPressure> {
+        MemoryPressure::read_from_path(self.root.join("pressure/memory"))
+    }
+
+    fn oom_kills(&self) -> Option<u64> {
+        read_oom_kill_counter(self.root.join("vmstat"))
+    }
+
+    fn cgroup_oom_kills(&self, cgroup: &str) -> Option<u64> {
+        let dir = self
+            .root
+            .join("cgroup")
+            .join(cgroup.trim_start_matches('/'));
+        read_oom_kill_counter(dir.join("memory.events"))
+    }
+}
+
+impl ProcessSource for FixtureSource {
+    fn processes(&self) -> Result<Vec<ProcessInfo>> {
+        let mut processes = ProcessInfo::all_from_dir(&self.root)?;
+        ActiveSessions::read_from(&self.root.join("sessions")).mark(&mut processes);
+        Ok(processes)
+    }
+
+    fn set_oom_score_adj(&self, pid: i32, adj: i32) -> Result<()> {
+        let path = self.root.join(pid.to_string()).join("oom_score_adj");
+        if !path.exists() {
+            anyhow::bail!("No such process {pid}");
+        }
+        fs::write(&path, format!("{adj}\n"))
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    fn is_alive(&self, process: &ProcessInfo) -> bool {
+        procfs::process::Process::new_with_root(self.root.join(process.pid.to_string()))
+            .and_then(|p| p.stat())
+            .is_ok_and(|stat| stat.starttime == process.start_ticks)
+    }
+
+    fn cgroup_dir(&self, process: &ProcessInfo) -> Option<PathBuf> {
+        let path = process.cgroup.trim_start_matches('/');
+        (!path.is_empty()).then(|| self.root.join("cgroup").join(path))
+    }
+}
+
+/// Boot time of fixture trees, in seconds since the Unix epoch
+const FIXTURE_BOOT_TIME: u64 = 1_600_000_000;
+
+/// A /proc/[pid]/stat line carrying the fields ProcessInfo reads
+fn stat_line(process: &ProcessInfo) -> String {
+    // Fields 4..=52 of stat(5); only ppid, pgrp, session, tty_nr, tpgid,
+    // starttime (field 22) and rss (field 24) matter here
+    let mut fields = ["0"; 49].map(String::from);
+    fields[0] = process.ppid.to_string();
+    fields[1] = process.pgrp.to_string();
+    fields[2] = process.session.to_string();
+    fields[3] = process.tty_nr.to_string();
+    fields[4] = process.tpgid.to_string();
+    fields[18] = (process.start_time.saturating_sub(FIXTURE_BOOT_TIME)
+        * procfs::ticks_per_second())
+    .to_string();
+    fields[20] = (process.rss_kb * 1024 / procfs::page_size()).to_string();
+
+    format!(
+        "{} ({}) {} {}\n",
+        process.pid,
+        process.name,
+        process.state,
+        fields.join(" ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monitor::unix_now;
+
+    fn process(pid: i32, name: &str, rss_kb: u64) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            name: name.to_string(),
+            cmdline: format!("/usr/bin/{name} --flag"),
+            rss_kb,
+            oom_score: 500,
+            oom_score_adj: 100,
+            uid: 1000,
+            state: 'S',
+            is_zombie: false,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_fixture_parent_group_exe_and_cgroup() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = FixtureSource::new(dir.path());
+        let process = ProcessInfo {
+            ppid: 7,
+            pgrp: 42,
+            session: 7,
+            exe: "/usr/bin/hog".to_string(),
+            cgroup: "/system.slice/hog.service".to_string(),
+            ..process(42, "hog", 4096)
+        };
+        source.write_process(&process).unwrap();
+
+        let read = &source.processes().unwrap()[0];
+        assert_eq!(read.ppid, 7);
+        assert_eq!((read.pgrp, read.session), (42, 7));
+        assert_eq!(read.exe, "/usr/bin/hog");
+        assert_eq!(read.cgroup, "/system.slice/hog.service");
+        assert_eq!(read.age(), None);
+    }
+
+    #[test]
+    fn test_fixture_start_time() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = FixtureSource::new(dir.path());
+        let start_time = unix_now() - 300;
+        let process = process(42, "hog", 4096).with_start_time(start_time, 0);
+        source.write_process(&process).unwrap();
+
+        let read = &source.processes().unwrap()[0];
+        assert_eq!(read.start_time, start_time);
+        assert_eq!(
+            read.start_ticks,
+            (start_time - FIXTURE_BOOT_TIME) * procfs::ticks_per_second()
+        );
+        assert!((300..310).contains(&read.age_secs));
+
+        // The PID given to a later process is another process
+        assert!(source.is_alive(read));
+        let reused = process.with_start_time(start_time + 60, 0);
+        source.write_process(&reused).unwrap();
+        assert!(!source.is_alive(read));
+        assert!(source.is_alive(&source.processes().unwrap()[0]));
+    }
+
+    #[test]
+    fn test_fixture_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = FixtureSource::new(dir.path());
+
+        let meminfo = MemInfo {
+            mem_total: 16_000_000,
+            mem_available: 800_000,
+            swap_total: 0,
+            swap_free: 0,
+        };
+        source.write_meminfo(&meminfo).unwrap();
+        source
+            .write_process(&process(42, "hog", 4_096_000))
+            .unwrap();
+        source.write_process(&process(43, "idle", 8_192)).unwrap();
+
+        let read = source.meminfo().unwrap();
+        assert_eq!(read.mem_total, 16_000_000);
+        assert_eq!(read.mem_available, 800_000);
+
+        assert_eq!(source.pressure(), None);
+        let pressure = MemoryPressure {
+            some_avg10: 25.5,
+            full_avg10: 10.0,
+            ..MemoryPressure::default()
+        };
+        source.write_pressure(&pressure).unwrap();
+        assert_eq!(source.pressure(), Some(pressure));
+
+        let mut processes = source.processes().unwrap();
+        processes.sort_by_key(|p| p.pid);
+        assert_eq!(processes.len(), 2);
+
+        let hog = &processes[0];
+        assert_eq!(hog.pid, 42);
+        assert_eq!(hog.name, "hog");
+        assert_eq!(hog.cmdline, "/usr/bin/hog --flag");
+        assert_eq!(hog.rss_kb, 4_096_000);
+        assert_eq!(hog.oom_score, 500);
+        assert_eq!(hog.oom_score_adj, 100);
+        assert_eq!(hog.uid, 1000);
+        assert_eq!(hog.state, 'S');
+    }
+
+    #[test]
+    fn test_fixture_remove_process() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = FixtureSource::new(dir.path());
+        let hog = process(42, "hog", 4096);
+        source.write_process(&hog).unwrap();
+        assert!(source.is_alive(&hog));
+
+        source.remove_process(42).unwrap();
+        assert!(source.processes().unwrap().is_empty());
+        assert!(!source.is_alive(&hog));
+    }
+
+    #[test]
+    fn test_fixture_cgroup_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = FixtureSource::new(dir.path());
+        let hog = process(42, "hog", 4096).with_cgroup("/system.slice/hog.service".to_string());
+
+        assert_eq!(
+            source.cgroup_dir(&hog),
+            Some(dir.path().join("cgroup/system.slice/hog.service"))
+        );
+        assert_eq!(source.cgroup_dir(&process(43, "idle", 8)), None);
+        assert_eq!(
+            source.cgroup_dir(&process(44, "init", 8).with_cgroup("/".to_string())),
+            None
+        );
+    }
+
+    #[test]
+    fn test_procfs_source_reads_live_system() {
+        let source = ProcfsSource;
+        assert!(source.meminfo().unwrap().mem_total > 0);
+        let own_pid = std::process::id() as i32;
+        let processes = source.processes().unwrap();
+        let own = processes.iter().find(|p| p.pid == own_pid).unwrap();
+        assert!(source.is_alive(own));
+        let reused = ProcessInfo {
+            start_ticks: own.start_ticks + 1,
+            ..own.clone()
+        };
+        assert!(!source.is_alive(&reused));
+    }
+}
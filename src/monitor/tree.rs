@@ -0,0 +1,178 @@
+// Process tree: processes grouped into applications by parent PID
+//
+// An application is the subtree below a root process, bounded at session
+// and cgroup boundaries: a browser with its renderers, or `make -j` with
+// its compilers, but not the login shell `make` was started from. Shells
+// with job control start each job in a process group of its own, which is
+// where the shell's subtree ends.
+
+use super::ProcessInfo;
+use std::collections::HashMap;
+
+/// PID of kthreadd, the parent of all kernel threads
+const KTHREADD_PID: i32 = 2;
+
+/// Upper bound on the depth of a process tree walk, in case of PID reuse
+/// producing a cycle in a snapshot
+const MAX_DEPTH: usize = 1024;
+
+/// Processes by PID, for walking up the tree
+pub struct ProcessTree<'a> {
+    by_pid: HashMap<i32, &'a ProcessInfo>,
+}
+
+impl<'a> ProcessTree<'a> {
+    /// Build the tree from a process list; processes whose parent is not in
+    /// the list start an application of their own
+    pub fn new(processes: &'a [ProcessInfo]) -> Self {
+        Self {
+            by_pid: processes.iter().map(|p| (p.pid, p)).collect(),
+        }
+    }
+
+    /// Whether a process starts an application rather than belonging to
+    /// its parent's: its parent is init, kthreadd or unknown, in another
+    /// session or cgroup, or a session leader (a shell) that started the
+    /// process as a job in another process group
+    pub fn is_app_root(&self, process: &ProcessInfo) -> bool {
+        if process.ppid <= 1 || process.ppid == KTHREADD_PID {
+            return true;
+        }
+        self.by_pid.get(&process.ppid).is_none_or(|parent| {
+            let job = parent.session == parent.pid && parent.pgrp != process.pgrp;
+            job || parent.session != process.session || parent.cgroup != process.cgroup
+        })
+    }
+
+    /// PID of the root of the application a process belongs to
+    pub fn app_root(&self, process: &'a ProcessInfo) -> i32 {
+        let mut current = process;
+        for _ in 0..MAX_DEPTH {
+            if self.is_app_root(current) {
+                break;
+            }
+            match self.by_pid.get(&current.ppid) {
+                Some(parent) => current = parent,
+                None => break,
+            }
+        }
+        current.pid
+    }
+
+    /// Members of each application by root PID, root first
+    pub fn applications(&self) -> HashMap<i32, Vec<&'a ProcessInfo>> {
+        let mut applications: HashMap<i32, Vec<&'a ProcessInfo>> = HashMap::new();
+        for &process in self.by_pid.values() {
+            applications
+                .entry(self.app_root(process))
+                .or_default()
+                .push(process);
+        }
+        for (root, members) in &mut applications {
+            members.sort_by_key(|p| (p.pid != *root, p.pid));
+        }
+        applications
+    }
+}
+
+/// The root of an application standing for all of it: RSS and oom_score
+/// are summed over `members`, which include the root
+pub fn aggregate(root: &ProcessInfo, members: &[&ProcessInfo]) -> ProcessInfo {
+    let oom_score: i64 = members.iter().map(|p| i64::from(p.oom_score)).sum();
+    ProcessInfo {
+        rss_kb: members.iter().map(|p| p.rss_kb).sum(),
+        oom_score: i32::try_from(oom_score).unwrap_or(i32::MAX),
+        tree_size: members.len(),
+        ..root.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(pid: i32, ppid: i32, pgrp: i32, cgroup: &str, rss_kb: u64) -> ProcessInfo {
+        // Sessions are led by the first process of the fixture's groups
+        let session = pgrp - pgrp % 100;
+        ProcessInfo {
+            pid,
+            ppid,
+            pgrp,
+            session,
+            name: format!("p{pid}"),
+            cgroup: cgroup.to_string(),
+            rss_kb,
+            oom_score: 10,
+            ..Default::default()
+        }
+    }
+
+    fn tree_processes() -> Vec<ProcessInfo> {
+        let term = "/user.slice/user-1000.slice/session-2.scope";
+        let chrome = "/user.slice/user-1000.slice/user@1000.service/app.slice/app-chrome.scope";
+        vec![
+            // Login shell, a session leader, running make -j with two
+            // compilers as a job in process group 110
+            process(100, 1, 100, term, 5_000),
+            process(110, 100, 110, term, 20_000),
+            process(111, 110, 110, term, 300_000),
+            process(112, 110, 110, term, 400_000),
+            // Browser started by the user's systemd, a session leader
+            // with renderers in its process group
+            process(200, 50, 200, chrome, 500_000),
+            process(201, 200, 200, chrome, 200_000),
+            process(202, 201, 200, chrome, 200_000),
+            // Child that moved to its own scope
+            process(300, 200, 200, "/user.slice/app-other.scope", 1_000),
+        ]
+    }
+
+    #[test]
+    fn test_app_roots_stop_at_boundaries() {
+        let processes = tree_processes();
+        let tree = ProcessTree::new(&processes);
+        let roots: Vec<(i32, i32)> = processes
+            .iter()
+            .map(|p| (p.pid, tree.app_root(p)))
+            .collect();
+        assert_eq!(
+            roots,
+            [
+                (100, 100),
+                (110, 110),
+                (111, 110),
+                (112, 110),
+                (200, 200),
+                (201, 200),
+                (202, 200),
+                (300, 300),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_applications_and_aggregate() {
+        let processes = tree_processes();
+        let tree = ProcessTree::new(&processes);
+        let applications = tree.applications();
+        assert_eq!(applications.len(), 4);
+
+        let chrome = &applications[&200];
+        assert_eq!(
+            chrome.iter().map(|p| p.pid).collect::<Vec<_>>(),
+            [200, 201, 202]
+        );
+        let app = aggregate(chrome[0], chrome);
+        assert_eq!(app.pid, 200);
+        assert_eq!(app.rss_kb, 900_000);
+        assert_eq!(app.oom_score, 30);
+        assert_eq!(app.tree_size, 3);
+    }
+
+    #[test]
+    fn test_unknown_parent_is_root() {
+        let processes = vec![process(110, 109, 110, "/a.scope", 1)];
+        let tree = ProcessTree::new(&processes);
+        assert_eq!(tree.app_root(&processes[0]), 110);
+    }
+}
@@ -0,0 +1,109 @@
+// User names from /etc/passwd, group members from /etc/group
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+
+/// Location of the user database
+pub const PASSWD_PATH: &str = "/etc/passwd";
+
+/// Location of the group database
+pub const GROUP_PATH: &str = "/etc/group";
+
+/// User names by UID
+#[derive(Debug, Clone, Default)]
+pub struct UserNames(HashMap<u32, String>);
+
+impl UserNames {
+    /// Read the user database, empty if it cannot be read
+    pub fn read() -> Self {
+        fs::read_to_string(PASSWD_PATH)
+            .map(|content| Self::parse(&content))
+            .unwrap_or_default()
+    }
+
+    /// Parse passwd(5) lines "name:password:uid:..."
+    pub fn parse(content: &str) -> Self {
+        Self(
+            content
+                .lines()
+                .filter_map(|line| {
+                    let mut fields = line.split(':');
+                    let name = fields.next()?;
+                    let uid = fields.nth(1)?.parse().ok()?;
+                    Some((uid, name.to_string()))
+                })
+                .collect(),
+        )
+    }
+
+    /// Name of `uid`, or the number itself when it has no entry (as ps does)
+    pub fn name(&self, uid: u32) -> String {
+        self.0.get(&uid).cloned().unwrap_or_else(|| uid.to_string())
+    }
+}
+
+/// UIDs of the members of `group`: users whose primary group it is and
+/// users listed in the group database. Empty if the group does not exist
+pub fn group_members(group: &str) -> HashSet<u32> {
+    let groups = fs::read_to_string(GROUP_PATH).unwrap_or_default();
+    let passwd = fs::read_to_string(PASSWD_PATH).unwrap_or_default();
+    parse_group_members(group, &groups, &passwd)
+}
+
+/// Members of `group` from group(5) lines "name:password:gid:user,user"
+/// and passwd(5) lines "name:password:uid:gid:..."
+pub fn parse_group_members(group: &str, groups: &str, passwd: &str) -> HashSet<u32> {
+    let Some((gid, listed)) = groups.lines().find_map(|line| {
+        let mut fields = line.split(':');
+        if fields.next()? != group {
+            return None;
+        }
+        let gid = fields.nth(1)?;
+        let listed: Vec<&str> = fields.next().unwrap_or("").split(',').collect();
+        Some((gid, listed))
+    }) else {
+        return HashSet::new();
+    };
+
+    passwd
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()?;
+            let uid: u32 = fields.nth(1)?.parse().ok()?;
+            let primary = fields.next()?;
+            (primary == gid || listed.contains(&name)).then_some(uid)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_names() {
+        let users = UserNames::parse(
+            "root:x:0:0:root:/root:/bin/bash\npostgres:x:70:70::/var/lib/pgsql:/bin/sh\nbroken\n",
+        );
+        assert_eq!(users.name(0), "root");
+        assert_eq!(users.name(70), "postgres");
+        assert_eq!(users.name(1234), "1234");
+    }
+
+    #[test]
+    fn test_group_members() {
+        let passwd = "root:x:0:0::/root:/bin/sh\nalice:x:1000:1000::/home/alice:/bin/sh\nbob:x:1001:100::/home/bob:/bin/sh\ncarol:x:1002:1002::/home/carol:/bin/sh\n";
+        let groups = "root:x:0:\nusers:x:100:\nstudents:x:2000:alice,carol\n";
+        let members = |group| {
+            let mut uids: Vec<u32> = parse_group_members(group, groups, passwd)
+                .into_iter()
+                .collect();
+            uids.sort_unstable();
+            uids
+        };
+        assert_eq!(members("students"), [1000, 1002]);
+        assert_eq!(members("users"), [1001]);
+        assert!(members("staff").is_empty());
+    }
+}
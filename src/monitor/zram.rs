@@ -0,0 +1,199 @@
+// zram swap accounting from /proc/swaps and /sys/block/zram*/mm_stat
+//
+// zram swap lives in RAM: every page swapped out to it still costs its
+// compressed size in memory. SwapFree therefore overstates the headroom
+// left, and with zram as the only swap memory can run out while swap
+// looks fine.
+
+use super::MemInfo;
+use std::fs;
+use std::path::Path;
+
+/// Path of the list of active swap areas
+pub const PROC_SWAPS_PATH: &str = "/proc/swaps";
+
+/// Directory of block devices in sysfs
+pub const SYS_BLOCK_PATH: &str = "/sys/block";
+
+/// One zram device used as swap. Sizes in KiB
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZramDevice {
+    /// Device name, e.g. zram0
+    pub name: String,
+    /// Swap size and used swap, from /proc/swaps
+    pub swap_total: u64,
+    pub swap_used: u64,
+    /// Uncompressed size of the data stored, from mm_stat
+    pub orig_data: u64,
+    /// RAM used by the device, including allocator overhead, from mm_stat
+    pub mem_used: u64,
+}
+
+/// All zram devices used as swap
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ZramSwap {
+    pub devices: Vec<ZramDevice>,
+}
+
+impl ZramSwap {
+    /// zram swap of the running system, None without any
+    pub fn read() -> Option<Self> {
+        Self::read_from(PROC_SWAPS_PATH, SYS_BLOCK_PATH)
+    }
+
+    /// zram swap listed in a file in /proc/swaps format, with the mm_stat
+    /// of each device under `sys_block`
+    pub fn read_from(proc_swaps: impl AsRef<Path>, sys_block: impl AsRef<Path>) -> Option<Self> {
+        let swaps = fs::read_to_string(proc_swaps).ok()?;
+        let devices: Vec<ZramDevice> = swaps
+            .lines()
+            .skip(1)
+            .filter_map(|line| {
+                // Filename Type Size Used Priority
+                let fields: Vec<&str> = line.split_whitespace().collect();
+                let name = fields.first()?.strip_prefix("/dev/")?;
+                if !name.starts_with("zram") {
+                    return None;
+                }
+                let mm_stat =
+                    fs::read_to_string(sys_block.as_ref().join(name).join("mm_stat")).ok();
+                let (orig_data, mem_used) = mm_stat
+                    .as_deref()
+                    .and_then(parse_mm_stat)
+                    .unwrap_or_default();
+                Some(ZramDevice {
+                    name: name.to_string(),
+                    swap_total: fields.get(2)?.parse().ok()?,
+                    swap_used: fields.get(3)?.parse().ok()?,
+                    orig_data,
+                    mem_used,
+                })
+            })
+            .collect();
+        (!devices.is_empty()).then_some(Self { devices })
+    }
+
+    /// Total size of zram swap in KiB
+    pub fn swap_total(&self) -> u64 {
+        self.devices.iter().map(|d| d.swap_total).sum()
+    }
+
+    /// Used zram swap in KiB
+    pub fn swap_used(&self) -> u64 {
+        self.devices.iter().map(|d| d.swap_used).sum()
+    }
+
+    /// RAM in KiB taken by the data swapped out to zram
+    pub fn mem_used(&self) -> u64 {
+        self.devices.iter().map(|d| d.mem_used).sum()
+    }
+
+    /// Memory information with zram left out of swap, leaving only swap on
+    /// disk. The RAM zram uses is already missing from MemAvailable
+    pub fn exclude_from(&self, meminfo: &MemInfo) -> MemInfo {
+        let swap_total = self.swap_total();
+        let swap_free = swap_total.saturating_sub(self.swap_used());
+        MemInfo {
+            swap_total: meminfo.swap_total.saturating_sub(swap_total),
+            swap_free: meminfo.swap_free.saturating_sub(swap_free),
+            ..*meminfo
+        }
+    }
+}
+
+impl std::fmt::Display for ZramSwap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let size = MemInfo::format_size;
+        let orig_data: u64 = self.devices.iter().map(|d| d.orig_data).sum();
+        write!(
+            f,
+            "zram swap: {} of {} used, holding {} in {} of RAM",
+            size(self.swap_used()),
+            size(self.swap_total()),
+            size(orig_data),
+            size(self.mem_used())
+        )?;
+        if self.mem_used() > 0 {
+            write!(f, " ({:.1}x)", orig_data as f64 / self.mem_used() as f64)?;
+        }
+        Ok(())
+    }
+}
+
+/// Uncompressed data size and RAM used, in KiB, from an mm_stat line:
+/// orig_data_size compr_data_size mem_used_total ... (bytes)
+fn parse_mm_stat(content: &str) -> Option<(u64, u64)> {
+    let fields: Vec<u64> = content
+        .split_whitespace()
+        .take(3)
+        .map(str::parse)
+        .collect::<Result<_, _>>()
+        .ok()?;
+    match fields[..] {
+        [orig_data, _, mem_used] => Some((orig_data / 1024, mem_used / 1024)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_zram_swap() {
+        let dir = tempfile::tempdir().unwrap();
+        let swaps = dir.path().join("swaps");
+        fs::write(
+            &swaps,
+            "Filename\t\t\t\tType\t\tSize\t\tUsed\t\tPriority\n\
+             /dev/zram0                              partition\t8388604\t\t2097152\t\t100\n\
+             /swapfile                               file\t\t4194300\t\t0\t\t-2\n",
+        )
+        .unwrap();
+        fs::create_dir_all(dir.path().join("block/zram0")).unwrap();
+        fs::write(
+            dir.path().join("block/zram0/mm_stat"),
+            "2147483648 536870912 629145600 0 629145600 1000 0 0 0\n",
+        )
+        .unwrap();
+
+        let zram = ZramSwap::read_from(&swaps, dir.path().join("block")).unwrap();
+        assert_eq!(zram.devices.len(), 1);
+        assert_eq!(zram.swap_total(), 8_388_604);
+        assert_eq!(zram.swap_used(), 2_097_152);
+        assert_eq!(zram.mem_used(), 614_400);
+        assert_eq!(
+            zram.to_string(),
+            "zram swap: 2.00 GiB of 8.00 GiB used, holding 2.00 GiB in 600.00 MiB of RAM (3.4x)"
+        );
+
+        // Only the swap file is left
+        let meminfo = MemInfo {
+            mem_total: 16_000_000,
+            mem_available: 1_000_000,
+            swap_total: 12_582_904,
+            swap_free: 10_485_752,
+        };
+        let disk_only = zram.exclude_from(&meminfo);
+        assert_eq!(disk_only.swap_total, 4_194_300);
+        assert_eq!(disk_only.swap_free, 4_194_300);
+        assert_eq!(disk_only.mem_available, 1_000_000);
+    }
+
+    #[test]
+    fn test_no_zram_swap() {
+        let dir = tempfile::tempdir().unwrap();
+        let swaps = dir.path().join("swaps");
+        fs::write(
+            &swaps,
+            "Filename\tType\tSize\tUsed\tPriority\n/swapfile file 4194300 0 -2\n",
+        )
+        .unwrap();
+        assert_eq!(ZramSwap::read_from(&swaps, dir.path()), None);
+        assert_eq!(
+            ZramSwap::read_from(dir.path().join("none"), dir.path()),
+            None
+        );
+        assert_eq!(parse_mm_stat("12 34"), None);
+    }
+}
@@ -114,9 +114,17 @@ impl HookValidator {
 /// - OOM_GUARD_PID: Process ID of the killed process
 /// - OOM_GUARD_NAME: Name of the killed process
 /// - OOM_GUARD_CMDLINE: Command line of the killed process
+/// - OOM_GUARD_UNIT: systemd service or scope of the killed process, if any
+/// - OOM_GUARD_CONTAINER: Container ID, if any
+/// - OOM_GUARD_RUNTIME: Container runtime (docker, containerd, cri-o, podman)
+/// - OOM_GUARD_POD: Kubernetes pod UID, if any
+/// - OOM_GUARD_QOS: Pod QoS class (Guaranteed, Burstable, BestEffort)
 /// - OOM_GUARD_UID: User ID of the process owner
 /// - OOM_GUARD_RSS: Resident Set Size in KiB
 /// - OOM_GUARD_SCORE: OOM score of the process
+/// - OOM_GUARD_AGE: Seconds since the process started, empty if unknown
+/// - OOM_GUARD_START_TIME: Start time in seconds since the Unix epoch, empty if unknown
+/// - OOM_GUARD_REASON: Why the process was selected
 pub struct HookEnvironment;
 
 impl HookEnvironment {
@@ -125,9 +133,17 @@ impl HookEnvironment {
             "OOM_GUARD_PID",
             "OOM_GUARD_NAME",
             "OOM_GUARD_CMDLINE",
+            "OOM_GUARD_UNIT",
+            "OOM_GUARD_CONTAINER",
+            "OOM_GUARD_RUNTIME",
+            "OOM_GUARD_POD",
+            "OOM_GUARD_QOS",
             "OOM_GUARD_UID",
             "OOM_GUARD_RSS",
             "OOM_GUARD_SCORE",
+            "OOM_GUARD_AGE",
+            "OOM_GUARD_START_TIME",
+            "OOM_GUARD_REASON",
         ]
     }
 
@@ -136,9 +152,17 @@ impl HookEnvironment {
              - OOM_GUARD_PID: Process ID of the killed process\n\
              - OOM_GUARD_NAME: Name of the killed process\n\
              - OOM_GUARD_CMDLINE: Command line of the killed process\n\
+             - OOM_GUARD_UNIT: systemd service or scope of the killed process, if any\n\
+             - OOM_GUARD_CONTAINER: Container ID, if any\n\
+             - OOM_GUARD_RUNTIME: Container runtime (docker, containerd, cri-o, podman)\n\
+             - OOM_GUARD_POD: Kubernetes pod UID, if any\n\
+             - OOM_GUARD_QOS: Pod QoS class (Guaranteed, Burstable, BestEffort)\n\
              - OOM_GUARD_UID: User ID of the process owner\n\
              - OOM_GUARD_RSS: Resident Set Size in KiB\n\
-             - OOM_GUARD_SCORE: OOM score of the process"
+             - OOM_GUARD_SCORE: OOM score of the process\n\
+             - OOM_GUARD_AGE: Seconds since the process started, empty if unknown\n\
+             - OOM_GUARD_START_TIME: Start time in seconds since the Unix epoch, empty if unknown\n\
+             - OOM_GUARD_REASON: Why the process was selected"
             .to_string()
     }
 }
@@ -192,13 +216,17 @@ mod tests {
     #[test]
     fn test_hook_environment_variables() {
         let vars = HookEnvironment::get_variable_names();
-        assert_eq!(vars.len(), 6);
+        assert_eq!(vars.len(), 14);
         assert!(vars.contains(&"OOM_GUARD_PID"));
         assert!(vars.contains(&"OOM_GUARD_NAME"));
         assert!(vars.contains(&"OOM_GUARD_CMDLINE"));
+        assert!(vars.contains(&"OOM_GUARD_UNIT"));
+        assert!(vars.contains(&"OOM_GUARD_QOS"));
         assert!(vars.contains(&"OOM_GUARD_UID"));
         assert!(vars.contains(&"OOM_GUARD_RSS"));
         assert!(vars.contains(&"OOM_GUARD_SCORE"));
+        assert!(vars.contains(&"OOM_GUARD_AGE"));
+        assert!(vars.contains(&"OOM_GUARD_REASON"));
     }
 
     #[test]
@@ -207,6 +235,7 @@ mod tests {
         assert!(desc.contains("OOM_GUARD_PID"));
         assert!(desc.contains("OOM_GUARD_NAME"));
         assert!(desc.contains("OOM_GUARD_CMDLINE"));
+        assert!(desc.contains("OOM_GUARD_UNIT"));
         assert!(desc.contains("OOM_GUARD_UID"));
         assert!(desc.contains("OOM_GUARD_RSS"));
         assert!(desc.contains("OOM_GUARD_SCORE"));
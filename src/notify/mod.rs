@@ -1,5 +1,6 @@
 pub mod hooks;
 
+use crate::killer::KillInfo;
 use anyhow::Result;
 use log::{error, info};
 use std::process::Command;
@@ -41,37 +42,21 @@ impl NotificationManager {
         }
     }
 
-    pub fn send_pre_kill_notification(
-        &self,
-        pid: i32,
-        name: &str,
-        cmdline: &str,
-        uid: u32,
-        rss_kb: u64,
-        score: i32,
-    ) -> Result<()> {
+    pub fn send_pre_kill_notification(&self, kill: &KillInfo) -> Result<()> {
         if let Some(script) = &self.pre_kill_script {
-            info!("Executing pre-kill script: {script} for process {pid} ({name})");
-            if let Err(e) = self.execute_script(script, pid, name, cmdline, uid, rss_kb, score) {
+            info!("Executing pre-kill script: {script} for {}", kill.label);
+            if let Err(e) = self.execute_script(script, kill) {
                 error!("Failed to execute pre-kill script: {e}");
             }
         }
         Ok(())
     }
 
-    pub fn send_post_kill_notification(
-        &self,
-        pid: i32,
-        name: &str,
-        cmdline: &str,
-        uid: u32,
-        rss_kb: u64,
-        score: i32,
-    ) -> Result<()> {
+    pub fn send_post_kill_notification(&self, kill: &KillInfo) -> Result<()> {
         // Execute post-kill script
         if let Some(script) = &self.post_kill_script {
-            info!("Executing post-kill script: {script} for process {pid} ({name})");
-            if let Err(e) = self.execute_script(script, pid, name, cmdline, uid, rss_kb, score) {
+            info!("Executing post-kill script: {script} for {}", kill.label);
+            if let Err(e) = self.execute_script(script, kill) {
                 error!("Failed to execute post-kill script: {e}");
             }
         }
@@ -79,7 +64,7 @@ impl NotificationManager {
         // Send D-Bus notification
         #[cfg(feature = "dbus-notify")]
         if self.enable_dbus {
-            if let Err(e) = self.send_dbus_notification(pid, name, rss_kb) {
+            if let Err(e) = self.send_dbus_notification(kill) {
                 error!("Failed to send D-Bus notification: {}", e);
             }
         }
@@ -92,28 +77,57 @@ impl NotificationManager {
         Ok(())
     }
 
-    #[allow(clippy::too_many_arguments)]
-    fn execute_script(
-        &self,
-        script_path: &str,
-        pid: i32,
-        name: &str,
-        cmdline: &str,
-        uid: u32,
-        rss_kb: u64,
-        score: i32,
-    ) -> Result<()> {
-        let safe_name = sanitize_env_value(name);
-        let safe_cmdline = sanitize_env_value(cmdline);
-
-        let output = Command::new(script_path)
-            .env("OOM_GUARD_PID", pid.to_string())
-            .env("OOM_GUARD_NAME", &safe_name)
-            .env("OOM_GUARD_CMDLINE", &safe_cmdline)
-            .env("OOM_GUARD_UID", uid.to_string())
-            .env("OOM_GUARD_RSS", rss_kb.to_string())
-            .env("OOM_GUARD_SCORE", score.to_string())
-            .output()?;
+    /// Raise an operator alert that is not tied to a single killed process
+    pub fn send_alert(&self, summary: &str, message: &str) {
+        error!("{summary}: {message}");
+
+        #[cfg(feature = "dbus-notify")]
+        if self.enable_dbus {
+            if let Err(e) = Notification::new()
+                .summary(summary)
+                .body(message)
+                .icon("dialog-error")
+                .timeout(Timeout::Never)
+                .show()
+            {
+                error!("Failed to send D-Bus alert: {e}");
+            }
+        }
+    }
+
+    /// Run an event hook script with the given OOM_GUARD_* variables
+    pub fn run_event_script(&self, script_path: &str, event: &str, vars: &[(&str, String)]) {
+        info!("Executing {event} script: {script_path}");
+
+        let mut command = Command::new(script_path);
+        command.env("OOM_GUARD_EVENT", event);
+        for (key, value) in vars {
+            command.env(key, sanitize_env_value(value));
+        }
+
+        match command.output() {
+            Ok(output) if output.status.success() => {
+                info!("Script {script_path} executed successfully");
+            }
+            Ok(output) => {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                error!(
+                    "Script {} failed with status {}: {}",
+                    script_path,
+                    output.status,
+                    stderr.trim()
+                );
+            }
+            Err(e) => error!("Failed to execute {event} script: {e}"),
+        }
+    }
+
+    fn execute_script(&self, script_path: &str, kill: &KillInfo) -> Result<()> {
+        let mut command = Command::new(script_path);
+        for (key, value) in kill.env_vars() {
+            command.env(key, sanitize_env_value(&value));
+        }
+        let output = command.output()?;
 
         if output.status.success() {
             info!("Script {script_path} executed successfully");
@@ -135,11 +149,12 @@ impl NotificationManager {
     }
 
     #[cfg(feature = "dbus-notify")]
-    fn send_dbus_notification(&self, pid: i32, name: &str, rss_kb: u64) -> Result<()> {
-        let rss_mb = rss_kb / 1024;
+    fn send_dbus_notification(&self, kill: &KillInfo) -> Result<()> {
         let message = format!(
-            "OOM Guard killed process:\nPID: {}\nName: {}\nRSS: {} MB",
-            pid, name, rss_mb
+            "OOM Guard killed {}\nRSS: {} MB\nReason: {}",
+            kill.label,
+            kill.rss_kb / 1024,
+            kill.reason
         );
 
         Notification::new()
@@ -149,7 +164,7 @@ impl NotificationManager {
             .timeout(Timeout::Milliseconds(6000))
             .show()?;
 
-        info!("D-Bus notification sent for process {} ({})", pid, name);
+        info!("D-Bus notification sent for {}", kill.label);
         Ok(())
     }
 }
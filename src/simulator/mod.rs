@@ -0,0 +1,387 @@
+// Policy simulator: synthetic memory scenarios run through the daemon's
+// decision logic on a virtual clock
+
+pub mod scenario;
+
+pub use scenario::{Expectation, Scenario, SimProcess};
+
+use crate::config::Config;
+use crate::daemon::replay::{ReplayEvent, SampleDriver};
+use crate::killer::KillStrategy;
+use crate::monitor::recording::Sample;
+use crate::monitor::{MemInfo, ProcessInfo};
+use anyhow::{bail, Result};
+use std::path::Path;
+use std::time::Duration;
+
+/// Outcome of one expectation
+#[derive(Debug, Clone)]
+pub struct ExpectationResult {
+    pub expectation: Expectation,
+    pub passed: bool,
+    pub detail: String,
+}
+
+/// Outcome of a simulation
+#[derive(Debug, Clone, Default)]
+pub struct SimulationReport {
+    pub events: Vec<ReplayEvent>,
+    /// Time memory and swap ran out completely; the simulation stops there
+    pub oom_at: Option<Duration>,
+    pub results: Vec<ExpectationResult>,
+}
+
+impl SimulationReport {
+    /// Whether every expectation held
+    pub fn passed(&self) -> bool {
+        self.results.iter().all(|r| r.passed)
+    }
+
+    /// Time of the first action taken against `pid`
+    fn first_action(&self, pid: i32) -> Option<&ReplayEvent> {
+        self.events.iter().find(|e| e.action.victim.pid == pid)
+    }
+}
+
+impl std::fmt::Display for SimulationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for event in &self.events {
+            writeln!(f, "{event}")?;
+        }
+        if let Some(at) = self.oom_at {
+            writeln!(f, "{:>9.1}s  out of memory", at.as_secs_f64())?;
+        }
+        for result in &self.results {
+            let status = if result.passed { "ok" } else { "FAILED" };
+            writeln!(
+                f,
+                "expect {}: {status} ({})",
+                result.expectation, result.detail
+            )?;
+        }
+        let failed = self.results.iter().filter(|r| !r.passed).count();
+        write!(
+            f,
+            "{} action(s), {} expectation(s), {failed} failed",
+            self.events.len(),
+            self.results.len()
+        )
+    }
+}
+
+/// State of a simulated process during a run
+#[derive(Debug)]
+struct Running<'a> {
+    process: &'a SimProcess,
+    /// RSS the process was held at by a freeze or throttle
+    held_rss_kb: Option<u64>,
+    killed: bool,
+}
+
+impl Running<'_> {
+    fn rss_at(&self, t: Duration) -> Option<u64> {
+        if self.killed {
+            return None;
+        }
+        self.held_rss_kb.or_else(|| self.process.rss_at(t))
+    }
+}
+
+/// Run a scenario through the decision logic
+///
+/// Terminating actions remove the victim; freezing and throttling stop its
+/// growth. All other memory is taken from the scenario.
+pub fn simulate(config: Config, scenario: &Scenario) -> SimulationReport {
+    let mut driver = SampleDriver::new(config);
+    let mut running: Vec<Running<'_>> = scenario
+        .processes
+        .iter()
+        .map(|process| Running {
+            process,
+            held_rss_kb: None,
+            killed: false,
+        })
+        .collect();
+    let mut report = SimulationReport::default();
+
+    let mut t = Duration::ZERO;
+    while t <= scenario.duration {
+        let sample = build_sample(scenario, &running, t);
+        if sample.meminfo.mem_available == 0 && sample.meminfo.swap_free == 0 {
+            report.oom_at = Some(t);
+            break;
+        }
+
+        for event in driver.step(sample) {
+            let action = &event.action;
+            let pids: Vec<i32> = std::iter::once(action.victim.pid)
+                .chain(action.members.iter().copied())
+                .collect();
+            for state in running.iter_mut().filter(|r| pids.contains(&r.process.pid)) {
+                match action.strategy {
+                    KillStrategy::Freeze | KillStrategy::Throttle => {
+                        state.held_rss_kb = state.rss_at(event.elapsed);
+                    }
+                    strategy if strategy.terminates() => state.killed = true,
+                    _ => {}
+                }
+            }
+            report.events.push(event);
+        }
+
+        t += scenario.step;
+    }
+
+    report.results = scenario
+        .expectations
+        .iter()
+        .map(|expectation| check(scenario, &report, expectation))
+        .collect();
+    report
+}
+
+/// Wall clock time the simulation starts at, in seconds since the Unix
+/// epoch, for the start times of simulated processes
+const SIM_EPOCH: u64 = 1_700_000_000;
+
+/// Memory and process table at time `t`
+///
+/// Used memory is the baseline plus the RSS of every live process; whatever
+/// does not fit in physical memory is assumed to be swapped out.
+fn build_sample(scenario: &Scenario, running: &[Running<'_>], t: Duration) -> Sample {
+    let processes: Vec<ProcessInfo> = running
+        .iter()
+        .filter_map(|r| {
+            let rss_kb = r.rss_at(t)?;
+            let p = r.process;
+            let score = (rss_kb * 1000 / scenario.mem_total_kb) as i32 + p.oom_score_adj;
+            let user = if p.user.is_empty() {
+                p.uid.to_string()
+            } else {
+                p.user.clone()
+            };
+            let process = ProcessInfo {
+                pid: p.pid,
+                ppid: scenario.process(&p.parent).map_or(1, |parent| parent.pid),
+                name: p.name.clone(),
+                cmdline: p.cmdline.clone(),
+                exe: p.exe.clone(),
+                rss_kb,
+                oom_score: score.clamp(0, 2000),
+                oom_score_adj: p.oom_score_adj,
+                uid: p.uid,
+                user,
+                state: 'S',
+                is_zombie: false,
+                ..ProcessInfo::default()
+            };
+            let start_time = (SIM_EPOCH + p.start.as_secs()).saturating_sub(p.age.as_secs());
+            Some(
+                process
+                    .with_start_time(start_time, SIM_EPOCH + t.as_secs())
+                    .with_cgroup(p.cgroup.clone()),
+            )
+        })
+        .collect();
+
+    let used_kb = scenario.baseline_kb + processes.iter().map(|p| p.rss_kb).sum::<u64>();
+    let swapped_kb = used_kb
+        .saturating_sub(scenario.mem_total_kb)
+        .min(scenario.swap_total_kb);
+
+    Sample {
+        elapsed: t,
+        time: SIM_EPOCH + t.as_secs(),
+        meminfo: MemInfo {
+            mem_total: scenario.mem_total_kb,
+            mem_available: scenario.mem_total_kb.saturating_sub(used_kb),
+            swap_total: scenario.swap_total_kb,
+            swap_free: scenario.swap_total_kb - swapped_kb,
+        },
+        pressure: None,
+        processes,
+    }
+}
+
+fn check(
+    scenario: &Scenario,
+    report: &SimulationReport,
+    expectation: &Expectation,
+) -> ExpectationResult {
+    let first_action = |name: &str| {
+        scenario
+            .process(name)
+            .and_then(|p| report.first_action(p.pid))
+    };
+    let describe = |event: Option<&ReplayEvent>| {
+        event.map_or_else(
+            || "never acted on".to_string(),
+            |e| format!("{} at {:.1}s", e.action.strategy, e.elapsed.as_secs_f64()),
+        )
+    };
+
+    let (passed, detail) = match expectation {
+        Expectation::ActedOn { name, from, to } => {
+            let event = first_action(name);
+            let passed = event.is_some_and(|e| (*from..=*to).contains(&e.elapsed));
+            (passed, describe(event))
+        }
+        Expectation::Untouched { name } => {
+            let event = first_action(name);
+            (event.is_none(), describe(event))
+        }
+        Expectation::NoOom => match report.oom_at {
+            Some(at) => (false, format!("ran out at {:.1}s", at.as_secs_f64())),
+            None => (true, "memory never ran out".to_string()),
+        },
+    };
+
+    ExpectationResult {
+        expectation: expectation.clone(),
+        passed,
+        detail,
+    }
+}
+
+/// Run a scenario file and print the report; fails if an expectation fails
+pub fn run_simulation(config: Config, path: &Path) -> Result<()> {
+    let scenario = Scenario::load(path)?;
+    let report = simulate(config, &scenario);
+    println!("{report}");
+
+    if !report.passed() {
+        bail!("Scenario {} did not meet its expectations", path.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(config: Config, scenario: &str) -> SimulationReport {
+        simulate(config, &Scenario::parse(scenario).unwrap())
+    }
+
+    const RUNAWAY: &str = "
+        memory 16GiB
+        swap 8GiB
+        baseline 2GiB
+        duration 120s
+        process A rss=500MiB grow=200MiB/s from=5s
+        process B rss=4GiB
+        expect A between 85s 95s
+        expect B untouched
+        expect no-oom
+    ";
+
+    #[test]
+    fn test_runaway_process_is_killed() {
+        let report = run(Config::default(), RUNAWAY);
+        assert!(report.passed(), "{report}");
+
+        let event = &report.events[0];
+        assert_eq!(event.action.victim.name, "A");
+        assert_eq!(event.action.strategy, KillStrategy::Graceful);
+        assert_eq!(report.events.len(), 1);
+    }
+
+    #[test]
+    fn test_example_scenario_passes() {
+        let scenario = Scenario::parse(include_str!("../../examples/scenarios/runaway.scenario"));
+        let report = simulate(Config::default(), &scenario.unwrap());
+        assert!(report.passed(), "{report}");
+    }
+
+    #[test]
+    fn test_steady_system_has_no_actions() {
+        let report = run(
+            Config::default(),
+            "memory 8GiB\nswap 2GiB\nprocess A rss=2GiB\nprocess B rss=1GiB\n",
+        );
+        assert!(report.events.is_empty());
+        assert_eq!(report.oom_at, None);
+        assert!(report.passed());
+    }
+
+    #[test]
+    fn test_oom_score_adj_protects_larger_process() {
+        let report = run(
+            Config::default(),
+            "
+            memory 10GiB
+            swap 1GiB
+            process db rss=9500MiB adj=-900
+            process cache rss=300MiB grow=100MiB/s
+            expect cache between 5s 20s
+            expect db untouched
+            ",
+        );
+        assert!(report.passed(), "{report}");
+    }
+
+    #[test]
+    fn test_kill_tree_takes_the_whole_application() {
+        // The compilers add up to more than the database, each is smaller
+        let scenario = "
+            memory 10GiB
+            swap 4GiB
+            process db rss=3GiB
+            process make rss=100MiB
+            process cc1 rss=1GiB grow=100MiB/s parent=make
+            process cc2 rss=1GiB grow=100MiB/s parent=make
+            process cc3 rss=1GiB grow=100MiB/s parent=make
+            expect make between 5s 40s
+            expect db untouched
+            expect no-oom
+            ";
+        let config = Config {
+            kill_tree: true,
+            sort_by_rss: true,
+            ..Config::default()
+        };
+        let report = run(config, scenario);
+        assert!(report.passed(), "{report}");
+        assert_eq!(report.events.len(), 1);
+        assert_eq!(report.events[0].action.members.len(), 3);
+        assert_eq!(report.events[0].action.victim.tree_size, 4);
+
+        // Without it, single processes are killed
+        let config = Config {
+            sort_by_rss: true,
+            ..Config::default()
+        };
+        let report = run(config, scenario);
+        assert!(!report.passed());
+        assert_ne!(report.events[0].action.victim.name, "make");
+        assert!(report.events[0].action.members.is_empty());
+    }
+
+    #[test]
+    fn test_unmet_expectation_fails() {
+        let report = run(
+            Config::default(),
+            "memory 8GiB\nswap 2GiB\nprocess A rss=1GiB\nexpect A between 0s 10s\n",
+        );
+        assert!(!report.passed());
+        assert_eq!(report.results[0].detail, "never acted on");
+    }
+
+    #[test]
+    fn test_growth_without_action_runs_out_of_memory() {
+        // Thresholds of zero never fire, so the hog exhausts memory and swap
+        let config = Config {
+            mem_threshold_warn: 0.0,
+            mem_threshold_kill: 0.0,
+            swap_threshold_warn: 0.0,
+            swap_threshold_kill: 0.0,
+            ..Config::default()
+        };
+        let report = run(
+            config,
+            "memory 4GiB\nswap 1GiB\nprocess A rss=1GiB grow=1GiB/s\nexpect no-oom\n",
+        );
+        assert_eq!(report.oom_at, Some(Duration::from_secs(4)));
+        assert!(!report.passed());
+    }
+}
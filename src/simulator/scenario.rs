@@ -0,0 +1,430 @@
+// Declarative description of synthetic memory scenarios
+//
+// One statement per line, `#` starts a comment:
+//
+//   memory 16GiB                 # physical memory (required)
+//   swap 8GiB                    # swap space (default: none)
+//   baseline 2GiB                # memory used by everything else
+//   duration 60s                 # simulated time (default: 60s)
+//   step 1s                      # time between checks (default: 1s)
+//   process A rss=500MiB grow=200MiB/s from=5s
+//   process B rss=4GiB adj=-500 cmdline="/usr/bin/db --serve" exe=/usr/bin/db
+//   process C rss=1GiB cgroup=/system.slice/batch.service user=batch
+//   process D rss=200MiB parent=C   # child of C (default parent: init)
+//   process E rss=1GiB age=3d     # already running for 3 days at the start
+//   expect A between 20s 40s     # A is the first action's victim in this window
+//   expect B untouched
+//   expect no-oom                # memory never runs out completely
+
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+use std::time::Duration;
+
+/// A simulated process
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimProcess {
+    pub name: String,
+    pub pid: i32,
+    /// Name of the parent process, empty for children of init
+    pub parent: String,
+    pub cmdline: String,
+    pub exe: String,
+    pub cgroup: String,
+    pub uid: u32,
+    /// User name, defaults to the UID
+    pub user: String,
+    pub oom_score_adj: i32,
+    /// RSS when the process appears, in KiB
+    pub rss_kb: u64,
+    /// Growth in KiB per second
+    pub growth_kb_per_sec: u64,
+    /// Time growth starts
+    pub grow_from: Duration,
+    /// Time the process appears
+    pub start: Duration,
+    /// Age of the process when it appears
+    pub age: Duration,
+    /// RSS at which growth stops, in KiB
+    pub max_rss_kb: Option<u64>,
+}
+
+impl SimProcess {
+    /// RSS at time `t`, or None before the process has started
+    pub fn rss_at(&self, t: Duration) -> Option<u64> {
+        if t < self.start {
+            return None;
+        }
+        let growing = t.saturating_sub(self.grow_from.max(self.start));
+        let grown = (self.growth_kb_per_sec as f64 * growing.as_secs_f64()) as u64;
+        let rss = self.rss_kb + grown;
+        Some(self.max_rss_kb.map_or(rss, |max| rss.min(max)))
+    }
+}
+
+/// An assertion about the outcome of a scenario
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expectation {
+    /// The process is acted on, for the first time, within the window
+    ActedOn {
+        name: String,
+        from: Duration,
+        to: Duration,
+    },
+    /// The process is never acted on
+    Untouched { name: String },
+    /// Memory and swap never run out completely
+    NoOom,
+}
+
+impl std::fmt::Display for Expectation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ActedOn { name, from, to } => write!(
+                f,
+                "{name} acted on between {:.1}s and {:.1}s",
+                from.as_secs_f64(),
+                to.as_secs_f64()
+            ),
+            Self::Untouched { name } => write!(f, "{name} untouched"),
+            Self::NoOom => write!(f, "memory never runs out"),
+        }
+    }
+}
+
+/// A synthetic scenario: machine size, processes and expectations
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scenario {
+    pub mem_total_kb: u64,
+    pub swap_total_kb: u64,
+    /// Memory used by everything outside the simulated processes, in KiB
+    pub baseline_kb: u64,
+    pub duration: Duration,
+    pub step: Duration,
+    pub processes: Vec<SimProcess>,
+    pub expectations: Vec<Expectation>,
+}
+
+/// First PID given to simulated processes without an explicit pid
+const FIRST_SIM_PID: i32 = 10_000;
+
+impl Scenario {
+    /// Load a scenario file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read scenario {}", path.display()))?;
+        Self::parse(&content).with_context(|| format!("Invalid scenario {}", path.display()))
+    }
+
+    /// Parse a scenario description
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut scenario = Self {
+            mem_total_kb: 0,
+            swap_total_kb: 0,
+            baseline_kb: 0,
+            duration: Duration::from_secs(60),
+            step: Duration::from_secs(1),
+            processes: Vec::new(),
+            expectations: Vec::new(),
+        };
+
+        for (index, line) in content.lines().enumerate() {
+            let line = line.split_once('#').map_or(line, |(code, _)| code).trim();
+            if line.is_empty() {
+                continue;
+            }
+            scenario
+                .parse_statement(line)
+                .with_context(|| format!("line {}", index + 1))?;
+        }
+
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    fn parse_statement(&mut self, line: &str) -> Result<()> {
+        let tokens = tokenize(line)?;
+        let (keyword, args) = tokens.split_first().context("empty statement")?;
+
+        match (keyword.as_str(), args) {
+            ("memory", [size]) => self.mem_total_kb = parse_size_kb(size)?,
+            ("swap", [size]) => self.swap_total_kb = parse_size_kb(size)?,
+            ("baseline", [size]) => self.baseline_kb = parse_size_kb(size)?,
+            ("duration", [time]) => self.duration = parse_duration(time)?,
+            ("step", [time]) => self.step = parse_duration(time)?,
+            ("process", [name, options @ ..]) => {
+                let pid = FIRST_SIM_PID + self.processes.len() as i32;
+                self.processes.push(parse_process(name, pid, options)?);
+            }
+            ("expect", [what]) if what == "no-oom" => self.expectations.push(Expectation::NoOom),
+            ("expect", [name, what]) if what == "untouched" => {
+                self.expectations
+                    .push(Expectation::Untouched { name: name.clone() });
+            }
+            ("expect", [name, what, from, to]) if what == "between" => {
+                self.expectations.push(Expectation::ActedOn {
+                    name: name.clone(),
+                    from: parse_duration(from)?,
+                    to: parse_duration(to)?,
+                });
+            }
+            _ => bail!("unknown statement \"{line}\""),
+        }
+
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.mem_total_kb == 0 {
+            bail!("missing \"memory\" statement");
+        }
+        if self.step.is_zero() {
+            bail!("step must be greater than zero");
+        }
+
+        for process in &self.processes {
+            if !process.parent.is_empty() && self.process(&process.parent).is_none() {
+                bail!(
+                    "process \"{}\" has unknown parent \"{}\"",
+                    process.name,
+                    process.parent
+                );
+            }
+        }
+
+        for expectation in &self.expectations {
+            let name = match expectation {
+                Expectation::ActedOn { name, .. } | Expectation::Untouched { name } => name,
+                Expectation::NoOom => continue,
+            };
+            if self.process(name).is_none() {
+                bail!("expectation refers to unknown process \"{name}\"");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Look up a simulated process by name
+    pub fn process(&self, name: &str) -> Option<&SimProcess> {
+        self.processes.iter().find(|p| p.name == name)
+    }
+}
+
+fn parse_process(name: &str, default_pid: i32, options: &[String]) -> Result<SimProcess> {
+    let mut process = SimProcess {
+        name: name.to_string(),
+        pid: default_pid,
+        parent: String::new(),
+        cmdline: name.to_string(),
+        exe: String::new(),
+        cgroup: String::new(),
+        uid: 1000,
+        user: String::new(),
+        oom_score_adj: 0,
+        rss_kb: 0,
+        growth_kb_per_sec: 0,
+        grow_from: Duration::ZERO,
+        start: Duration::ZERO,
+        age: Duration::ZERO,
+        max_rss_kb: None,
+    };
+
+    for option in options {
+        let (key, value) = option
+            .split_once('=')
+            .with_context(|| format!("expected key=value, got \"{option}\""))?;
+        match key {
+            "rss" => process.rss_kb = parse_size_kb(value)?,
+            "grow" => {
+                let rate = value
+                    .strip_suffix("/s")
+                    .with_context(|| format!("growth must be SIZE/s, got \"{value}\""))?;
+                process.growth_kb_per_sec = parse_size_kb(rate)?;
+            }
+            "from" => process.grow_from = parse_duration(value)?,
+            "start" => process.start = parse_duration(value)?,
+            "age" => process.age = parse_duration(value)?,
+            "max" => process.max_rss_kb = Some(parse_size_kb(value)?),
+            "pid" => process.pid = value.parse().context("invalid pid")?,
+            "parent" => process.parent = value.to_string(),
+            "uid" => process.uid = value.parse().context("invalid uid")?,
+            "user" => process.user = value.to_string(),
+            "adj" => process.oom_score_adj = value.parse().context("invalid adj")?,
+            "cmdline" => process.cmdline = value.to_string(),
+            "exe" => process.exe = value.to_string(),
+            "cgroup" => process.cgroup = value.to_string(),
+            _ => bail!("unknown process option \"{key}\""),
+        }
+    }
+
+    Ok(process)
+}
+
+/// Split a statement into whitespace-separated tokens; double quotes group
+/// words (`cmdline="/usr/bin/app --flag"`)
+fn tokenize(line: &str) -> Result<Vec<String>> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+
+    for c in line.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if quoted {
+        bail!("unterminated quote");
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    Ok(tokens)
+}
+
+/// Parse a size such as `512MiB`, `4G` or `2048` (KiB) into KiB
+pub fn parse_size_kb(s: &str) -> Result<u64> {
+    let split = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let value: f64 = number
+        .parse()
+        .with_context(|| format!("invalid size \"{s}\""))?;
+
+    let multiplier = match unit.to_ascii_lowercase().as_str() {
+        "" | "k" | "kb" | "kib" => 1.0,
+        "m" | "mb" | "mib" => 1024.0,
+        "g" | "gb" | "gib" => 1024.0 * 1024.0,
+        "t" | "tb" | "tib" => 1024.0 * 1024.0 * 1024.0,
+        _ => bail!("invalid size unit in \"{s}\""),
+    };
+
+    Ok((value * multiplier) as u64)
+}
+
+/// Parse a duration such as `500ms`, `5s`, `2m` or `1.5s`
+pub fn parse_duration(s: &str) -> Result<Duration> {
+    let split = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let value: f64 = number
+        .parse()
+        .with_context(|| format!("invalid duration \"{s}\""))?;
+
+    let seconds = match unit {
+        "ms" => value / 1000.0,
+        "" | "s" => value,
+        "m" | "min" => value * 60.0,
+        "h" => value * 3600.0,
+        "d" => value * 86400.0,
+        _ => bail!("invalid duration unit in \"{s}\""),
+    };
+
+    Ok(Duration::from_secs_f64(seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size_kb("2048").unwrap(), 2048);
+        assert_eq!(parse_size_kb("200MiB").unwrap(), 200 * 1024);
+        assert_eq!(parse_size_kb("4G").unwrap(), 4 * 1024 * 1024);
+        assert_eq!(parse_size_kb("1.5GiB").unwrap(), 1_572_864);
+        assert!(parse_size_kb("12parsecs").is_err());
+        assert!(parse_size_kb("MiB").is_err());
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_duration("5s").unwrap(), Duration::from_secs(5));
+        assert_eq!(parse_duration("2m").unwrap(), Duration::from_secs(120));
+        assert_eq!(
+            parse_duration("3d").unwrap(),
+            Duration::from_secs(3 * 24 * 3600)
+        );
+        assert!(parse_duration("5w").is_err());
+    }
+
+    #[test]
+    fn test_parse_scenario() {
+        let scenario = Scenario::parse(
+            r#"
+            # A grows until it is killed
+            memory 16GiB
+            swap 8GiB
+            duration 90s
+            process A rss=500MiB grow=200MiB/s from=5s
+            process B rss=4GiB adj=-500 cmdline="/usr/bin/db --serve"
+            expect A between 20s 60s
+            expect B untouched
+            expect no-oom
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(scenario.mem_total_kb, 16 * 1024 * 1024);
+        assert_eq!(scenario.swap_total_kb, 8 * 1024 * 1024);
+        assert_eq!(scenario.duration, Duration::from_secs(90));
+        assert_eq!(scenario.processes.len(), 2);
+
+        let a = scenario.process("A").unwrap();
+        assert_eq!(a.pid, FIRST_SIM_PID);
+        assert_eq!(a.growth_kb_per_sec, 200 * 1024);
+        assert_eq!(a.grow_from, Duration::from_secs(5));
+
+        let b = scenario.process("B").unwrap();
+        assert_eq!(b.cmdline, "/usr/bin/db --serve");
+        assert_eq!(b.oom_score_adj, -500);
+
+        assert_eq!(scenario.expectations.len(), 3);
+        assert_eq!(scenario.expectations[2], Expectation::NoOom);
+    }
+
+    #[test]
+    fn test_parse_scenario_errors() {
+        assert!(Scenario::parse("swap 1GiB\n").is_err());
+        assert!(Scenario::parse("memory 1GiB\nprocess A size=1\n").is_err());
+        assert!(Scenario::parse("memory 1GiB\nexpect Z untouched\n").is_err());
+        assert!(Scenario::parse("memory 1GiB\nprocess A parent=Z\n").is_err());
+        assert!(Scenario::parse("memory 1GiB\nfly away\n").is_err());
+    }
+
+    #[test]
+    fn test_rss_at() {
+        let process = SimProcess {
+            name: "A".to_string(),
+            pid: 1,
+            parent: String::new(),
+            cmdline: "A".to_string(),
+            exe: String::new(),
+            cgroup: String::new(),
+            uid: 0,
+            user: String::new(),
+            oom_score_adj: 0,
+            rss_kb: 1000,
+            growth_kb_per_sec: 100,
+            grow_from: Duration::from_secs(5),
+            start: Duration::from_secs(2),
+            age: Duration::ZERO,
+            max_rss_kb: Some(1500),
+        };
+
+        assert_eq!(process.rss_at(Duration::from_secs(1)), None);
+        assert_eq!(process.rss_at(Duration::from_secs(3)), Some(1000));
+        assert_eq!(process.rss_at(Duration::from_secs(7)), Some(1200));
+        assert_eq!(process.rss_at(Duration::from_secs(60)), Some(1500));
+    }
+}
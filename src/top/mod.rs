@@ -0,0 +1,107 @@
+// Interactive top-style view of memory state and kill candidates
+
+mod view;
+
+pub use view::{render, threshold_gaps, LineStyle, Snapshot, ThresholdGap};
+
+use crate::config::Config;
+use crate::daemon::DaemonService;
+use crate::monitor::ProcfsSource;
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Color, Print, ResetColor, SetAttribute, SetForegroundColor};
+use crossterm::{cursor, execute, queue, terminal};
+use std::io::{stdout, Stdout, Write};
+use std::time::{Duration, Instant};
+
+/// Raw mode and the alternate screen, restored when dropped
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter() -> Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(Self)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = execute!(stdout(), cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Show memory state and the candidate ranking of the live system with the
+/// given configuration, refreshed every check interval, until q is pressed
+pub fn run_top(config: Config) -> Result<()> {
+    let service = DaemonService::new(config.clone());
+    let _guard = TerminalGuard::enter()?;
+    let mut out = stdout();
+
+    loop {
+        let (width, height) = terminal::size()?;
+        let snapshot = Snapshot::capture(&config, &ProcfsSource, &service)?;
+        draw(
+            &mut out,
+            &render(&snapshot, usize::from(width), usize::from(height)),
+        )?;
+
+        if wait_for_quit(config.check_interval)? {
+            return Ok(());
+        }
+    }
+}
+
+fn draw(out: &mut Stdout, lines: &[(LineStyle, String)]) -> Result<()> {
+    queue!(out, terminal::Clear(terminal::ClearType::All))?;
+
+    for (row, (style, line)) in lines.iter().enumerate() {
+        queue!(out, cursor::MoveTo(0, row as u16))?;
+        match style {
+            LineStyle::Normal => {}
+            LineStyle::Header => queue!(out, SetAttribute(Attribute::Bold))?,
+            LineStyle::Next => queue!(
+                out,
+                SetForegroundColor(Color::Yellow),
+                SetAttribute(Attribute::Bold)
+            )?,
+            LineStyle::Victim => queue!(
+                out,
+                SetForegroundColor(Color::Red),
+                SetAttribute(Attribute::Reverse)
+            )?,
+            LineStyle::Dim => queue!(out, SetForegroundColor(Color::DarkGrey))?,
+        }
+        queue!(out, Print(line), SetAttribute(Attribute::Reset), ResetColor)?;
+    }
+
+    out.flush()?;
+    Ok(())
+}
+
+/// Wait up to `interval` for input; true if the user asked to quit, false
+/// on timeout or when the screen needs redrawing
+fn wait_for_quit(interval: Duration) -> Result<bool> {
+    let deadline = Instant::now() + interval;
+
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() || !event::poll(remaining)? {
+            return Ok(false);
+        }
+
+        match event::read()? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(true),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    return Ok(true);
+                }
+                // Any other key refreshes immediately
+                _ => return Ok(false),
+            },
+            Event::Resize(..) => return Ok(false),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,327 @@
+// Screen contents of the top command, independent of the terminal
+
+use crate::config::{Config, Trigger};
+use crate::daemon::{DaemonService, Explanation, Verdict};
+use crate::killer::KillStrategy;
+use crate::monitor::{MemInfo, MemoryPressure, ProcessSource, SystemSource};
+use crate::sanitize_for_log;
+use anyhow::{Context, Result};
+
+/// Distance of one threshold from being crossed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThresholdGap {
+    /// "mem warn", "swap kill", ...
+    pub label: &'static str,
+    /// Threshold as configured, e.g. "10.0%" or "512.00 MiB"
+    pub limit: String,
+    /// Threshold in KiB
+    pub limit_kb: u64,
+    /// Available (or free) KiB above the threshold, negative once crossed
+    pub headroom_kb: i64,
+}
+
+impl ThresholdGap {
+    fn new(label: &'static str, percent: f64, size_kb: Option<u64>, total: u64, free: u64) -> Self {
+        let (limit, limit_kb) = size_kb.map_or_else(
+            || {
+                let kb = (total as f64 * percent / 100.0) as u64;
+                (format!("{percent:.1}%"), kb)
+            },
+            |kb| (format_kb(kb), kb),
+        );
+        Self {
+            label,
+            limit,
+            limit_kb,
+            headroom_kb: free as i64 - limit_kb as i64,
+        }
+    }
+
+    pub const fn crossed(&self) -> bool {
+        self.headroom_kb < 0
+    }
+}
+
+impl std::fmt::Display for ThresholdGap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let status = if self.crossed() {
+            format!("crossed by {}", format_kb(self.headroom_kb.unsigned_abs()))
+        } else {
+            format!("{} to go", format_kb(self.headroom_kb.unsigned_abs()))
+        };
+        write!(f, "{:<9} < {:<10} {status}", self.label, self.limit)
+    }
+}
+
+/// Threshold distances in the daemon's order: warn, then kill
+pub fn threshold_gaps(config: &Config, meminfo: &MemInfo) -> Vec<ThresholdGap> {
+    let mem = |label, percent, size| {
+        ThresholdGap::new(
+            label,
+            percent,
+            size,
+            meminfo.mem_total,
+            meminfo.mem_available,
+        )
+    };
+    let swap = |label, percent, size| {
+        ThresholdGap::new(label, percent, size, meminfo.swap_total, meminfo.swap_free)
+    };
+
+    vec![
+        mem("mem warn", config.mem_threshold_warn, config.mem_size_warn),
+        swap(
+            "swap warn",
+            config.swap_threshold_warn,
+            config.swap_size_warn,
+        ),
+        mem("mem kill", config.mem_threshold_kill, config.mem_size_kill),
+        swap(
+            "swap kill",
+            config.swap_threshold_kill,
+            config.swap_size_kill,
+        ),
+    ]
+}
+
+/// Everything shown on one refresh
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub meminfo: MemInfo,
+    pub pressure: Option<MemoryPressure>,
+    /// Threshold currently crossed, if any
+    pub breached: Option<KillStrategy>,
+    pub gaps: Vec<ThresholdGap>,
+    /// How the thresholds combine
+    pub trigger: Trigger,
+    /// Candidate ranking followed by ignored processes
+    pub ranking: Vec<Explanation>,
+}
+
+impl Snapshot {
+    /// Read the current state through the daemon's own threshold checks and
+    /// victim ranking
+    pub fn capture<S: SystemSource, P: ProcessSource>(
+        config: &Config,
+        system: &S,
+        service: &DaemonService<S, P>,
+    ) -> Result<Self> {
+        let meminfo = system.meminfo().context("Failed to read memory info")?;
+        Ok(Self {
+            pressure: system.pressure(),
+            breached: service.breached_threshold(&meminfo),
+            gaps: threshold_gaps(config, &meminfo),
+            trigger: config.trigger.clone(),
+            ranking: service.explain()?,
+            meminfo,
+        })
+    }
+}
+
+/// How a line is drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineStyle {
+    Normal,
+    Header,
+    /// The process that would be chosen next
+    Next,
+    /// The process that would be acted on right now
+    Victim,
+    Dim,
+}
+
+/// Lay out a snapshot on a screen of `width` x `height` characters
+pub fn render(snapshot: &Snapshot, width: usize, height: usize) -> Vec<(LineStyle, String)> {
+    let m = &snapshot.meminfo;
+    let mut lines = vec![
+        (
+            LineStyle::Header,
+            "oom_guard top - q to quit (preview, nothing is killed)".to_string(),
+        ),
+        (
+            LineStyle::Normal,
+            format!(
+                "Memory: {} of {} available ({:.1}%)   Swap: {} of {} free ({:.1}%)",
+                format_kb(m.mem_available),
+                format_kb(m.mem_total),
+                m.mem_available_percent(),
+                format_kb(m.swap_free),
+                format_kb(m.swap_total),
+                m.swap_free_percent()
+            ),
+        ),
+        (
+            LineStyle::Normal,
+            snapshot
+                .pressure
+                .map_or_else(|| "PSI: unavailable".to_string(), |p| p.to_string()),
+        ),
+        (
+            LineStyle::Normal,
+            match &snapshot.trigger {
+                Trigger::All => "Thresholds (both memory and swap must be crossed):".to_string(),
+                Trigger::Any => "Thresholds (memory or swap must be crossed):".to_string(),
+                Trigger::MemOnly => "Thresholds (memory must be crossed):".to_string(),
+                Trigger::Expr(expr) => format!("Thresholds (trigger: {expr}):"),
+            },
+        ),
+    ];
+    for gap in &snapshot.gaps {
+        let style = if gap.crossed() {
+            LineStyle::Victim
+        } else {
+            LineStyle::Normal
+        };
+        lines.push((style, format!("  {gap}")));
+    }
+
+    let status = match snapshot.breached {
+        Some(KillStrategy::Forceful) => {
+            "Status: KILL threshold crossed - top candidate would be killed now"
+        }
+        Some(_) => "Status: WARN threshold crossed - top candidate would be acted on now",
+        None => "Status: OK - no threshold crossed",
+    };
+    lines.push((LineStyle::Normal, status.to_string()));
+    lines.push((LineStyle::Normal, String::new()));
+    lines.push((
+        LineStyle::Header,
+        format!(
+            "{:>4}  {:>7}  {:>6}  {:>10}  {:>5}  {:>5}  {:<9}  {:<16}  {}",
+            "RANK", "PID", "UID", "RSS", "OOM", "ADJ", "VERDICT", "NAME", "REASON"
+        ),
+    ));
+
+    let rows = height.saturating_sub(lines.len());
+    for e in snapshot.ranking.iter().take(rows) {
+        let style = match (e.rank, e.verdict) {
+            (Some(1), _) if snapshot.breached.is_some() => LineStyle::Victim,
+            (Some(1), _) => LineStyle::Next,
+            (_, Verdict::Ignored) => LineStyle::Dim,
+            _ => LineStyle::Normal,
+        };
+        let rank = e.rank.map_or_else(|| "-".to_string(), |r| r.to_string());
+        lines.push((
+            style,
+            format!(
+                "{:>4}  {:>7}  {:>6}  {:>10}  {:>5}  {:>5}  {:<9}  {:<16}  {}",
+                rank,
+                e.process.pid,
+                e.process.uid,
+                format_kb(e.process.rss_kb),
+                e.process.oom_score,
+                e.process.oom_score_adj,
+                e.verdict,
+                sanitize_for_log(&e.process.name),
+                sanitize_for_log(&e.reason)
+            ),
+        ));
+    }
+
+    lines.truncate(height);
+    for (_, line) in &mut lines {
+        if let Some((index, _)) = line.char_indices().nth(width) {
+            line.truncate(index);
+        }
+    }
+    lines
+}
+
+fn format_kb(kb: u64) -> String {
+    MemInfo::format_size(kb)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monitor::ProcessInfo;
+
+    fn meminfo(available_percent: u64, swap_free_percent: u64) -> MemInfo {
+        MemInfo {
+            mem_total: 1_000_000,
+            mem_available: available_percent * 10_000,
+            swap_total: 500_000,
+            swap_free: swap_free_percent * 5_000,
+        }
+    }
+
+    fn snapshot(breached: Option<KillStrategy>) -> Snapshot {
+        let explanation = |pid, rank, verdict| Explanation {
+            process: ProcessInfo {
+                pid,
+                name: format!("app-{pid}"),
+                cmdline: String::new(),
+                rss_kb: 2048,
+                oom_score: 500,
+                oom_score_adj: 0,
+                uid: 1000,
+                state: 'S',
+                is_zombie: false,
+                ..Default::default()
+            },
+            rank,
+            verdict,
+            reason: "candidate; oom_score 500".to_string(),
+        };
+        let meminfo = meminfo(20, 50);
+        Snapshot {
+            meminfo,
+            pressure: None,
+            breached,
+            gaps: threshold_gaps(&Config::default(), &meminfo),
+            trigger: Trigger::All,
+            ranking: vec![
+                explanation(10, Some(1), Verdict::Candidate),
+                explanation(11, Some(2), Verdict::Candidate),
+                explanation(1, None, Verdict::Ignored),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_threshold_gaps() {
+        let config = Config {
+            swap_size_kill: Some(100_000),
+            ..Config::default()
+        };
+        let gaps = threshold_gaps(&config, &meminfo(8, 50));
+
+        // Default warn threshold 10% of 1,000,000 KiB, 80,000 KiB available
+        assert_eq!(gaps[0].label, "mem warn");
+        assert_eq!(gaps[0].limit, "10.0%");
+        assert_eq!(gaps[0].headroom_kb, -20_000);
+        assert!(gaps[0].crossed());
+
+        // Absolute swap kill threshold, 250,000 KiB free
+        assert_eq!(gaps[3].limit_kb, 100_000);
+        assert_eq!(gaps[3].headroom_kb, 150_000);
+        assert!(!gaps[3].crossed());
+    }
+
+    #[test]
+    fn test_render_highlights_next_victim() {
+        let lines = render(&snapshot(None), 200, 50);
+        let row = lines.iter().find(|(_, l)| l.contains("app-10")).unwrap();
+        assert_eq!(row.0, LineStyle::Next);
+        assert!(lines
+            .iter()
+            .any(|(_, l)| l == "Status: OK - no threshold crossed"));
+
+        let ignored = lines.iter().find(|(_, l)| l.contains("app-1 ")).unwrap();
+        assert_eq!(ignored.0, LineStyle::Dim);
+    }
+
+    #[test]
+    fn test_render_highlights_victim_when_breached() {
+        let lines = render(&snapshot(Some(KillStrategy::Graceful)), 200, 50);
+        let row = lines.iter().find(|(_, l)| l.contains("app-10")).unwrap();
+        assert_eq!(row.0, LineStyle::Victim);
+    }
+
+    #[test]
+    fn test_render_fits_screen() {
+        let lines = render(&snapshot(None), 40, 12);
+        assert_eq!(lines.len(), 12);
+        assert!(lines.iter().all(|(_, l)| l.chars().count() <= 40));
+    }
+}